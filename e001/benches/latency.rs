@@ -6,7 +6,7 @@ use e001::btree::BTreeBook;
 use e001::hashmap::HashMapBook;
 use e001::hybrid::HybridBook;
 
-fn setup_book<T: OrderBook>(mut book: T) -> T {
+fn setup_book<T: OrderBook<Price = Decimal, Qty = Decimal>>(mut book: T) -> T {
     for i in 0..1000 {
         book.insert(Side::Bid, Decimal::from(1000 - i), Decimal::from(i + 1));
     }
@@ -14,7 +14,11 @@ fn setup_book<T: OrderBook>(mut book: T) -> T {
 }
 
 // Insert benchmark
-fn bench_insert<T: OrderBook>(c: &mut Criterion, name: &str, mut make_book: impl FnMut() -> T) {
+fn bench_insert<T: OrderBook<Price = Decimal, Qty = Decimal>>(
+    c: &mut Criterion,
+    name: &str,
+    mut make_book: impl FnMut() -> T,
+) {
     let quantity = Decimal::from(10);
 
     c.bench_function(&format!("{} insert", name), |b| {
@@ -33,7 +37,7 @@ fn bench_insert<T: OrderBook>(c: &mut Criterion, name: &str, mut make_book: impl
 }
 
 // Modify benchmark
-fn bench_modify<T: OrderBook + Clone>(
+fn bench_modify<T: OrderBook<Price = Decimal, Qty = Decimal> + Clone>(
     c: &mut Criterion,
     name: &str,
     mut make_book: impl FnMut() -> T,
@@ -57,7 +61,11 @@ fn bench_modify<T: OrderBook + Clone>(
 }
 
 // Delete benchmark
-fn bench_delete<T: OrderBook>(c: &mut Criterion, name: &str, mut make_book: impl FnMut() -> T) {
+fn bench_delete<T: OrderBook<Price = Decimal, Qty = Decimal>>(
+    c: &mut Criterion,
+    name: &str,
+    mut make_book: impl FnMut() -> T,
+) {
     c.bench_function(&format!("{} delete", name), |b| {
         b.iter_batched(
             || setup_book(make_book()),
@@ -74,7 +82,11 @@ fn bench_delete<T: OrderBook>(c: &mut Criterion, name: &str, mut make_book: impl
 }
 
 // Top benchmark
-fn bench_top<T: OrderBook>(c: &mut Criterion, name: &str, mut make_book: impl FnMut() -> T) {
+fn bench_top<T: OrderBook<Price = Decimal, Qty = Decimal>>(
+    c: &mut Criterion,
+    name: &str,
+    mut make_book: impl FnMut() -> T,
+) {
     c.bench_function(&format!("{} top", name), |b| {
         b.iter_batched(
             || setup_book(make_book()),
@@ -90,7 +102,11 @@ fn bench_top<T: OrderBook>(c: &mut Criterion, name: &str, mut make_book: impl Fn
 }
 
 // Bids benchmark
-fn bench_bids<T: OrderBook>(c: &mut Criterion, name: &str, mut make_book: impl FnMut() -> T) {
+fn bench_bids<T: OrderBook<Price = Decimal, Qty = Decimal>>(
+    c: &mut Criterion,
+    name: &str,
+    mut make_book: impl FnMut() -> T,
+) {
     c.bench_function(&format!("{} bids", name), |b| {
         b.iter_batched(
             || setup_book(make_book()),
@@ -106,7 +122,7 @@ fn bench_bids<T: OrderBook>(c: &mut Criterion, name: &str, mut make_book: impl F
     });
 }
 
-fn bench_all<T: OrderBook + Clone>(
+fn bench_all<T: OrderBook<Price = Decimal, Qty = Decimal> + Clone>(
     c: &mut Criterion,
     name: &str,
     mut make_book: impl FnMut() -> T,
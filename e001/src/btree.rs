@@ -1,14 +1,20 @@
-use crate::orderbook::{OrderBook, Side};
+use crate::orderbook::{Level, OrderBook, Side, Top};
 use rust_decimal::Decimal;
 use std::collections::BTreeMap;
 
 #[derive(Clone)]
-pub struct BTreeBook {
-    asks: BTreeMap<Decimal, Decimal>,
-    bids: BTreeMap<Decimal, Decimal>,
+pub struct BTreeBook<P = Decimal, Q = Decimal> {
+    asks: BTreeMap<P, Q>,
+    bids: BTreeMap<P, Q>,
 }
 
-impl BTreeBook {
+impl<P: Ord, Q> Default for BTreeBook<P, Q> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: Ord, Q> BTreeBook<P, Q> {
     pub fn new() -> Self {
         Self {
             asks: BTreeMap::new(),
@@ -16,7 +22,7 @@ impl BTreeBook {
         }
     }
 
-    fn get_map(&mut self, side: Side) -> &mut BTreeMap<Decimal, Decimal> {
+    fn get_map(&mut self, side: Side) -> &mut BTreeMap<P, Q> {
         match side {
             Side::Bid => &mut self.bids,
             Side::Ask => &mut self.asks,
@@ -24,19 +30,22 @@ impl BTreeBook {
     }
 }
 
-impl OrderBook for BTreeBook {
+impl<P: Ord, Q> OrderBook for BTreeBook<P, Q> {
+    type Price = P;
+    type Qty = Q;
+
     #[inline]
-    fn insert(&mut self, side: Side, price: Decimal, quantity: Decimal) {
+    fn insert(&mut self, side: Side, price: P, quantity: Q) {
         self.get_map(side).insert(price, quantity);
     }
 
     #[inline]
-    fn delete(&mut self, side: Side, price: Decimal) {
+    fn delete(&mut self, side: Side, price: P) {
         self.get_map(side).remove(&price);
     }
 
     #[inline]
-    fn top(&self) -> (Option<(&Decimal, &Decimal)>, Option<(&Decimal, &Decimal)>) {
+    fn top(&self) -> Top<'_, P, Q> {
         // B-Tree is sorted in descending order
         // Get the last element for the highest bid
         let bid = self.bids.iter().next_back();
@@ -47,13 +56,13 @@ impl OrderBook for BTreeBook {
     }
 
     #[inline]
-    fn bids(&self) -> impl Iterator<Item = (&Decimal, &Decimal)> {
+    fn bids(&self) -> impl Iterator<Item = Level<'_, P, Q>> {
         // Reverse the iterator to get the highest bid first
         self.bids.iter().rev()
     }
 
     #[inline]
-    fn asks(&self) -> impl Iterator<Item = (&Decimal, &Decimal)> {
+    fn asks(&self) -> impl Iterator<Item = Level<'_, P, Q>> {
         // Get the lowest ask first
         self.asks.iter()
     }
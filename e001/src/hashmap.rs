@@ -1,14 +1,21 @@
-use crate::orderbook::{OrderBook, Side};
+use crate::orderbook::{Level, OrderBook, Side, Top};
 use hashbrown::HashMap;
 use rust_decimal::Decimal;
+use std::hash::Hash;
 
 #[derive(Clone)]
-pub struct HashMapBook {
-    asks: HashMap<Decimal, Decimal>,
-    bids: HashMap<Decimal, Decimal>,
+pub struct HashMapBook<P = Decimal, Q = Decimal> {
+    asks: HashMap<P, Q>,
+    bids: HashMap<P, Q>,
 }
 
-impl HashMapBook {
+impl<P: Ord + Hash, Q> Default for HashMapBook<P, Q> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: Ord + Hash, Q> HashMapBook<P, Q> {
     pub fn new() -> Self {
         Self {
             asks: HashMap::new(),
@@ -16,7 +23,7 @@ impl HashMapBook {
         }
     }
 
-    fn get_map(&mut self, side: Side) -> &mut HashMap<Decimal, Decimal> {
+    fn get_map(&mut self, side: Side) -> &mut HashMap<P, Q> {
         match side {
             Side::Bid => &mut self.bids,
             Side::Ask => &mut self.asks,
@@ -24,19 +31,22 @@ impl HashMapBook {
     }
 }
 
-impl OrderBook for HashMapBook {
+impl<P: Ord + Hash, Q> OrderBook for HashMapBook<P, Q> {
+    type Price = P;
+    type Qty = Q;
+
     #[inline]
-    fn insert(&mut self, side: Side, price: Decimal, quantity: Decimal) {
+    fn insert(&mut self, side: Side, price: P, quantity: Q) {
         self.get_map(side).insert(price, quantity);
     }
 
     #[inline]
-    fn delete(&mut self, side: Side, price: Decimal) {
+    fn delete(&mut self, side: Side, price: P) {
         self.get_map(side).remove(&price);
     }
 
     #[inline]
-    fn top(&self) -> (Option<(&Decimal, &Decimal)>, Option<(&Decimal, &Decimal)>) {
+    fn top(&self) -> Top<'_, P, Q> {
         let bid = self.bids.iter().max_by_key(|(price, _)| *price);
         let ask = self.asks.iter().min_by_key(|(price, _)| *price);
 
@@ -44,14 +54,14 @@ impl OrderBook for HashMapBook {
     }
 
     #[inline]
-    fn bids(&self) -> impl Iterator<Item = (&Decimal, &Decimal)> {
+    fn bids(&self) -> impl Iterator<Item = Level<'_, P, Q>> {
         let mut bids: Vec<_> = self.bids.iter().collect();
         bids.sort_unstable_by(|a, b| b.0.cmp(a.0)); // descending
         bids.into_iter()
     }
 
     #[inline]
-    fn asks(&self) -> impl Iterator<Item = (&Decimal, &Decimal)> {
+    fn asks(&self) -> impl Iterator<Item = Level<'_, P, Q>> {
         let mut asks: Vec<_> = self.asks.iter().collect();
         asks.sort_unstable_by(|a, b| a.0.cmp(b.0)); // ascending
         asks.into_iter()
@@ -1,20 +1,27 @@
-use crate::orderbook::{OrderBook, Side};
+use crate::orderbook::{Level, OrderBook, Side, Top};
 use hashbrown::HashMap;
 use rust_decimal::Decimal;
 use std::collections::BTreeMap;
+use std::hash::Hash;
 use std::ptr::NonNull;
 
 #[derive(Clone)]
-pub struct HybridBook {
-    asks: BTreeMap<Decimal, NonNull<Decimal>>,
-    bids: BTreeMap<Decimal, NonNull<Decimal>>,
-    askmap: HashMap<Decimal, NonNull<Decimal>>,
-    bidmap: HashMap<Decimal, NonNull<Decimal>>,
-    topbid: Option<(Decimal, NonNull<Decimal>)>,
-    topask: Option<(Decimal, NonNull<Decimal>)>,
+pub struct HybridBook<P = Decimal, Q = Decimal> {
+    asks: BTreeMap<P, NonNull<Q>>,
+    bids: BTreeMap<P, NonNull<Q>>,
+    askmap: HashMap<P, NonNull<Q>>,
+    bidmap: HashMap<P, NonNull<Q>>,
+    topbid: Option<(P, NonNull<Q>)>,
+    topask: Option<(P, NonNull<Q>)>,
 }
 
-impl HybridBook {
+impl<P: Ord + Hash + Copy, Q> Default for HybridBook<P, Q> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: Ord + Hash + Copy, Q> HybridBook<P, Q> {
     pub fn new() -> Self {
         Self {
             asks: BTreeMap::new(),
@@ -27,9 +34,12 @@ impl HybridBook {
     }
 }
 
-impl OrderBook for HybridBook {
+impl<P: Ord + Hash + Copy, Q> OrderBook for HybridBook<P, Q> {
+    type Price = P;
+    type Qty = Q;
+
     #[inline]
-    fn insert(&mut self, side: Side, price: Decimal, quantity: Decimal) {
+    fn insert(&mut self, side: Side, price: P, quantity: Q) {
         let map = match side {
             Side::Bid => &mut self.bidmap,
             Side::Ask => &mut self.askmap,
@@ -68,7 +78,8 @@ impl OrderBook for HybridBook {
     }
 
     #[inline]
-    fn delete(&mut self, side: Side, price: Decimal) {
+    #[allow(clippy::collapsible_if)]
+    fn delete(&mut self, side: Side, price: P) {
         let map = match side {
             Side::Bid => &mut self.bidmap,
             Side::Ask => &mut self.askmap,
@@ -97,7 +108,7 @@ impl OrderBook for HybridBook {
     }
 
     #[inline]
-    fn top(&self) -> (Option<(&Decimal, &Decimal)>, Option<(&Decimal, &Decimal)>) {
+    fn top(&self) -> Top<'_, P, Q> {
         let bid = self
             .topbid
             .as_ref()
@@ -112,7 +123,7 @@ impl OrderBook for HybridBook {
     }
 
     #[inline]
-    fn bids(&self) -> impl Iterator<Item = (&Decimal, &Decimal)> {
+    fn bids(&self) -> impl Iterator<Item = Level<'_, P, Q>> {
         self.bids
             .iter()
             .rev()
@@ -120,7 +131,7 @@ impl OrderBook for HybridBook {
     }
 
     #[inline]
-    fn asks(&self) -> impl Iterator<Item = (&Decimal, &Decimal)> {
+    fn asks(&self) -> impl Iterator<Item = Level<'_, P, Q>> {
         self.asks
             .iter()
             .map(|(p, ptr)| (p, unsafe { &*ptr.as_ptr() }))
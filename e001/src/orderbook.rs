@@ -1,19 +1,27 @@
-use rust_decimal::Decimal;
 use std::hash::Hash;
 
-#[derive(Clone, Hash, Eq, PartialEq)]
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
 pub enum Side {
     Bid,
     Ask,
 }
 
+/// A price level as handed out by the books
+pub type Level<'a, P, Q> = (&'a P, &'a Q);
+
+/// Best bid and best ask
+pub type Top<'a, P, Q> = (Option<Level<'a, P, Q>>, Option<Level<'a, P, Q>>);
+
 pub trait OrderBook {
-    fn insert(&mut self, side: Side, price: Decimal, quantity: Decimal);
-    fn delete(&mut self, side: Side, price: Decimal);
+    type Price;
+    type Qty;
 
-    fn top(&self) -> (Option<(&Decimal, &Decimal)>, Option<(&Decimal, &Decimal)>);
-    fn bids(&self) -> impl Iterator<Item = (&Decimal, &Decimal)>;
-    fn asks(&self) -> impl Iterator<Item = (&Decimal, &Decimal)>;
+    fn insert(&mut self, side: Side, price: Self::Price, quantity: Self::Qty);
+    fn delete(&mut self, side: Side, price: Self::Price);
+
+    fn top(&self) -> Top<'_, Self::Price, Self::Qty>;
+    fn bids(&self) -> impl Iterator<Item = Level<'_, Self::Price, Self::Qty>>;
+    fn asks(&self) -> impl Iterator<Item = Level<'_, Self::Price, Self::Qty>>;
 }
 
 #[cfg(test)]
@@ -21,8 +29,11 @@ pub mod tests {
     use super::*;
     use rust_decimal::Decimal;
 
+    pub trait DecimalBook: OrderBook<Price = Decimal, Qty = Decimal> {}
+    impl<T: OrderBook<Price = Decimal, Qty = Decimal>> DecimalBook for T {}
+
     // The constructor is a function/closure that returns a new instance of the book
-    fn test_insert<T: OrderBook>(mut new_book: impl FnMut() -> T) {
+    fn test_insert<T: DecimalBook>(mut new_book: impl FnMut() -> T) {
         let mut book = new_book();
 
         book.insert(Side::Bid, Decimal::from(100), Decimal::from(10));
@@ -32,7 +43,7 @@ pub mod tests {
         );
     }
 
-    fn test_modify<T: OrderBook>(mut new_book: impl FnMut() -> T) {
+    fn test_modify<T: DecimalBook>(mut new_book: impl FnMut() -> T) {
         let mut book = new_book();
         book.insert(Side::Bid, Decimal::from(100), Decimal::from(10));
         book.insert(Side::Bid, Decimal::from(100), Decimal::from(20));
@@ -43,7 +54,7 @@ pub mod tests {
         );
     }
 
-    fn test_delete<T: OrderBook>(mut new_book: impl FnMut() -> T) {
+    fn test_delete<T: DecimalBook>(mut new_book: impl FnMut() -> T) {
         let mut book = new_book();
         book.insert(Side::Bid, Decimal::from(100), Decimal::from(10));
         book.delete(Side::Bid, Decimal::from(100));
@@ -51,7 +62,7 @@ pub mod tests {
         assert_eq!(book.top(), (None, None));
     }
 
-    fn test_top<T: OrderBook>(mut new_book: impl FnMut() -> T) {
+    fn test_top<T: DecimalBook>(mut new_book: impl FnMut() -> T) {
         let mut book = new_book();
 
         book.insert(Side::Ask, Decimal::from(140), Decimal::from(20));
@@ -82,7 +93,7 @@ pub mod tests {
         assert_eq!(book.top(), (None, None));
     }
 
-    fn test_bids<T: OrderBook>(mut new_book: impl FnMut() -> T) {
+    fn test_bids<T: DecimalBook>(mut new_book: impl FnMut() -> T) {
         let mut book = new_book();
         book.insert(Side::Bid, Decimal::from(100), Decimal::from(10));
         book.insert(Side::Bid, Decimal::from(90), Decimal::from(20));
@@ -99,7 +110,7 @@ pub mod tests {
         );
     }
 
-    fn test_asks<T: OrderBook>(mut new_book: impl FnMut() -> T) {
+    fn test_asks<T: DecimalBook>(mut new_book: impl FnMut() -> T) {
         let mut book = new_book();
         book.insert(Side::Ask, Decimal::from(100), Decimal::from(10));
         book.insert(Side::Ask, Decimal::from(90), Decimal::from(20));
@@ -116,7 +127,7 @@ pub mod tests {
         );
     }
 
-    pub fn test_all<T: OrderBook>(mut new_book: impl FnMut() -> T) {
+    pub fn test_all<T: DecimalBook>(mut new_book: impl FnMut() -> T) {
         test_insert(&mut new_book);
         test_modify(&mut new_book);
         test_delete(&mut new_book);
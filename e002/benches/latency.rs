@@ -1,5 +1,11 @@
 use criterion::{Criterion, criterion_group, criterion_main};
+use e001::hybrid::HybridBook;
+use e001::orderbook::{OrderBook, Side};
+use e002::apply::apply_sonic;
+use e002::batch::{Kernel, parse_levels, parse_levels_with};
+use e002::binance::Depth;
 use e002::fp::Fp;
+use e002::narrow::{Fp32, Fp64};
 use rust_decimal::Decimal;
 use serde::Deserialize;
 use std::hint::black_box;
@@ -57,8 +63,26 @@ pub struct OrderBookV3 {
     pub asks: Vec<(Decimal, Decimal)>,
 }
 
+// The fastest schema, now the library one
+pub type OrderBookV4 = Depth<2, 3>;
+
+#[derive(Debug, Deserialize)]
+pub struct OrderBookV5 {
+    #[serde(rename = "lastUpdateId")]
+    pub last_update_id: u64,
+
+    #[serde(rename = "E")]
+    pub event_time: u64,
+
+    #[serde(rename = "T")]
+    pub tx_time: u64,
+
+    pub bids: Vec<(Fp64<2>, Fp64<3>)>,
+    pub asks: Vec<(Fp64<2>, Fp64<3>)>,
+}
+
 #[derive(Debug, Deserialize)]
-pub struct OrderBookV4 {
+pub struct OrderBookV6 {
     #[serde(rename = "lastUpdateId")]
     pub last_update_id: u64,
 
@@ -68,11 +92,12 @@ pub struct OrderBookV4 {
     #[serde(rename = "T")]
     pub tx_time: u64,
 
-    pub bids: Vec<(Fp<2>, Fp<3>)>,
-    pub asks: Vec<(Fp<2>, Fp<3>)>,
+    pub bids: Vec<(Fp32<2>, Fp32<3>)>,
+    pub asks: Vec<(Fp32<2>, Fp32<3>)>,
 }
 
 // // Insert benchmark
+#[allow(clippy::needless_borrow)]
 fn bench_serde(c: &mut Criterion) {
     c.bench_function("serde_v1", |b| {
         b.iter(|| {
@@ -103,6 +128,7 @@ fn bench_serde(c: &mut Criterion) {
     });
 }
 
+#[allow(clippy::needless_borrow)]
 fn bench_sonic(c: &mut Criterion) {
     c.bench_function("sonic_v1", |b| {
         b.iter(|| {
@@ -167,5 +193,134 @@ fn bench_simd_json(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, bench_serde, bench_sonic, bench_simd_json);
+// The hand-written scanner, against serde_v4, sonic_v4 and simd_json_v4
+fn bench_scan(c: &mut Criterion) {
+    c.bench_function("scan_v4", |b| {
+        b.iter(|| {
+            let res = OrderBookV4::from_scan(TEST_DATA).unwrap();
+            black_box(res);
+        });
+    });
+
+    // Same, decoding into one book so the level vectors are reused
+    let mut book = OrderBookV4::from_scan(TEST_DATA).unwrap();
+    c.bench_function("scan_v4_reuse", |b| {
+        b.iter(|| {
+            book.scan_from(TEST_DATA).unwrap();
+            black_box(&book);
+        });
+    });
+}
+
+// Decoding into Vecs and then inserting, against streaming the levels
+// straight into the book. Reapplying the snapshot overwrites the same levels
+fn bench_apply(c: &mut Criterion) {
+    let mut book = HybridBook::<Fp<2>, Fp<3>>::new();
+    c.bench_function("sonic_v4_insert", |b| {
+        b.iter(|| {
+            let res: OrderBookV4 = sonic_rs::from_slice(TEST_DATA).unwrap();
+            for (price, qty) in res.bids {
+                book.insert(Side::Bid, price, qty);
+            }
+            for (price, qty) in res.asks {
+                book.insert(Side::Ask, price, qty);
+            }
+            black_box(&book);
+        });
+    });
+
+    let mut book = HybridBook::<Fp<2>, Fp<3>>::new();
+    c.bench_function("sonic_apply", |b| {
+        b.iter(|| {
+            apply_sonic(TEST_DATA, &mut book).unwrap();
+            black_box(&book);
+        });
+    });
+}
+
+// Same snapshot with each backing width: decode, then total the depth on
+// both sides and the price range so the arithmetic is part of the cost
+macro_rules! bench_width {
+    ($c:expr, $name:expr, $book:ty, $qty:ty) => {
+        $c.bench_function($name, |b| {
+            b.iter(|| {
+                let res: $book = sonic_rs::from_slice(TEST_DATA).unwrap();
+                let depth = res.bids.iter().chain(&res.asks).map(|l| l.1).sum::<$qty>();
+                let range = res.asks[res.asks.len() - 1].0 - res.bids[res.bids.len() - 1].0;
+                black_box((depth, range));
+            });
+        });
+    };
+}
+
+fn bench_widths(c: &mut Criterion) {
+    bench_width!(c, "width_fp128", OrderBookV4, Fp<3>);
+    bench_width!(c, "width_fp64", OrderBookV5, Fp64<3>);
+    bench_width!(c, "width_fp32", OrderBookV6, Fp32<3>);
+}
+
+// Borrowed strings from the v2 schema, then every level parsed in one batch
+// into buffers that are reused across messages
+fn bench_batch(c: &mut Criterion) {
+    let mut bids = vec![(Fp::<2>::ZERO, Fp::<3>::ZERO); 64];
+    let mut asks = vec![(Fp::<2>::ZERO, Fp::<3>::ZERO); 64];
+
+    c.bench_function("serde_v2_batch", |b| {
+        b.iter(|| {
+            let res: OrderBookV2 = serde_json::from_slice(TEST_DATA).unwrap();
+            parse_levels(&res.bids, &mut bids[..res.bids.len()]).unwrap();
+            parse_levels(&res.asks, &mut asks[..res.asks.len()]).unwrap();
+            black_box((&bids, &asks));
+        });
+    });
+
+    c.bench_function("sonic_v2_batch", |b| {
+        b.iter(|| {
+            let res: OrderBookV2 = sonic_rs::from_slice(TEST_DATA).unwrap();
+            parse_levels(&res.bids, &mut bids[..res.bids.len()]).unwrap();
+            parse_levels(&res.asks, &mut asks[..res.asks.len()]).unwrap();
+            black_box((&bids, &asks));
+        });
+    });
+
+    c.bench_function("simd_json_v2_batch", |b| {
+        b.iter(|| {
+            let mut data = TEST_DATA.to_vec();
+            let res: OrderBookV2 = simd_json::from_slice(&mut data).unwrap();
+            parse_levels(&res.bids, &mut bids[..res.bids.len()]).unwrap();
+            parse_levels(&res.asks, &mut asks[..res.asks.len()]).unwrap();
+            black_box((&bids, &asks));
+        });
+    });
+
+    // The number parsing alone, per kernel
+    let res: OrderBookV2 = serde_json::from_slice(TEST_DATA).unwrap();
+    for (name, kernel) in [
+        ("batch_scalar", Kernel::Scalar),
+        ("batch_ssse3", Kernel::Ssse3),
+        ("batch_avx2", Kernel::Avx2),
+    ] {
+        if !kernel.is_supported() {
+            continue;
+        }
+        c.bench_function(name, |b| {
+            b.iter(|| {
+                parse_levels_with(kernel, &res.bids, &mut bids[..res.bids.len()]).unwrap();
+                parse_levels_with(kernel, &res.asks, &mut asks[..res.asks.len()]).unwrap();
+                black_box((&bids, &asks));
+            });
+        });
+    }
+}
+
+criterion_group!(
+    benches,
+    bench_serde,
+    bench_sonic,
+    bench_simd_json,
+    bench_scan,
+    bench_apply,
+    bench_widths,
+    bench_batch
+);
 criterion_main!(benches);
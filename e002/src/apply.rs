@@ -0,0 +1,301 @@
+use std::fmt;
+
+use e001::orderbook::{OrderBook, Side};
+use serde::Deserialize;
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+
+// Applies depth messages to a book while they are being decoded. Each
+// `[price, qty]` pair goes straight from the JSON into `OrderBook::insert`,
+// or `OrderBook::delete` when the quantity is zero, so no level vectors are
+// built. Works for snapshots (`lastUpdateId`, `bids`, `asks`) and diff
+// updates (`U`, `u`, `pu`, `b`, `a`) alike; the ids come back in a
+// `DepthHeader` so the caller can sequence updates against snapshots.
+//
+// A snapshot is applied on top of whatever the book holds, so start it from
+// an empty book. Levels are applied as they are read: if decoding fails
+// halfway, the levels before the error have already been applied.
+
+/// Everything in a depth message except the levels. `update_id` is
+/// `lastUpdateId` for snapshots and `u` for diff updates.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DepthHeader<'a> {
+    pub update_id: u64,
+    pub first_update_id: Option<u64>,
+    pub prev_update_id: Option<u64>,
+    pub event_time: Option<u64>,
+    pub tx_time: Option<u64>,
+    pub symbol: Option<&'a str>,
+}
+
+/// Seed that applies a depth message to `book` and yields its header
+pub struct ApplyDepth<'b, B> {
+    pub book: &'b mut B,
+}
+
+impl<'b, B> ApplyDepth<'b, B> {
+    pub fn new(book: &'b mut B) -> Self {
+        ApplyDepth { book }
+    }
+}
+
+impl<'de, B> DeserializeSeed<'de> for ApplyDepth<'_, B>
+where
+    B: OrderBook,
+    B::Price: Deserialize<'de>,
+    B::Qty: Deserialize<'de> + Default + PartialEq,
+{
+    type Value = DepthHeader<'de>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, B> Visitor<'de> for ApplyDepth<'_, B>
+where
+    B: OrderBook,
+    B::Price: Deserialize<'de>,
+    B::Qty: Deserialize<'de> + Default + PartialEq,
+{
+    type Value = DepthHeader<'de>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a depth snapshot or diff depth update")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut header = DepthHeader::default();
+        let mut update_id = None;
+
+        while let Some(key) = map.next_key::<&'de str>()? {
+            match key {
+                "bids" | "b" => map.next_value_seed(ApplyLevels {
+                    book: &mut *self.book,
+                    side: Side::Bid,
+                })?,
+                "asks" | "a" => map.next_value_seed(ApplyLevels {
+                    book: &mut *self.book,
+                    side: Side::Ask,
+                })?,
+                "lastUpdateId" | "u" => update_id = Some(map.next_value()?),
+                "U" => header.first_update_id = Some(map.next_value()?),
+                "pu" => header.prev_update_id = Some(map.next_value()?),
+                "E" => header.event_time = Some(map.next_value()?),
+                "T" => header.tx_time = Some(map.next_value()?),
+                "s" => header.symbol = Some(map.next_value()?),
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        header.update_id = update_id.ok_or_else(|| de::Error::missing_field("lastUpdateId"))?;
+        Ok(header)
+    }
+}
+
+// One side's `[[price, qty], ..]` array
+struct ApplyLevels<'b, B> {
+    book: &'b mut B,
+    side: Side,
+}
+
+impl<'de, B> DeserializeSeed<'de> for ApplyLevels<'_, B>
+where
+    B: OrderBook,
+    B::Price: Deserialize<'de>,
+    B::Qty: Deserialize<'de> + Default + PartialEq,
+{
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, B> Visitor<'de> for ApplyLevels<'_, B>
+where
+    B: OrderBook,
+    B::Price: Deserialize<'de>,
+    B::Qty: Deserialize<'de> + Default + PartialEq,
+{
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an array of [price, quantity] levels")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let zero = B::Qty::default();
+        while let Some((price, qty)) = seq.next_element::<(B::Price, B::Qty)>()? {
+            if qty == zero {
+                self.book.delete(self.side.clone(), price);
+            } else {
+                self.book.insert(self.side.clone(), price, qty);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Applies the depth message in `buf` to `book` with serde_json
+pub fn apply_serde_json<'de, B>(
+    buf: &'de [u8],
+    book: &mut B,
+) -> serde_json::Result<DepthHeader<'de>>
+where
+    B: OrderBook,
+    B::Price: Deserialize<'de>,
+    B::Qty: Deserialize<'de> + Default + PartialEq,
+{
+    let mut de = serde_json::Deserializer::from_slice(buf);
+    let header = ApplyDepth::new(book).deserialize(&mut de)?;
+    de.end()?;
+    Ok(header)
+}
+
+/// Applies the depth message in `buf` to `book` with sonic-rs
+pub fn apply_sonic<'de, B>(buf: &'de [u8], book: &mut B) -> sonic_rs::Result<DepthHeader<'de>>
+where
+    B: OrderBook,
+    B::Price: Deserialize<'de>,
+    B::Qty: Deserialize<'de> + Default + PartialEq,
+{
+    let mut de = sonic_rs::Deserializer::from_slice(buf);
+    let header = ApplyDepth::new(book).deserialize(&mut de)?;
+    de.end()?;
+    Ok(header)
+}
+
+/// Applies the depth message in `buf` to `book` with simd-json. The tape
+/// lives in `buffers`, which should be kept across messages to stay off the
+/// allocator.
+pub fn apply_simd_json<'de, B>(
+    buf: &'de mut [u8],
+    buffers: &mut simd_json::Buffers,
+    book: &mut B,
+) -> simd_json::Result<DepthHeader<'de>>
+where
+    B: OrderBook,
+    B::Price: Deserialize<'de>,
+    B::Qty: Deserialize<'de> + Default + PartialEq,
+{
+    let mut de = simd_json::Deserializer::from_slice_with_buffers(buf, buffers)?;
+    ApplyDepth::new(book).deserialize(&mut de)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binance::Depth;
+    use crate::fp::Fp;
+    use e001::btree::BTreeBook;
+    use e001::hybrid::HybridBook;
+    use rust_decimal::Decimal;
+
+    const SNAPSHOT: &[u8] = br#"{"lastUpdateId":100,"E":1746977566204,"T":1746977566198,"bids":[["104276.90","10.023"],["104276.80","0.032"],["104276.30","0.002"]],"asks":[["104277.00","15.341"],["104277.10","0.010"]]}"#;
+
+    // Levels come before the ids here, and `e` is skipped
+    const UPDATE: &[u8] = br#"{"e":"depthUpdate","b":[["104276.80","0.000"],["104276.95","1.000"]],"a":[["104277.00","15.000"],["104277.10","0.000"]],"E":1746977566300,"T":1746977566299,"s":"BTCUSDT","U":101,"u":105,"pu":100}"#;
+
+    type Sides<P, Q> = (Vec<(P, Q)>, Vec<(P, Q)>);
+
+    fn levels<B: OrderBook>(book: &B) -> Sides<B::Price, B::Qty>
+    where
+        B::Price: Clone,
+        B::Qty: Clone,
+    {
+        let clone = |(p, q): (&B::Price, &B::Qty)| (p.clone(), q.clone());
+        (
+            book.bids().map(clone).collect(),
+            book.asks().map(clone).collect(),
+        )
+    }
+
+    #[test]
+    fn test_apply_snapshot() {
+        let expected = Depth::<2, 3>::from_serde_json(SNAPSHOT).unwrap();
+
+        let mut book = HybridBook::<Fp<2>, Fp<3>>::new();
+        let header = apply_serde_json(SNAPSHOT, &mut book).unwrap();
+        assert_eq!(header.update_id, 100);
+        assert_eq!(header.event_time, Some(1746977566204));
+        assert_eq!(header.symbol, None);
+        assert_eq!(
+            levels(&book),
+            (expected.bids.clone(), expected.asks.clone())
+        );
+
+        let mut book = BTreeBook::<Fp<2>, Fp<3>>::new();
+        apply_sonic(SNAPSHOT, &mut book).unwrap();
+        assert_eq!(
+            levels(&book),
+            (expected.bids.clone(), expected.asks.clone())
+        );
+
+        let mut book = BTreeBook::<Fp<2>, Fp<3>>::new();
+        let mut buffers = simd_json::Buffers::default();
+        apply_simd_json(&mut SNAPSHOT.to_vec(), &mut buffers, &mut book).unwrap();
+        assert_eq!(levels(&book), (expected.bids, expected.asks));
+    }
+
+    #[test]
+    fn test_apply_update() {
+        let mut book = HybridBook::<Fp<2>, Fp<3>>::new();
+        apply_sonic(SNAPSHOT, &mut book).unwrap();
+        let header = apply_sonic(UPDATE, &mut book).unwrap();
+        assert_eq!(
+            header,
+            DepthHeader {
+                update_id: 105,
+                first_update_id: Some(101),
+                prev_update_id: Some(100),
+                event_time: Some(1746977566300),
+                tx_time: Some(1746977566299),
+                symbol: Some("BTCUSDT"),
+            }
+        );
+
+        let p = |s: &str| s.parse::<Fp<2>>().unwrap();
+        let q = |s: &str| s.parse::<Fp<3>>().unwrap();
+        let (bids, asks) = levels(&book);
+        assert_eq!(
+            bids,
+            vec![
+                (p("104276.95"), q("1.000")),
+                (p("104276.90"), q("10.023")),
+                (p("104276.30"), q("0.002")),
+            ]
+        );
+        assert_eq!(asks, vec![(p("104277.00"), q("15.000"))]);
+
+        // Any book whose types deserialize works, Decimal included
+        let mut book = BTreeBook::<Decimal, Decimal>::new();
+        apply_serde_json(SNAPSHOT, &mut book).unwrap();
+        apply_serde_json(UPDATE, &mut book).unwrap();
+        assert_eq!(book.bids().count(), 3);
+        assert_eq!(book.asks().count(), 1);
+    }
+
+    #[test]
+    fn test_apply_errors() {
+        let mut book = BTreeBook::<Fp<2>, Fp<3>>::new();
+        assert!(apply_serde_json(br#"{"bids":[],"asks":[]}"#, &mut book).is_err());
+        assert!(apply_serde_json(br#"{"u":1,"b":[["1.0","1.000"]]}"#, &mut book).is_err());
+        assert!(apply_sonic(br#"{"u":1,"b":[]} {}"#, &mut book).is_err());
+        assert!(apply_serde_json(br#"[1]"#, &mut book).is_err());
+        assert_eq!(book.bids().count(), 0);
+    }
+}
@@ -0,0 +1,659 @@
+use std::collections::VecDeque;
+
+use crate::fees::{FeeModel, Liquidity};
+use crate::fp::Fp;
+use crate::order::{OrderType, TimeInForce};
+use crate::position::{CostBasis, Fill, Position};
+use crate::queue::{CancelModel, QueuePosition};
+use crate::strategy::{
+    EventLoop, Executor, Intent, MarketEvent, OrderFill, OrderId, OrderIntent, Strategy, TradeTick,
+};
+use e001::orderbook::{OrderBook, Side};
+
+// Replays recorded market data through a strategy against a simulated
+// exchange. The exchange keeps its own copy of the book at recorded time;
+// the strategy sees every event `market_data` later, its intents reach the
+// exchange `order_entry` after it sends them, and fills come back with the
+// market data delay. Everything is driven by the recorded timestamps, so a
+// run is a pure function of its inputs.
+//
+// Fill model: an arriving order takes liquidity from the opposite side up
+// to its limit, removing it from the simulated book until the next update
+// overwrites the level. A resting order joins behind the level's displayed
+// size and moves up as `QueuePosition` estimates, by default only on trades
+// at its price or when the level shrinks below the queue ahead of it. It is
+// filled outright when the market trades or quotes through its price.
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Latency {
+    /// From the strategy sending an intent to the exchange acting on it
+    pub order_entry: u64,
+    /// From the exchange to the strategy, for market data and fills alike
+    pub market_data: u64,
+}
+
+/// Order flow counts of a run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OrderStats {
+    pub orders: usize,
+    pub cancels: usize,
+    pub amends: usize,
+    /// Post-only orders that would have crossed, and fill-or-kill orders
+    /// the book could not fill
+    pub rejects: usize,
+    /// Immediate-or-cancel and market remainders dropped unfilled
+    pub expired: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Resting<const P: usize, const Q: usize> {
+    order: OrderIntent<P, Q>,
+    queue: QueuePosition<P, Q>,
+}
+
+/// The simulated exchange, as an `Executor` for the strategy's loop
+pub struct SimExchange<X, const P: usize, const Q: usize> {
+    book: X,
+    latency: Latency,
+    now: u64,
+    pending: VecDeque<(u64, Intent<P, Q>)>,
+    resting: Vec<Resting<P, Q>>,
+    /// Fills on their way to the strategy
+    reports: VecDeque<OrderFill<P, Q>>,
+    /// Fills at exchange time, not yet booked by the backtest
+    executed: Vec<(u64, OrderFill<P, Q>)>,
+    stats: OrderStats,
+    /// Who cancels at our levels; `Back` unless set
+    pub cancels: CancelModel,
+}
+
+impl<X, const P: usize, const Q: usize> SimExchange<X, P, Q>
+where
+    X: OrderBook<Price = Fp<P>, Qty = Fp<Q>>,
+{
+    pub fn new(book: X, latency: Latency) -> Self {
+        SimExchange {
+            book,
+            latency,
+            now: 0,
+            pending: VecDeque::new(),
+            resting: Vec::new(),
+            reports: VecDeque::new(),
+            executed: Vec::new(),
+            stats: OrderStats::default(),
+            cancels: CancelModel::Back,
+        }
+    }
+
+    /// The book as the exchange sees it
+    pub fn book(&self) -> &X {
+        &self.book
+    }
+
+    /// Our orders resting on the book: id, side, price and quantity left
+    pub fn resting(&self) -> impl Iterator<Item = (OrderId, &Side, Fp<P>, Fp<Q>)> {
+        self.resting
+            .iter()
+            .map(|r| (r.order.id, &r.order.side, r.queue.price, r.queue.qty()))
+    }
+
+    pub fn stats(&self) -> &OrderStats {
+        &self.stats
+    }
+
+    /// Acts on the intents that arrived by the event's time, then applies
+    /// the event
+    pub fn process(&mut self, event: &MarketEvent<P, Q>) {
+        let time = event.time();
+        while let Some(at) = self.pending.front().map(|(at, _)| *at) {
+            if at > time {
+                break;
+            }
+            self.now = self.now.max(at);
+            let (_, intent) = self.pending.pop_front().unwrap();
+            self.act(intent);
+        }
+        self.now = self.now.max(time);
+
+        match event {
+            MarketEvent::Book(update) => {
+                update.apply_to(&mut self.book);
+                self.on_book();
+            }
+            MarketEvent::Trade(trade) => self.on_trade(trade),
+        }
+    }
+
+    fn act(&mut self, intent: Intent<P, Q>) {
+        match intent {
+            Intent::Place(order) => self.place(order),
+            Intent::Cancel(id) => {
+                self.stats.cancels += 1;
+                self.resting.retain(|r| r.order.id != id);
+            }
+            Intent::Amend(id, qty) => {
+                self.stats.amends += 1;
+                if let Some(r) = self.resting.iter_mut().find(|r| r.order.id == id) {
+                    r.queue.amend(qty);
+                }
+                self.resting.retain(|r| r.queue.qty().is_positive());
+            }
+        }
+    }
+
+    fn place(&mut self, order: OrderIntent<P, Q>) {
+        self.stats.orders += 1;
+        let crosses = |price: &Fp<P>| match (&order.side, order.price) {
+            (_, None) => true,
+            (Side::Bid, Some(limit)) => *price <= limit,
+            (Side::Ask, Some(limit)) => *price >= limit,
+        };
+        let opposite: Vec<(Fp<P>, Fp<Q>)> = match order.side {
+            Side::Bid => self.book.asks().map(|(p, q)| (*p, *q)).collect(),
+            Side::Ask => self.book.bids().map(|(p, q)| (*p, *q)).collect(),
+        };
+        let available: Fp<Q> = opposite
+            .iter()
+            .take_while(|(p, _)| crosses(p))
+            .map(|(_, q)| *q)
+            .sum();
+
+        let maker = order.order_type == OrderType::LimitMaker;
+        let fok = order.time_in_force == Some(TimeInForce::Fok);
+        if (maker && available.is_positive()) || (fok && available < order.qty) {
+            self.stats.rejects += 1;
+            return;
+        }
+
+        // Take what crosses
+        let mut left = order.qty;
+        let mut taken = Vec::new();
+        for (price, size) in opposite.into_iter().take_while(|(p, _)| crosses(p)) {
+            if !left.is_positive() {
+                break;
+            }
+            let take = size.min(left);
+            left -= take;
+            taken.push((price, take));
+            let side = match order.side {
+                Side::Bid => Side::Ask,
+                Side::Ask => Side::Bid,
+            };
+            match size == take {
+                true => self.book.delete(side, price),
+                false => self.book.insert(side, price, size - take),
+            }
+        }
+
+        let rests = left.is_positive()
+            && order.price.is_some()
+            && !matches!(
+                order.time_in_force,
+                Some(TimeInForce::Ioc) | Some(TimeInForce::Fok)
+            );
+        let count = taken.len();
+        for (i, (price, qty)) in taken.into_iter().enumerate() {
+            let done = i + 1 == count && !rests;
+            self.fill(&order, price, qty, Liquidity::Taker, done);
+        }
+
+        match order.price {
+            Some(price) if rests => {
+                let side = order.side.clone();
+                let queue = QueuePosition::join(side, price, left, &self.book, self.cancels);
+                self.resting.push(Resting { order, queue });
+            }
+            _ if left.is_positive() => self.stats.expired += 1,
+            _ => {}
+        }
+    }
+
+    fn on_book(&mut self) {
+        let (bid, ask) = self.book.top();
+        let (bid, ask) = (bid.map(|(p, _)| *p), ask.map(|(p, _)| *p));
+
+        let mut filled = Vec::new();
+        for r in &mut self.resting {
+            r.queue.on_book(&self.book);
+            let price = r.queue.price;
+            let through = match r.order.side {
+                Side::Bid => ask.is_some_and(|ask| ask <= price),
+                Side::Ask => bid.is_some_and(|bid| bid >= price),
+            };
+            if through {
+                let qty = r.queue.qty();
+                r.queue.fill(qty);
+                filled.push((r.order.clone(), price, qty));
+            }
+        }
+        self.settle(filled);
+    }
+
+    fn on_trade(&mut self, trade: &TradeTick<P, Q>) {
+        let mut filled = Vec::new();
+        for r in &mut self.resting {
+            let qty = r.queue.on_trade(trade.price, trade.qty, &trade.aggressor);
+            if qty.is_positive() {
+                r.queue.fill(qty);
+                filled.push((r.order.clone(), r.queue.price, qty));
+            }
+        }
+        self.settle(filled);
+    }
+
+    fn settle(&mut self, filled: Vec<(OrderIntent<P, Q>, Fp<P>, Fp<Q>)>) {
+        for (order, price, qty) in filled {
+            let done = !self
+                .resting
+                .iter()
+                .any(|r| r.order.id == order.id && r.queue.qty().is_positive());
+            self.fill(&order, price, qty, Liquidity::Maker, done);
+        }
+        self.resting.retain(|r| r.queue.qty().is_positive());
+    }
+
+    fn fill(
+        &mut self,
+        order: &OrderIntent<P, Q>,
+        price: Fp<P>,
+        qty: Fp<Q>,
+        liquidity: Liquidity,
+        done: bool,
+    ) {
+        let fill = OrderFill {
+            id: order.id,
+            time: self.now + self.latency.market_data,
+            side: order.side.clone(),
+            price,
+            qty,
+            liquidity,
+            done,
+        };
+        self.executed.push((self.now, fill.clone()));
+        self.reports.push_back(fill);
+    }
+}
+
+impl<X, const P: usize, const Q: usize> Executor<P, Q> for SimExchange<X, P, Q> {
+    fn submit(&mut self, now: u64, intent: Intent<P, Q>) {
+        let at = (now + self.latency.order_entry).max(self.now);
+        self.pending.push_back((at, intent));
+    }
+
+    fn poll(&mut self, now: u64, fills: &mut Vec<OrderFill<P, Q>>) {
+        while self.reports.front().is_some_and(|fill| fill.time <= now) {
+            fills.extend(self.reports.pop_front());
+        }
+    }
+}
+
+/// A fill as the exchange made it, with its fee in the quote asset
+#[derive(Debug, Clone, PartialEq)]
+pub struct Execution<const P: usize, const Q: usize, const M: usize> {
+    /// Exchange time; the strategy heard of it at `fill.time`
+    pub time: u64,
+    pub fill: OrderFill<P, Q>,
+    pub fee: Fp<M>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExecStats<const Q: usize, const M: usize> {
+    pub orders: OrderStats,
+    pub fills: usize,
+    pub maker_fills: usize,
+    pub taker_fills: usize,
+    pub volume: Fp<Q>,
+    pub fees: Fp<M>,
+    /// Largest fall of the PnL curve from a previous high
+    pub max_drawdown: Fp<M>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report<const P: usize, const Q: usize, const M: usize> {
+    pub executions: Vec<Execution<P, Q, M>>,
+    /// Realised plus unrealised at the mid, at each exchange time it changed
+    pub pnl: Vec<(u64, Fp<M>)>,
+    pub stats: ExecStats<Q, M>,
+    pub position: Position<P, Q, M>,
+}
+
+pub struct Backtest<B, X, S, const P: usize, const Q: usize, const M: usize> {
+    pub ev: EventLoop<B, S, SimExchange<X, P, Q>, P, Q>,
+    pub fees: FeeModel<M>,
+    latency: Latency,
+    report: Report<P, Q, M>,
+    peak: Fp<M>,
+}
+
+impl<B, X, S, const P: usize, const Q: usize, const M: usize> Backtest<B, X, S, P, Q, M>
+where
+    B: OrderBook<Price = Fp<P>, Qty = Fp<Q>>,
+    X: OrderBook<Price = Fp<P>, Qty = Fp<Q>>,
+    S: Strategy<P, Q>,
+{
+    /// `book` is the strategy's, `exchange` the simulator's; both start
+    /// empty
+    pub fn new(
+        book: B,
+        exchange: X,
+        strategy: S,
+        latency: Latency,
+        fees: FeeModel<M>,
+        basis: CostBasis,
+    ) -> Self {
+        let sim = SimExchange::new(exchange, latency);
+        Backtest {
+            ev: EventLoop::new(book, strategy, sim),
+            fees,
+            latency,
+            report: Report {
+                executions: Vec::new(),
+                pnl: Vec::new(),
+                stats: ExecStats::default(),
+                position: Position::new(basis),
+            },
+            peak: Fp::ZERO,
+        }
+    }
+
+    pub fn strategy(&self) -> &S {
+        &self.ev.strategy
+    }
+
+    pub fn report(&self) -> &Report<P, Q, M> {
+        &self.report
+    }
+
+    /// Replays `events`, which must be in time order
+    pub fn run(&mut self, events: impl IntoIterator<Item = MarketEvent<P, Q>>) {
+        for event in events {
+            self.step(event);
+        }
+    }
+
+    pub fn step(&mut self, mut event: MarketEvent<P, Q>) {
+        let time = event.time();
+        // Everything the strategy sends before this reaches the exchange
+        // ahead of the event
+        self.ev
+            .advance(time.saturating_sub(self.latency.order_entry));
+        self.ev.executor.process(&event);
+        self.book_fills(time);
+
+        match &mut event {
+            MarketEvent::Book(update) => update.time += self.latency.market_data,
+            MarketEvent::Trade(trade) => trade.time += self.latency.market_data,
+        }
+        self.ev.handle(event);
+    }
+
+    fn book_fills(&mut self, time: u64) {
+        let report = &mut self.report;
+        for (at, fill) in self.ev.executor.executed.drain(..) {
+            let fee = self
+                .fees
+                .fee(&fill.side, fill.liquidity, fill.price, fill.qty);
+            let fee = fee.in_quote(fill.price);
+            report.position.apply(&Fill {
+                side: fill.side.clone(),
+                price: fill.price,
+                qty: fill.qty,
+                fee,
+            });
+
+            let stats = &mut report.stats;
+            stats.fills += 1;
+            match fill.liquidity {
+                Liquidity::Maker => stats.maker_fills += 1,
+                Liquidity::Taker => stats.taker_fills += 1,
+            }
+            stats.volume += fill.qty;
+            stats.fees += fee;
+            report.executions.push(Execution {
+                time: at,
+                fill,
+                fee,
+            });
+        }
+        report.stats.orders = self.ev.executor.stats;
+
+        let book = &self.ev.executor.book;
+        let Some(open) = report.position.unrealized_mid(book) else {
+            return;
+        };
+        let pnl = report.position.realized() + open;
+        if report.pnl.last().is_none_or(|&(_, last)| last != pnl) {
+            report.pnl.push((time, pnl));
+        }
+        self.peak = self.peak.max(pnl);
+        report.stats.max_drawdown = report.stats.max_drawdown.max(self.peak - pnl);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fp;
+    use crate::strategy::Context;
+    use crate::testutil::{book, trade};
+    use e001::btree::BTreeBook;
+    use e001::hybrid::HybridBook;
+
+    type Book = HybridBook<Fp<2>, Fp<3>>;
+
+    // Bids at the best bid, then cancels what is left and sells what was
+    // bought at market 20ms later
+    #[derive(Default)]
+    struct Scalper {
+        order: Option<OrderId>,
+        bought: Fp<3>,
+        seen: Vec<(u64, Fp<3>)>,
+    }
+
+    impl Strategy<2, 3> for Scalper {
+        fn on_book_update(
+            &mut self,
+            book: &impl OrderBook<Price = Fp<2>, Qty = Fp<3>>,
+            ctx: &mut Context<2, 3>,
+        ) {
+            if self.order.is_none() {
+                let bid = *book.top().0.unwrap().0;
+                self.order = Some(ctx.limit(Side::Bid, bid, fp!(0.500)));
+                ctx.set_timer(ctx.now() + 20);
+            }
+        }
+
+        fn on_fill(&mut self, fill: &OrderFill<2, 3>, ctx: &mut Context<2, 3>) {
+            self.seen.push((ctx.now(), fill.qty));
+            if fill.side == Side::Bid {
+                self.bought += fill.qty;
+            }
+        }
+
+        fn on_timer(&mut self, ctx: &mut Context<2, 3>) {
+            ctx.cancel(self.order.unwrap());
+            ctx.market(Side::Ask, self.bought);
+        }
+    }
+
+    fn replay() -> Vec<MarketEvent<2, 3>> {
+        vec![
+            book(
+                1000,
+                true,
+                &[(fp!(99.00), fp!(1.000)), (fp!(98.00), fp!(2.000))],
+                &[(fp!(101.00), fp!(1.000)), (fp!(102.00), fp!(1.000))],
+            ),
+            // 1.000 ahead of us, so 0.200 is ours
+            trade(1020, fp!(99.00), fp!(1.200), Side::Ask),
+            book(1040, false, &[(fp!(99.00), fp!(0.000))], &[]),
+        ]
+    }
+
+    fn backtest() -> Backtest<Book, BTreeBook<Fp<2>, Fp<3>>, Scalper, 2, 3, 8> {
+        let latency = Latency {
+            order_entry: 5,
+            market_data: 10,
+        };
+        let fees = FeeModel::flat(fp!(0.00000000), fp!(0.00100000));
+        let exchange = BTreeBook::new();
+        Backtest::new(
+            Book::new(),
+            exchange,
+            Scalper::default(),
+            latency,
+            fees,
+            CostBasis::Fifo,
+        )
+    }
+
+    #[test]
+    fn test_backtest() {
+        let mut bt = backtest();
+        bt.run(replay());
+        let report = bt.report();
+
+        // Seen at 1010, at the exchange by 1015, partly filled at 1020 and
+        // heard of at 1030; the market sell reaches the book at 1035
+        assert_eq!(bt.strategy().seen, [(1030, fp!(0.200)), (1045, fp!(0.200))]);
+        let fills: Vec<_> = report
+            .executions
+            .iter()
+            .map(|e| {
+                (
+                    e.time,
+                    e.fill.side.clone(),
+                    e.fill.price,
+                    e.fill.liquidity,
+                    e.fill.done,
+                )
+            })
+            .collect();
+        assert_eq!(
+            fills,
+            [
+                (1020, Side::Bid, fp!(99.00), Liquidity::Maker, false),
+                (1035, Side::Ask, fp!(99.00), Liquidity::Taker, true),
+            ]
+        );
+
+        let stats = &report.stats;
+        assert_eq!(stats.orders.orders, 2);
+        assert_eq!(stats.orders.cancels, 1);
+        assert_eq!((stats.maker_fills, stats.taker_fills), (1, 1));
+        assert_eq!(stats.volume, fp!(0.400));
+        // 0.1% of 19.80, taker side only
+        assert_eq!(stats.fees, fp!(0.01980000));
+        assert_eq!(report.position.qty(), Fp::ZERO);
+        assert_eq!(report.position.realized(), fp!(-0.01980000));
+
+        // Bought at 99 with the mid at 100, then flat after fees
+        assert_eq!(
+            report.pnl,
+            [
+                (1000, Fp::ZERO),
+                (1020, fp!(0.20000000)),
+                (1040, fp!(-0.01980000))
+            ]
+        );
+        assert_eq!(stats.max_drawdown, fp!(0.21980000));
+
+        // The same inputs give the same run
+        let mut again = backtest();
+        again.run(replay());
+        assert_eq!(again.report(), report);
+    }
+
+    #[test]
+    fn test_sim_exchange() {
+        let latency = Latency::default();
+        let mut sim = SimExchange::new(Book::new(), latency);
+        sim.process(&book(
+            0,
+            true,
+            &[(fp!(99.00), fp!(1.000))],
+            &[(fp!(101.00), fp!(1.000))],
+        ));
+
+        let order = |id, side, order_type, tif, price: Fp<2>, qty: Fp<3>| {
+            Intent::Place(OrderIntent {
+                id: OrderId(id),
+                side,
+                order_type,
+                time_in_force: tif,
+                price: Some(price),
+                qty,
+            })
+        };
+        // Post-only through the ask and an unfillable FOK are rejected, the
+        // IOC takes the whole ask and drops the rest
+        sim.submit(
+            1,
+            order(
+                1,
+                Side::Bid,
+                OrderType::LimitMaker,
+                None,
+                fp!(101.00),
+                fp!(1.000),
+            ),
+        );
+        sim.submit(
+            1,
+            order(
+                2,
+                Side::Bid,
+                OrderType::Limit,
+                Some(TimeInForce::Fok),
+                fp!(101.00),
+                fp!(2.000),
+            ),
+        );
+        sim.submit(
+            1,
+            order(
+                3,
+                Side::Bid,
+                OrderType::Limit,
+                Some(TimeInForce::Ioc),
+                fp!(101.00),
+                fp!(1.500),
+            ),
+        );
+        sim.submit(
+            1,
+            order(
+                4,
+                Side::Ask,
+                OrderType::Limit,
+                Some(TimeInForce::Gtc),
+                fp!(100.00),
+                fp!(1.000),
+            ),
+        );
+        sim.process(&trade(2, fp!(99.00), fp!(0.100), Side::Ask));
+
+        assert_eq!(sim.stats().rejects, 2);
+        assert_eq!(sim.stats().expired, 1);
+        assert_eq!(sim.book().top().1, None);
+        let resting: Vec<_> = sim.resting().map(|(id, _, price, _)| (id, price)).collect();
+        assert_eq!(resting, [(OrderId(4), fp!(100.00))]);
+
+        // The bid moving up to our ask fills it
+        sim.process(&book(3, false, &[(fp!(100.00), fp!(0.500))], &[]));
+        assert_eq!(sim.resting().count(), 0);
+        let mut fills = Vec::new();
+        sim.poll(3, &mut fills);
+        let fills: Vec<_> = fills
+            .iter()
+            .map(|f| (f.id, f.qty, f.liquidity, f.done))
+            .collect();
+        assert_eq!(
+            fills,
+            [
+                (OrderId(3), fp!(1.000), Liquidity::Taker, true),
+                (OrderId(4), fp!(1.000), Liquidity::Maker, true),
+            ]
+        );
+    }
+}
@@ -0,0 +1,522 @@
+use std::{error::Error, fmt};
+
+use crate::fp::{Fp, ParseFpError};
+
+/// Longest input the vector kernels take: 15 digits and the decimal point.
+/// Anything longer, or not in `[-]int.frac` form with exactly the expected
+/// number of fraction digits, goes through the scalar parser.
+const LANE: usize = 16;
+
+/// Which implementation parses a batch, picked at runtime from the CPU.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Kernel {
+    Scalar,
+    Ssse3,
+    Avx2,
+}
+
+impl Kernel {
+    /// Best kernel the running CPU supports
+    pub fn detect() -> Self {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx2") {
+                return Kernel::Avx2;
+            }
+            if is_x86_feature_detected!("ssse3") {
+                return Kernel::Ssse3;
+            }
+        }
+        Kernel::Scalar
+    }
+
+    pub fn is_supported(self) -> bool {
+        match self {
+            Kernel::Scalar => true,
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Ssse3 => is_x86_feature_detected!("ssse3"),
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Avx2 => is_x86_feature_detected!("avx2"),
+            #[cfg(not(target_arch = "x86_64"))]
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchError {
+    /// Position of the first input that failed to parse
+    pub index: usize,
+    pub error: ParseFpError,
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "input {}: {}", self.index, self.error)
+    }
+}
+
+impl Error for BatchError {}
+
+/// Parses fixed-format decimals (`"104276.90"`) into `out`, the vector way
+/// when the CPU allows. Results match `Fp::from_bytes::<N>` on every input.
+/// Panics if `out` and `inputs` differ in length.
+pub fn parse_batch<const N: usize>(inputs: &[&[u8]], out: &mut [Fp<N>]) -> Result<(), BatchError> {
+    parse_batch_with(Kernel::detect(), inputs, out)
+}
+
+/// Same as `parse_batch` for `(price, quantity)` levels of a depth message.
+pub fn parse_levels<const P: usize, const Q: usize>(
+    levels: &[(&[u8], &[u8])],
+    out: &mut [(Fp<P>, Fp<Q>)],
+) -> Result<(), BatchError> {
+    parse_levels_with(Kernel::detect(), levels, out)
+}
+
+/// `parse_batch` on a given kernel. Panics if the CPU doesn't support it.
+pub fn parse_batch_with<const N: usize>(
+    kernel: Kernel,
+    inputs: &[&[u8]],
+    out: &mut [Fp<N>],
+) -> Result<(), BatchError> {
+    assert_eq!(inputs.len(), out.len(), "output length mismatch");
+    assert!(
+        kernel.is_supported(),
+        "{:?} is not supported on this CPU",
+        kernel
+    );
+
+    match kernel {
+        // SAFETY: support for the kernel was checked above
+        #[cfg(target_arch = "x86_64")]
+        Kernel::Avx2 => unsafe { x86::batch_avx2(inputs, out) },
+        #[cfg(target_arch = "x86_64")]
+        Kernel::Ssse3 => unsafe { x86::batch_ssse3(inputs, out) },
+        _ => {
+            for (i, input) in inputs.iter().enumerate() {
+                out[i] = scalar(input, i)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// `parse_levels` on a given kernel. Panics if the CPU doesn't support it.
+/// Errors report the flat index: the price of level `k` is `2k`, its
+/// quantity `2k + 1`.
+pub fn parse_levels_with<const P: usize, const Q: usize>(
+    kernel: Kernel,
+    levels: &[(&[u8], &[u8])],
+    out: &mut [(Fp<P>, Fp<Q>)],
+) -> Result<(), BatchError> {
+    assert_eq!(levels.len(), out.len(), "output length mismatch");
+    assert!(
+        kernel.is_supported(),
+        "{:?} is not supported on this CPU",
+        kernel
+    );
+
+    match kernel {
+        // SAFETY: support for the kernel was checked above
+        #[cfg(target_arch = "x86_64")]
+        Kernel::Avx2 => unsafe { x86::levels_avx2(levels, out) },
+        #[cfg(target_arch = "x86_64")]
+        Kernel::Ssse3 => unsafe { x86::levels_ssse3(levels, out) },
+        _ => {
+            for (k, &(price, qty)) in levels.iter().enumerate() {
+                out[k] = (scalar(price, 2 * k)?, scalar(qty, 2 * k + 1)?);
+            }
+            Ok(())
+        }
+    }
+}
+
+#[inline(always)]
+fn scalar<const N: usize>(input: &[u8], index: usize) -> Result<Fp<N>, BatchError> {
+    Fp::<N>::from_bytes::<N>(input).map_err(|error| BatchError { index, error })
+}
+
+/// An input gathered into two words, along with the shuffle mask that
+/// turns them into right-aligned digits.
+///
+/// Going through registers rather than a stack copy avoids store forwarding
+/// stalls on the vector load, which otherwise cost more than the parse.
+struct Prepared {
+    low: u64,
+    high: u64,
+    mask: &'static [u8; LANE],
+    negative: bool,
+}
+
+impl Prepared {
+    /// `None` when the input is not in the exact layout the kernels handle
+    #[inline(always)]
+    fn new<const N: usize>(input: &[u8]) -> Option<Self> {
+        let (negative, digits) = match input.split_first() {
+            Some((b'-', rest)) => (true, rest),
+            _ => (false, input),
+        };
+
+        let len = digits.len();
+        if len < N + 1 || len > LANE || digits[len - 1 - N] != b'.' {
+            return None;
+        }
+
+        // Head and tail words overlap for short inputs; the masks account
+        // for where each input byte ends up
+        let (low, high) = if len >= 8 {
+            (read::<8>(digits, 0), read::<8>(digits, len - 8))
+        } else if len >= 4 {
+            let word = read::<4>(digits, 0) | read::<4>(digits, len - 4) << 32;
+            (word, 0)
+        } else {
+            return None;
+        };
+
+        Some(Prepared {
+            low,
+            high,
+            mask: &Masks::<N>::TABLE_REF[len],
+            negative,
+        })
+    }
+
+    #[inline(always)]
+    fn finish<const N: usize>(&self, value: u64) -> Fp<N> {
+        let value = value as i128;
+        Fp::from_raw(if self.negative { -value } else { value })
+    }
+}
+
+/// Little-endian word of `W` bytes starting at `at`
+#[inline(always)]
+fn read<const W: usize>(buf: &[u8], at: usize) -> u64 {
+    let mut word = [0u8; 8];
+    word[..W].copy_from_slice(&buf[at..at + W]);
+    u64::from_le_bytes(word)
+}
+
+/// Shuffle masks indexed by input length, dropping the decimal point and
+/// right-aligning the digits, zero filling (0x80) on the left.
+struct Masks<const N: usize>;
+
+impl<const N: usize> Masks<N> {
+    const TABLE: [[u8; LANE]; LANE + 1] = {
+        let mut table = [[0x80u8; LANE]; LANE + 1];
+        let mut len = if N + 1 > 4 { N + 1 } else { 4 };
+        while len <= LANE {
+            let dot = len - 1 - N;
+            let digits = len - 1;
+            let mut j = 0;
+            while j < digits {
+                let byte = if j < dot { j } else { j + 1 };
+                table[len][LANE - digits + j] = Self::position(byte, len);
+                j += 1;
+            }
+            len += 1;
+        }
+        table
+    };
+    const TABLE_REF: &'static [[u8; LANE]; LANE + 1] = &Self::TABLE;
+
+    /// Vector position of input byte `byte` as laid out by `Prepared::new`
+    const fn position(byte: usize, len: usize) -> u8 {
+        let half = if len >= 8 { 8 } else { 4 };
+        (if byte < half {
+            byte
+        } else {
+            byte + 2 * half - len
+        }) as u8
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use super::{BatchError, LANE, Prepared, scalar};
+    use crate::fp::Fp;
+    use std::arch::x86_64::*;
+
+    const TENS: [i8; LANE] = [10, 1, 10, 1, 10, 1, 10, 1, 10, 1, 10, 1, 10, 1, 10, 1];
+
+    /// Folds 16 right-aligned digit values into two 8-digit halves, using the
+    /// usual multiply-add ladder: pairs, quads, then octets.
+    #[inline]
+    #[target_feature(enable = "ssse3")]
+    fn fold_ssse3(digits: __m128i) -> Option<(u32, u32)> {
+        // Every byte must be a digit value, non-digits wrapped above 9
+        let nine = _mm_set1_epi8(9);
+        let valid = _mm_cmpeq_epi8(_mm_max_epu8(digits, nine), nine);
+        if _mm_movemask_epi8(valid) != 0xFFFF {
+            return None;
+        }
+
+        // SAFETY: TENS is LANE bytes long
+        let tens = unsafe { _mm_loadu_si128(TENS.as_ptr() as *const __m128i) };
+        let pairs = _mm_maddubs_epi16(digits, tens);
+        let quads = _mm_madd_epi16(pairs, _mm_setr_epi16(100, 1, 100, 1, 100, 1, 100, 1));
+        let packed = _mm_packs_epi32(quads, quads);
+        let octets = _mm_madd_epi16(
+            packed,
+            _mm_setr_epi16(10000, 1, 10000, 1, 10000, 1, 10000, 1),
+        );
+
+        let high = _mm_cvtsi128_si32(octets) as u32;
+        let low = _mm_cvtsi128_si32(_mm_srli_si128(octets, 4)) as u32;
+        Some((high, low))
+    }
+
+    #[inline]
+    #[target_feature(enable = "ssse3")]
+    fn parse_ssse3<const N: usize>(input: &[u8]) -> Option<Fp<N>> {
+        let prepared = Prepared::new::<N>(input)?;
+
+        let raw = _mm_set_epi64x(prepared.high as i64, prepared.low as i64);
+        // SAFETY: masks are LANE bytes long
+        let mask = unsafe { _mm_loadu_si128(prepared.mask.as_ptr() as *const __m128i) };
+
+        let digits = _mm_shuffle_epi8(_mm_sub_epi8(raw, _mm_set1_epi8(b'0' as i8)), mask);
+        let (high, low) = fold_ssse3(digits)?;
+
+        Some(prepared.finish(high as u64 * 100_000_000 + low as u64))
+    }
+
+    /// Parses two inputs at once, one per 128-bit lane, each with its own
+    /// precision. `None` if either input needs the scalar path.
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    fn parse_pair_avx2<const A: usize, const B: usize>(
+        a: &[u8],
+        b: &[u8],
+    ) -> Option<(Fp<A>, Fp<B>)> {
+        let pa = Prepared::new::<A>(a)?;
+        let pb = Prepared::new::<B>(b)?;
+
+        let raw = _mm256_set_epi64x(pb.high as i64, pb.low as i64, pa.high as i64, pa.low as i64);
+
+        // SAFETY: masks and TENS are LANE bytes long
+        let (mask, tens) = unsafe {
+            (
+                _mm256_loadu2_m128i(
+                    pb.mask.as_ptr() as *const __m128i,
+                    pa.mask.as_ptr() as *const __m128i,
+                ),
+                _mm256_loadu2_m128i(
+                    TENS.as_ptr() as *const __m128i,
+                    TENS.as_ptr() as *const __m128i,
+                ),
+            )
+        };
+
+        let digits = _mm256_shuffle_epi8(_mm256_sub_epi8(raw, _mm256_set1_epi8(b'0' as i8)), mask);
+
+        let nine = _mm256_set1_epi8(9);
+        let valid = _mm256_cmpeq_epi8(_mm256_max_epu8(digits, nine), nine);
+        if _mm256_movemask_epi8(valid) != -1 {
+            return None;
+        }
+
+        let pairs = _mm256_maddubs_epi16(digits, tens);
+        let quads = _mm256_madd_epi16(pairs, _mm256_set1_epi32(0x0001_0064));
+        let packed = _mm256_packs_epi32(quads, quads);
+        let octets = _mm256_madd_epi16(packed, _mm256_set1_epi32(0x0001_2710));
+
+        let mut lanes = [0u32; 8];
+        // SAFETY: lanes is 32 bytes long
+        unsafe { _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, octets) };
+
+        Some((
+            pa.finish(lanes[0] as u64 * 100_000_000 + lanes[1] as u64),
+            pb.finish(lanes[4] as u64 * 100_000_000 + lanes[5] as u64),
+        ))
+    }
+
+    // The loops live behind the target features so the kernels inline
+    #[target_feature(enable = "ssse3")]
+    pub(super) fn batch_ssse3<const N: usize>(
+        inputs: &[&[u8]],
+        out: &mut [Fp<N>],
+    ) -> Result<(), BatchError> {
+        for (i, input) in inputs.iter().enumerate() {
+            out[i] = match parse_ssse3(input) {
+                Some(fp) => fp,
+                None => scalar(input, i)?,
+            };
+        }
+        Ok(())
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) fn batch_avx2<const N: usize>(
+        inputs: &[&[u8]],
+        out: &mut [Fp<N>],
+    ) -> Result<(), BatchError> {
+        let mut i = 0;
+        while i + 1 < inputs.len() {
+            (out[i], out[i + 1]) = match parse_pair_avx2(inputs[i], inputs[i + 1]) {
+                Some(pair) => pair,
+                None => (scalar(inputs[i], i)?, scalar(inputs[i + 1], i + 1)?),
+            };
+            i += 2;
+        }
+        batch_ssse3(&inputs[i..], &mut out[i..]).map_err(|e| BatchError {
+            index: e.index + i,
+            ..e
+        })
+    }
+
+    #[target_feature(enable = "ssse3")]
+    pub(super) fn levels_ssse3<const P: usize, const Q: usize>(
+        levels: &[(&[u8], &[u8])],
+        out: &mut [(Fp<P>, Fp<Q>)],
+    ) -> Result<(), BatchError> {
+        for (k, &(price, qty)) in levels.iter().enumerate() {
+            let price = match parse_ssse3(price) {
+                Some(fp) => fp,
+                None => scalar(price, 2 * k)?,
+            };
+            let qty = match parse_ssse3(qty) {
+                Some(fp) => fp,
+                None => scalar(qty, 2 * k + 1)?,
+            };
+            out[k] = (price, qty);
+        }
+        Ok(())
+    }
+
+    /// A whole level per call: price in the low lane, quantity in the high
+    #[target_feature(enable = "avx2")]
+    pub(super) fn levels_avx2<const P: usize, const Q: usize>(
+        levels: &[(&[u8], &[u8])],
+        out: &mut [(Fp<P>, Fp<Q>)],
+    ) -> Result<(), BatchError> {
+        for (k, &(price, qty)) in levels.iter().enumerate() {
+            out[k] = match parse_pair_avx2(price, qty) {
+                Some(level) => level,
+                None => (scalar(price, 2 * k)?, scalar(qty, 2 * k + 1)?),
+            };
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fp::FpErrorKind;
+
+    const KERNELS: [Kernel; 3] = [Kernel::Scalar, Kernel::Ssse3, Kernel::Avx2];
+
+    const INPUTS: &[&str] = &[
+        "104276.90",
+        "0.00",
+        "-0.01",
+        ".50",
+        "-104276.90",
+        "9999999999999.99",
+        "99999999999999.99",
+        "1.2",
+        "1.234",
+        "12",
+        "1x.00",
+        "-",
+        "",
+        "123456789012.34",
+        "7.07",
+    ];
+
+    fn scalar_reference<const N: usize>(input: &str) -> Result<Fp<N>, ParseFpError> {
+        Fp::<N>::from_bytes::<N>(input.as_bytes())
+    }
+
+    #[test]
+    fn test_batch_matches_scalar() {
+        for kernel in KERNELS.into_iter().filter(|k| k.is_supported()) {
+            // One at a time, so each error is checked against the scalar one
+            for input in INPUTS {
+                let mut out = [Fp::<2>::ZERO];
+                let res = parse_batch_with(kernel, &[input.as_bytes()], &mut out);
+                match scalar_reference::<2>(input) {
+                    Ok(fp) => assert_eq!((res, out[0]), (Ok(()), fp), "{kernel:?} {input}"),
+                    Err(error) => assert_eq!(res, Err(BatchError { index: 0, error })),
+                }
+            }
+
+            // Valid inputs in bulk, odd length to cover the AVX2 tail
+            let valid: Vec<&[u8]> = INPUTS
+                .iter()
+                .filter(|s| scalar_reference::<2>(s).is_ok())
+                .map(|s| s.as_bytes())
+                .collect();
+            let mut out = vec![Fp::<2>::ZERO; valid.len()];
+            parse_batch_with(kernel, &valid, &mut out).unwrap();
+            for (input, fp) in valid.iter().zip(&out) {
+                assert_eq!(Fp::<2>::from_bytes::<2>(input).unwrap(), *fp);
+            }
+        }
+    }
+
+    #[test]
+    fn test_batch_generated() {
+        // Every digit count and sign, with all digit values in each position
+        let mut inputs = Vec::new();
+        for digits in 1..=15 {
+            for seed in 0..10u64 {
+                let mut s = String::new();
+                if seed % 2 == 1 {
+                    s.push('-');
+                }
+                for k in 0..digits {
+                    if digits >= 3 && k == digits - 3 {
+                        s.push('.');
+                    }
+                    s.push((b'0' + ((seed + k as u64 * 7) % 10) as u8) as char);
+                }
+                inputs.push(s);
+            }
+        }
+        let bytes: Vec<&[u8]> = inputs.iter().map(|s| s.as_bytes()).collect();
+
+        for kernel in KERNELS.into_iter().filter(|k| k.is_supported()) {
+            let mut out = vec![Fp::<3>::ZERO; bytes.len()];
+            let res = parse_batch_with(kernel, &bytes, &mut out);
+
+            // Inputs with fewer than 3 fraction digits fail on every kernel
+            let first_err = bytes
+                .iter()
+                .position(|b| Fp::<3>::from_bytes::<3>(b).is_err());
+            assert_eq!(res.map_err(|e| e.index).err(), first_err);
+
+            let valid: Vec<&[u8]> = bytes
+                .iter()
+                .copied()
+                .filter(|b| Fp::<3>::from_bytes::<3>(b).is_ok())
+                .collect();
+            let mut out = vec![Fp::<3>::ZERO; valid.len()];
+            parse_batch_with(kernel, &valid, &mut out).unwrap();
+            for (input, fp) in valid.iter().zip(&out) {
+                assert_eq!(Fp::<3>::from_bytes::<3>(input).unwrap(), *fp, "{kernel:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_batch_levels() {
+        let levels: [(&[u8], &[u8]); 3] = [
+            (b"104276.90", b"10.023"),
+            (b"104276.80", b"0.032"),
+            (b"104276.30", b"0.002"),
+        ];
+
+        for kernel in KERNELS.into_iter().filter(|k| k.is_supported()) {
+            let mut out = [(Fp::<2>::ZERO, Fp::<3>::ZERO); 3];
+            parse_levels_with(kernel, &levels, &mut out).unwrap();
+            assert_eq!(out[0], (Fp::from_raw(10427690), Fp::from_raw(10023)));
+            assert_eq!(out[2], (Fp::from_raw(10427630), Fp::from_raw(2)));
+
+            let bad: [(&[u8], &[u8]); 2] = [(b"1.00", b"1.000"), (b"1.00", b"1.0")];
+            let err = parse_levels_with(kernel, &bad, &mut out[..2]).unwrap_err();
+            assert_eq!(err.index, 3);
+            assert_eq!(err.error.kind(), &FpErrorKind::InvalidFraction);
+        }
+    }
+}
@@ -0,0 +1,459 @@
+use std::fmt;
+use std::marker::PhantomData;
+
+use crate::fp::Fp;
+use e001::orderbook::Side;
+use serde::Deserialize;
+use serde::de::{self, Deserializer, IgnoredAny, SeqAccess, Visitor};
+
+// Typed Binance market-data messages. Prices and quantities parse straight
+// into `Fp` and symbols borrow from the input, so decoding a message only
+// allocates the level vectors, or nothing at all for `DepthSnapshot`. `P` is
+// the price precision and `Q` the quantity precision of the instrument, e.g.
+// `Depth<2, 3>` for BTCUSDT futures or `Depth<8, 8>` for most spot pairs.
+
+pub type Level<const P: usize, const Q: usize> = (Fp<P>, Fp<Q>);
+
+// Generates the parse entry points, one per JSON backend
+macro_rules! impl_parse {
+    ($name:ident) => {
+        impl<const P: usize, const Q: usize> $name<P, Q> {
+            impl_parse!(@methods '_);
+        }
+    };
+    ($name:ident<'a>) => {
+        impl<'a, const P: usize, const Q: usize> $name<'a, P, Q> {
+            impl_parse!(@methods 'a);
+        }
+    };
+    (@methods $lt:lifetime) => {
+        pub fn from_serde_json(buf: &$lt [u8]) -> serde_json::Result<Self> {
+            serde_json::from_slice(buf)
+        }
+
+        pub fn from_sonic(buf: &$lt [u8]) -> sonic_rs::Result<Self> {
+            sonic_rs::from_slice(buf)
+        }
+
+        // simd-json parses in place, so it needs the buffer mutably
+        pub fn from_simd_json(buf: &$lt mut [u8]) -> simd_json::Result<Self> {
+            simd_json::from_slice(buf)
+        }
+    };
+}
+
+/// Depth snapshot from `GET /api/v3/depth` (spot) or `GET /fapi/v1/depth`
+/// (futures). Only futures sends the event and transaction times.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Depth<const P: usize, const Q: usize> {
+    #[serde(rename = "lastUpdateId")]
+    pub last_update_id: u64,
+
+    #[serde(rename = "E")]
+    pub event_time: Option<u64>,
+
+    #[serde(rename = "T")]
+    pub tx_time: Option<u64>,
+
+    pub bids: Vec<Level<P, Q>>,
+    pub asks: Vec<Level<P, Q>>,
+}
+
+/// `Depth` with fixed-capacity sides, for a market-data thread that never
+/// touches the heap. Each side holds at most `LEVELS` levels; what happens
+/// to the rest of a longer message is up to the `Overflow` policy `O`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(bound(deserialize = "O: Overflow"))]
+pub struct DepthSnapshot<const LEVELS: usize, const P: usize, const Q: usize, O = Reject> {
+    #[serde(rename = "lastUpdateId")]
+    pub last_update_id: u64,
+
+    #[serde(rename = "E")]
+    pub event_time: Option<u64>,
+
+    #[serde(rename = "T")]
+    pub tx_time: Option<u64>,
+
+    #[serde(deserialize_with = "bounded::<_, _, LEVELS, O>")]
+    pub bids: heapless::Vec<Level<P, Q>, LEVELS>,
+
+    #[serde(deserialize_with = "bounded::<_, _, LEVELS, O>")]
+    pub asks: heapless::Vec<Level<P, Q>, LEVELS>,
+
+    #[serde(skip)]
+    policy: PhantomData<O>,
+}
+
+/// What `DepthSnapshot` does with a side longer than its capacity
+pub trait Overflow {
+    const TRUNCATE: bool;
+}
+
+/// Fail to decode the message
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Reject;
+
+/// Keep the first levels, which Binance sends best first, and skip the rest
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Truncate;
+
+impl Overflow for Reject {
+    const TRUNCATE: bool = false;
+}
+
+impl Overflow for Truncate {
+    const TRUNCATE: bool = true;
+}
+
+fn bounded<'de, D, T, const N: usize, O>(deserializer: D) -> Result<heapless::Vec<T, N>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+    O: Overflow,
+{
+    struct BoundedVisitor<T, const N: usize, O>(PhantomData<(T, O)>);
+
+    impl<'de, T, const N: usize, O> Visitor<'de> for BoundedVisitor<T, N, O>
+    where
+        T: Deserialize<'de>,
+        O: Overflow,
+    {
+        type Value = heapless::Vec<T, N>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "at most {} levels", N)
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            let mut out = heapless::Vec::new();
+            while !out.is_full() {
+                match seq.next_element()? {
+                    Some(level) => _ = out.push(level),
+                    None => return Ok(out),
+                }
+            }
+
+            // Full: anything left is only skipped over, never parsed
+            if seq.next_element::<IgnoredAny>()?.is_some() {
+                if !O::TRUNCATE {
+                    return Err(de::Error::invalid_length(N + 1, &self));
+                }
+                while seq.next_element::<IgnoredAny>()?.is_some() {}
+            }
+            Ok(out)
+        }
+    }
+
+    deserializer.deserialize_seq(BoundedVisitor::<T, N, O>(PhantomData))
+}
+
+impl<const LEVELS: usize, const P: usize, const Q: usize, O> DepthSnapshot<LEVELS, P, Q, O> {
+    pub fn new(last_update_id: u64) -> Self {
+        DepthSnapshot {
+            last_update_id,
+            event_time: None,
+            tx_time: None,
+            bids: heapless::Vec::new(),
+            asks: heapless::Vec::new(),
+            policy: PhantomData,
+        }
+    }
+}
+
+impl<const LEVELS: usize, const P: usize, const Q: usize, O: Overflow>
+    DepthSnapshot<LEVELS, P, Q, O>
+{
+    impl_parse!(@methods '_);
+}
+
+/// Diff depth stream event (`<symbol>@depth`). A zero quantity removes the
+/// level. Futures adds the transaction time and the previous final update
+/// id, which is used to detect gaps.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DepthUpdate<'a, const P: usize, const Q: usize> {
+    #[serde(rename = "E")]
+    pub event_time: u64,
+
+    #[serde(rename = "T")]
+    pub tx_time: Option<u64>,
+
+    #[serde(rename = "s")]
+    pub symbol: &'a str,
+
+    #[serde(rename = "U")]
+    pub first_update_id: u64,
+
+    #[serde(rename = "u")]
+    pub final_update_id: u64,
+
+    #[serde(rename = "pu")]
+    pub prev_final_update_id: Option<u64>,
+
+    #[serde(rename = "b")]
+    pub bids: Vec<Level<P, Q>>,
+
+    #[serde(rename = "a")]
+    pub asks: Vec<Level<P, Q>>,
+}
+
+/// Best bid and offer stream event (`<symbol>@bookTicker`). Spot sends
+/// neither the event nor the transaction time.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct BookTicker<'a, const P: usize, const Q: usize> {
+    #[serde(rename = "u")]
+    pub update_id: u64,
+
+    #[serde(rename = "E")]
+    pub event_time: Option<u64>,
+
+    #[serde(rename = "T")]
+    pub tx_time: Option<u64>,
+
+    #[serde(rename = "s")]
+    pub symbol: &'a str,
+
+    #[serde(rename = "b")]
+    pub bid_price: Fp<P>,
+
+    #[serde(rename = "B")]
+    pub bid_qty: Fp<Q>,
+
+    #[serde(rename = "a")]
+    pub ask_price: Fp<P>,
+
+    #[serde(rename = "A")]
+    pub ask_qty: Fp<Q>,
+}
+
+/// Trade stream event (`<symbol>@trade`)
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct Trade<'a, const P: usize, const Q: usize> {
+    #[serde(rename = "E")]
+    pub event_time: u64,
+
+    #[serde(rename = "s")]
+    pub symbol: &'a str,
+
+    #[serde(rename = "t")]
+    pub trade_id: u64,
+
+    #[serde(rename = "p")]
+    pub price: Fp<P>,
+
+    #[serde(rename = "q")]
+    pub qty: Fp<Q>,
+
+    #[serde(rename = "T")]
+    pub trade_time: u64,
+
+    #[serde(rename = "m")]
+    pub buyer_is_maker: bool,
+}
+
+/// Aggregate trade stream event (`<symbol>@aggTrade`), covering the trades
+/// `first_trade_id..=last_trade_id` filled by one taker order at one price
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct AggTrade<'a, const P: usize, const Q: usize> {
+    #[serde(rename = "E")]
+    pub event_time: u64,
+
+    #[serde(rename = "s")]
+    pub symbol: &'a str,
+
+    #[serde(rename = "a")]
+    pub agg_trade_id: u64,
+
+    #[serde(rename = "p")]
+    pub price: Fp<P>,
+
+    #[serde(rename = "q")]
+    pub qty: Fp<Q>,
+
+    #[serde(rename = "f")]
+    pub first_trade_id: u64,
+
+    #[serde(rename = "l")]
+    pub last_trade_id: u64,
+
+    #[serde(rename = "T")]
+    pub trade_time: u64,
+
+    #[serde(rename = "m")]
+    pub buyer_is_maker: bool,
+}
+
+impl_parse!(Depth);
+impl_parse!(DepthUpdate<'a>);
+impl_parse!(BookTicker<'a>);
+impl_parse!(Trade<'a>);
+impl_parse!(AggTrade<'a>);
+
+impl<const P: usize, const Q: usize> Trade<'_, P, Q> {
+    /// Book side of the taker: a maker buyer means the taker sold into the
+    /// bids
+    pub fn aggressor(&self) -> Side {
+        if self.buyer_is_maker {
+            Side::Ask
+        } else {
+            Side::Bid
+        }
+    }
+}
+
+impl<const P: usize, const Q: usize> AggTrade<'_, P, Q> {
+    /// Book side of the taker: a maker buyer means the taker sold into the
+    /// bids
+    pub fn aggressor(&self) -> Side {
+        if self.buyer_is_maker {
+            Side::Ask
+        } else {
+            Side::Bid
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPOT_DEPTH: &[u8] = br#"{"lastUpdateId":1027024,"bids":[["4.00000000","431.00000000"]],"asks":[["4.00000200","12.00000000"]]}"#;
+
+    const FUTURES_DEPTH: &[u8] = br#"{"lastUpdateId":7488596254027,"E":1746977566204,"T":1746977566198,"bids":[["104276.90","10.023"],["104276.80","0.032"]],"asks":[["104277.00","15.341"]]}"#;
+
+    const DEPTH_UPDATE: &[u8] = br#"{"e":"depthUpdate","E":1571889248277,"T":1571889248276,"s":"BTCUSDT","U":390497796,"u":390497878,"pu":390497794,"b":[["7403.89","0.002"],["7403.90","0.000"]],"a":[["7405.96","3.340"]]}"#;
+
+    const BOOK_TICKER: &[u8] = br#"{"u":400900217,"s":"BNBUSDT","b":"25.35190000","B":"31.21000000","a":"25.36520000","A":"40.66000000"}"#;
+
+    const TRADE: &[u8] = br#"{"e":"trade","E":1672515782136,"s":"BNBBTC","t":12345,"p":"0.00100000","q":"100.00000000","T":1672515782136,"m":true,"M":true}"#;
+
+    const AGG_TRADE: &[u8] = br#"{"e":"aggTrade","E":1672515782136,"s":"BTCUSDT","a":5933014,"p":"16500.10","q":"0.012","f":100,"l":105,"T":1672515782136,"m":false}"#;
+
+    // Decodes with every backend and runs the same checks on each result
+    macro_rules! decode_all {
+        ($buf:expr, |$v:ident: $ty:ty| $check:block) => {
+            let check = |$v: $ty| $check;
+            check(serde_json::from_slice($buf).unwrap());
+            check(sonic_rs::from_slice($buf).unwrap());
+            check(simd_json::from_slice(&mut $buf.to_vec()).unwrap());
+        };
+    }
+
+    #[test]
+    fn test_depth() {
+        let spot = Depth::<8, 8>::from_serde_json(SPOT_DEPTH).unwrap();
+        assert_eq!(spot.last_update_id, 1027024);
+        assert_eq!(spot.event_time, None);
+        assert_eq!(
+            spot.bids,
+            vec![(
+                "4.00000000".parse().unwrap(),
+                "431.00000000".parse().unwrap()
+            )]
+        );
+        assert_eq!(spot.asks[0].0, "4.00000200".parse().unwrap());
+        assert_eq!(Depth::<8, 8>::from_sonic(SPOT_DEPTH).unwrap(), spot);
+        assert_eq!(
+            Depth::<8, 8>::from_simd_json(&mut SPOT_DEPTH.to_vec()).unwrap(),
+            spot
+        );
+
+        decode_all!(FUTURES_DEPTH, |d: Depth<2, 3>| {
+            assert_eq!(d.event_time, Some(1746977566204));
+            assert_eq!(d.tx_time, Some(1746977566198));
+            assert_eq!(d.bids.len(), 2);
+            assert_eq!(
+                d.bids[1],
+                (Fp::from_parts(104276, 80), Fp::from_parts(0, 32))
+            );
+            assert_eq!(d.asks[0].1, Fp::from_parts(15, 341));
+        });
+
+        // Too few decimals for the instrument is an error; extra decimals
+        // are truncated, as by `Fp::from_bytes`
+        assert!(Depth::<8, 8>::from_serde_json(FUTURES_DEPTH).is_err());
+        let coarse = Depth::<1, 2>::from_serde_json(FUTURES_DEPTH).unwrap();
+        assert_eq!(coarse.bids[1].0, Fp::from_parts(104276, 8));
+        assert_eq!(coarse.asks[0].1, Fp::from_parts(15, 34));
+    }
+
+    #[test]
+    fn test_depth_snapshot() {
+        let depth = Depth::<2, 3>::from_serde_json(FUTURES_DEPTH).unwrap();
+
+        decode_all!(FUTURES_DEPTH, |d: DepthSnapshot<2, 2, 3>| {
+            assert_eq!(d.last_update_id, depth.last_update_id);
+            assert_eq!(d.tx_time, Some(1746977566198));
+            assert_eq!(&d.bids[..], &depth.bids[..]);
+            assert_eq!(&d.asks[..], &depth.asks[..]);
+        });
+
+        // One bid too many
+        assert!(DepthSnapshot::<1, 2, 3>::from_serde_json(FUTURES_DEPTH).is_err());
+        assert!(DepthSnapshot::<1, 2, 3, Reject>::from_sonic(FUTURES_DEPTH).is_err());
+        assert!(DepthSnapshot::<1, 2, 3>::from_simd_json(&mut FUTURES_DEPTH.to_vec()).is_err());
+
+        decode_all!(FUTURES_DEPTH, |d: DepthSnapshot<1, 2, 3, Truncate>| {
+            assert_eq!(&d.bids[..], &depth.bids[..1]);
+            assert_eq!(&d.asks[..], &depth.asks[..]);
+        });
+
+        // Skipped levels are not parsed, but have to be valid JSON
+        let bad = br#"{"lastUpdateId":1,"bids":[["1.00","1.000"],["x","1.000"]],"asks":[]}"#;
+        assert!(DepthSnapshot::<1, 2, 3, Truncate>::from_serde_json(bad).is_ok());
+        let bad = br#"{"lastUpdateId":1,"bids":[["1.00","1.000"],[}],"asks":[]}"#;
+        assert!(DepthSnapshot::<1, 2, 3, Truncate>::from_serde_json(bad).is_err());
+
+        let empty = DepthSnapshot::<0, 2, 3, Truncate>::from_sonic(FUTURES_DEPTH).unwrap();
+        assert_eq!(
+            empty,
+            DepthSnapshot {
+                event_time: Some(1746977566204),
+                tx_time: Some(1746977566198),
+                ..DepthSnapshot::new(7488596254027)
+            }
+        );
+    }
+
+    #[test]
+    fn test_depth_update() {
+        let mut buf = DEPTH_UPDATE.to_vec();
+        let d = DepthUpdate::<2, 3>::from_simd_json(&mut buf).unwrap();
+        assert_eq!(d.symbol, "BTCUSDT");
+        assert_eq!(
+            (d.first_update_id, d.final_update_id),
+            (390497796, 390497878)
+        );
+        assert_eq!(d.prev_final_update_id, Some(390497794));
+        assert!(d.bids[1].1.is_zero());
+
+        let d = DepthUpdate::<2, 3>::from_sonic(DEPTH_UPDATE).unwrap();
+        assert_eq!(
+            d.asks,
+            vec![(Fp::from_parts(7405, 96), Fp::from_parts(3, 340))]
+        );
+    }
+
+    #[test]
+    fn test_tickers_and_trades() {
+        let t = BookTicker::<8, 8>::from_serde_json(BOOK_TICKER).unwrap();
+        assert_eq!(t.symbol, "BNBUSDT");
+        assert_eq!(t.event_time, None);
+        assert_eq!(t.bid_price, "25.35190000".parse().unwrap());
+        assert_eq!(t.ask_qty, "40.66000000".parse().unwrap());
+
+        decode_all!(TRADE, |t: Trade<8, 8>| {
+            assert_eq!(t.trade_id, 12345);
+            assert_eq!(t.qty, Fp::from_int(100));
+            assert!(t.aggressor() == Side::Ask);
+        });
+
+        decode_all!(AGG_TRADE, |t: AggTrade<2, 3>| {
+            assert_eq!(t.price, Fp::from_parts(16500, 10));
+            assert_eq!(t.last_trade_id - t.first_trade_id, 5);
+            assert!(t.aggressor() == Side::Bid);
+        });
+    }
+}
@@ -0,0 +1,462 @@
+use std::{
+    cmp::Ordering,
+    error::Error,
+    fmt,
+    hash::{Hash, Hasher},
+    ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign},
+    str::FromStr,
+};
+
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer};
+
+use crate::fp::{Fp, FpErrorKind, ParseFpError, parse_fixed, trim_zeros, write_decimal};
+
+const POW10: [i128; 39] = {
+    let mut table = [1i128; 39];
+    let mut i = 1;
+    while i < table.len() {
+        table[i] = table[i - 1] * 10;
+        i += 1;
+    }
+    table
+};
+
+/// Fixed point number whose precision is only known at runtime, e.g. from
+/// exchange metadata. Holds `value * 10^-scale`.
+///
+/// Values compare and hash numerically, so `1.0` and `1.00` are the same
+/// book key. Arithmetic follows `Fp`: sums take the larger scale, products
+/// and quotients keep the scale of the left-hand side.
+#[derive(Copy, Clone, Default)]
+pub struct DynFp {
+    value: i128,
+    scale: u8,
+}
+
+impl DynFp {
+    pub const MAX_SCALE: u8 = 38;
+
+    #[inline]
+    pub const fn from_raw(value: i128, scale: u8) -> Self {
+        assert!(scale <= Self::MAX_SCALE, "scale out of range");
+        DynFp { value, scale }
+    }
+
+    #[inline]
+    pub const fn raw(self) -> i128 {
+        self.value
+    }
+
+    #[inline]
+    pub const fn scale(self) -> u8 {
+        self.scale
+    }
+
+    /// Parses `[-]int.frac` with exactly `scale` fraction digits, using the
+    /// same fast path as `Fp::from_bytes`.
+    #[inline]
+    pub fn from_bytes(buf: &[u8], scale: u8) -> Result<Self, ParseFpError> {
+        if scale > Self::MAX_SCALE {
+            return Err(ParseFpError {
+                kind: FpErrorKind::TooManyDecimals,
+            });
+        }
+
+        parse_fixed(buf, scale as usize, POW10[scale as usize]).map(|value| DynFp { value, scale })
+    }
+
+    /// Same value at another scale, `None` if digits would be lost or the
+    /// result overflows.
+    pub fn rescale(self, scale: u8) -> Option<Self> {
+        if scale > Self::MAX_SCALE {
+            return None;
+        }
+
+        let value = match scale.cmp(&self.scale) {
+            Ordering::Equal => self.value,
+            Ordering::Greater => self
+                .value
+                .checked_mul(POW10[(scale - self.scale) as usize])?,
+            Ordering::Less => {
+                let divisor = POW10[(self.scale - scale) as usize];
+                if self.value % divisor != 0 {
+                    return None;
+                }
+                self.value / divisor
+            }
+        };
+
+        Some(DynFp { value, scale })
+    }
+
+    #[inline]
+    pub fn is_zero(self) -> bool {
+        self.value == 0
+    }
+
+    #[inline]
+    pub fn abs(self) -> Self {
+        DynFp {
+            value: self.value.abs(),
+            scale: self.scale,
+        }
+    }
+
+    /// Raw values of both sides at the larger of the two scales
+    #[inline]
+    fn align(self, rhs: Self) -> (i128, i128, u8) {
+        match self.scale.cmp(&rhs.scale) {
+            Ordering::Equal => (self.value, rhs.value, self.scale),
+            Ordering::Less => (
+                self.value * POW10[(rhs.scale - self.scale) as usize],
+                rhs.value,
+                rhs.scale,
+            ),
+            Ordering::Greater => (
+                self.value,
+                rhs.value * POW10[(self.scale - rhs.scale) as usize],
+                self.scale,
+            ),
+        }
+    }
+}
+
+impl Add for DynFp {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        let (a, b, scale) = self.align(rhs);
+        DynFp {
+            value: a + b,
+            scale,
+        }
+    }
+}
+
+impl Sub for DynFp {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        let (a, b, scale) = self.align(rhs);
+        DynFp {
+            value: a - b,
+            scale,
+        }
+    }
+}
+
+impl Mul for DynFp {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        let result = (self.value * rhs.value) / POW10[rhs.scale as usize];
+        DynFp {
+            value: result,
+            scale: self.scale,
+        }
+    }
+}
+
+impl Div for DynFp {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self {
+        let result = (self.value * POW10[rhs.scale as usize]) / rhs.value;
+        DynFp {
+            value: result,
+            scale: self.scale,
+        }
+    }
+}
+
+impl Neg for DynFp {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        DynFp {
+            value: -self.value,
+            scale: self.scale,
+        }
+    }
+}
+
+impl AddAssign for DynFp {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl SubAssign for DynFp {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl Ord for DynFp {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if self.scale == other.scale {
+            return self.value.cmp(&other.value);
+        }
+
+        // Bring the coarser side up; if that overflows, it is larger in
+        // magnitude than anything the finer side can hold
+        if self.scale < other.scale {
+            match self
+                .value
+                .checked_mul(POW10[(other.scale - self.scale) as usize])
+            {
+                Some(value) => value.cmp(&other.value),
+                None => self.value.cmp(&0),
+            }
+        } else {
+            other.cmp(self).reverse()
+        }
+    }
+}
+
+impl PartialOrd for DynFp {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for DynFp {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for DynFp {}
+
+impl Hash for DynFp {
+    // Hash the value without trailing zeros to stay consistent with `Eq`
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let (magnitude, scale) = trim_zeros(self.value.unsigned_abs(), self.scale as usize);
+        (self.value < 0).hash(state);
+        magnitude.hash(state);
+        scale.hash(state);
+    }
+}
+
+impl fmt::Display for DynFp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut buf = [0u8; Fp::<0>::MAX_STR_LEN];
+        let len = write_decimal(
+            false,
+            self.value.unsigned_abs(),
+            self.scale as usize,
+            &mut buf,
+        );
+        // Only ASCII digits and '.' were written
+        let s = std::str::from_utf8(&buf[..len]).unwrap();
+        f.pad_integral(self.value >= 0, "", s)
+    }
+}
+
+impl fmt::Debug for DynFp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// Takes the scale from the number of fraction digits, so `"0.0100"` has a
+/// scale of 4.
+impl FromStr for DynFp {
+    type Err = ParseFpError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let buf = s.as_bytes();
+        let Some(dot) = buf.iter().position(|&b| b == b'.') else {
+            return Err(ParseFpError {
+                kind: FpErrorKind::InvalidFormat,
+            });
+        };
+
+        let scale = buf.len() - dot - 1;
+        if scale > Self::MAX_SCALE as usize {
+            return Err(ParseFpError {
+                kind: FpErrorKind::TooManyDecimals,
+            });
+        }
+
+        DynFp::from_bytes(buf, scale as u8)
+    }
+}
+
+impl<'de> Deserialize<'de> for DynFp {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct DynVisitor;
+
+        impl Visitor<'_> for DynVisitor {
+            type Value = DynFp;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "a decimal string")
+            }
+
+            fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                s.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(DynVisitor)
+    }
+}
+
+impl<const DECIMALS: usize> From<Fp<DECIMALS>> for DynFp {
+    #[inline]
+    fn from(fp: Fp<DECIMALS>) -> Self {
+        DynFp::from_raw(fp.raw(), DECIMALS as u8)
+    }
+}
+
+impl<const DECIMALS: usize> TryFrom<DynFp> for Fp<DECIMALS> {
+    type Error = ScaleMismatch;
+
+    #[inline]
+    fn try_from(value: DynFp) -> Result<Self, Self::Error> {
+        if value.scale as usize != DECIMALS {
+            return Err(ScaleMismatch {
+                expected: DECIMALS as u8,
+                found: value.scale,
+            });
+        }
+        Ok(Fp::from_raw(value.value))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScaleMismatch {
+    pub expected: u8,
+    pub found: u8,
+}
+
+impl fmt::Display for ScaleMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "expected {} decimals, found {}",
+            self.expected, self.found
+        )
+    }
+}
+
+impl Error for ScaleMismatch {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fp;
+    use e001::btree::BTreeBook;
+    use e001::hashmap::HashMapBook;
+    use e001::hybrid::HybridBook;
+    use e001::orderbook::{OrderBook, Side};
+
+    fn dfp(s: &str) -> DynFp {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_dynfp_parsing() {
+        let price = DynFp::from_bytes(b"104276.90", 2).unwrap();
+        assert_eq!((price.raw(), price.scale()), (10427690, 2));
+        assert_eq!(price.to_string(), "104276.90");
+
+        let tick = dfp("0.01000000");
+        assert_eq!((tick.raw(), tick.scale()), (1000000, 8));
+        assert_eq!(dfp("-0.5").to_string(), "-0.5");
+
+        assert_eq!(
+            DynFp::from_bytes(b"1.2", 3).unwrap_err().kind(),
+            &FpErrorKind::InvalidFraction
+        );
+        assert_eq!(
+            "12".parse::<DynFp>().unwrap_err().kind(),
+            &FpErrorKind::InvalidFormat
+        );
+    }
+
+    #[test]
+    fn test_dynfp_arithmetic() {
+        let a = dfp("1.234");
+        let b = dfp("2.345");
+
+        // Same results as Fp<3>
+        assert_eq!(a + b, dfp("3.579"));
+        assert_eq!(b - a, dfp("1.111"));
+        assert_eq!(a * b, dfp("2.893"));
+        assert_eq!(b / a, dfp("1.900"));
+
+        // Mixed scales
+        let sum = dfp("1.5") + dfp("0.25");
+        assert_eq!((sum.raw(), sum.scale()), (175, 2));
+        let notional = dfp("100.50") * dfp("0.003");
+        assert_eq!((notional.raw(), notional.scale()), (30, 2));
+        assert_eq!(-dfp("1.5"), dfp("-1.50"));
+    }
+
+    #[test]
+    fn test_dynfp_ordering() {
+        assert_eq!(dfp("1.0"), dfp("1.00"));
+        assert!(dfp("1.01") > dfp("1.0"));
+        assert!(dfp("-1.01") < dfp("-1.0"));
+        assert!(DynFp::from_raw(i128::MAX / 10, 0) > dfp("1.00000000000000000000000000000000000"));
+        assert!(DynFp::from_raw(i128::MIN / 10, 0) < dfp("1.00000000000000000000000000000000000"));
+
+        let mut set = std::collections::HashSet::new();
+        set.insert(dfp("1.0"));
+        assert!(set.contains(&dfp("1.000")));
+        assert!(!set.contains(&dfp("-1.0")));
+
+        assert_eq!(dfp("1.50").rescale(1), Some(dfp("1.5")));
+        assert_eq!(dfp("1.55").rescale(1), None);
+        assert_eq!(dfp("1.5").rescale(3).map(DynFp::scale), Some(3));
+    }
+
+    #[test]
+    fn test_dynfp_fp_conversion() {
+        let fp: Fp<2> = fp!(104276.90);
+        let dynfp = DynFp::from(fp);
+
+        assert_eq!(dynfp, dfp("104276.90"));
+        assert_eq!(Fp::<2>::try_from(dynfp), Ok(fp));
+        assert_eq!(
+            Fp::<3>::try_from(dynfp),
+            Err(ScaleMismatch {
+                expected: 3,
+                found: 2
+            })
+        );
+    }
+
+    fn check_book<B: OrderBook<Price = DynFp, Qty = DynFp>>(mut book: B) {
+        book.insert(Side::Bid, dfp("100.10"), dfp("1.000"));
+        book.insert(Side::Bid, dfp("100.20"), dfp("2.000"));
+        book.insert(Side::Ask, dfp("100.30"), dfp("3.000"));
+        book.insert(Side::Bid, dfp("100.1"), dfp("4.000"));
+
+        let bids: Vec<_> = book.bids().map(|(p, q)| (*p, *q)).collect();
+        assert_eq!(
+            bids,
+            vec![(dfp("100.2"), dfp("2.0")), (dfp("100.1"), dfp("4.0"))]
+        );
+        assert_eq!(book.top().1, Some((&dfp("100.30"), &dfp("3.000"))));
+
+        book.delete(Side::Ask, dfp("100.3"));
+        assert_eq!(book.top().1, None);
+    }
+
+    #[test]
+    fn test_dynfp_book_keys() {
+        check_book(BTreeBook::new());
+        check_book(HashMapBook::new());
+        check_book(HybridBook::new());
+    }
+}
@@ -0,0 +1,389 @@
+use std::collections::VecDeque;
+use std::io;
+
+use crate::fp::Fp;
+use crate::strategy::{MarketEvent, TradeTick};
+use e001::orderbook::{OrderBook, Side};
+
+// Event-based book features over a rolling window, one row per event:
+//
+// - order flow imbalance (Cont, Kukanov and Stoikov), per level for the top
+//   `levels` levels; level 0 is the classic best bid and offer OFI. A bid
+//   that rises or grows counts as buying pressure, one that falls or
+//   shrinks as selling, and the other way round for asks.
+// - trade flow imbalance, buy less sell aggressor volume over their sum
+// - depletion, quantity leaving the best bid and ask queues per second,
+//   whole queues counting when the price moves away
+// - intensity, top of book changes per second
+//
+// Rates are over the window's length for a time window, and over the span
+// of the events in it for an event window, zero until that span is
+// positive. The first book state seen is the baseline and adds no flow.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Window {
+    /// The last `n` events
+    Events(usize),
+    /// Events within the last `n` milliseconds
+    Millis(u64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Features<const Q: usize> {
+    pub time: u64,
+    /// Events in the window
+    pub events: usize,
+    /// OFI per level, best first
+    pub ofi: Vec<Fp<Q>>,
+    pub buy_volume: Fp<Q>,
+    pub sell_volume: Fp<Q>,
+    /// From -1 to 1, zero without trades
+    pub trade_imbalance: f64,
+    pub bid_depletion: f64,
+    pub ask_depletion: f64,
+    pub intensity: f64,
+}
+
+// What one event added to the window
+#[derive(Debug, Clone)]
+struct Sample<const Q: usize> {
+    time: u64,
+    ofi: Vec<Fp<Q>>,
+    buy: Fp<Q>,
+    sell: Fp<Q>,
+    bid_depleted: Fp<Q>,
+    ask_depleted: Fp<Q>,
+    top_changed: bool,
+}
+
+type Depth<const P: usize, const Q: usize> = Vec<Option<(Fp<P>, Fp<Q>)>>;
+
+pub struct OrderFlow<const P: usize, const Q: usize> {
+    pub levels: usize,
+    pub window: Window,
+    bids: Option<Depth<P, Q>>,
+    asks: Option<Depth<P, Q>>,
+    samples: VecDeque<Sample<Q>>,
+    // Sums over `samples`
+    sum: Sample<Q>,
+    top_changes: usize,
+}
+
+impl<const P: usize, const Q: usize> OrderFlow<P, Q> {
+    pub fn new(levels: usize, window: Window) -> Self {
+        OrderFlow {
+            levels,
+            window,
+            bids: None,
+            asks: None,
+            samples: VecDeque::new(),
+            sum: Sample::empty(0, levels),
+            top_changes: 0,
+        }
+    }
+
+    /// The book as it is after an update at `time`
+    pub fn on_book<B>(&mut self, time: u64, book: &B) -> Features<Q>
+    where
+        B: OrderBook<Price = Fp<P>, Qty = Fp<Q>>,
+    {
+        let bids = depth(book.bids(), self.levels);
+        let asks = depth(book.asks(), self.levels);
+        let mut sample = Sample::empty(time, self.levels);
+        if let (Some(old_bids), Some(old_asks)) = (&self.bids, &self.asks) {
+            for (level, ofi) in sample.ofi.iter_mut().enumerate() {
+                *ofi = flow(&Side::Bid, old_bids[level], bids[level])
+                    - flow(&Side::Ask, old_asks[level], asks[level]);
+            }
+            sample.bid_depleted = depleted(&Side::Bid, old_bids[0], bids[0]);
+            sample.ask_depleted = depleted(&Side::Ask, old_asks[0], asks[0]);
+            sample.top_changed =
+                old_bids.first() != bids.first() || old_asks.first() != asks.first();
+        }
+        self.bids = Some(bids);
+        self.asks = Some(asks);
+        self.push(sample)
+    }
+
+    pub fn on_trade(&mut self, trade: &TradeTick<P, Q>) -> Features<Q> {
+        let mut sample = Sample::empty(trade.time, self.levels);
+        match trade.aggressor {
+            Side::Bid => sample.buy = trade.qty,
+            Side::Ask => sample.sell = trade.qty,
+        }
+        self.push(sample)
+    }
+
+    /// Applies a book update to `book` first, then reads it
+    pub fn on_event<B>(&mut self, event: &MarketEvent<P, Q>, book: &mut B) -> Features<Q>
+    where
+        B: OrderBook<Price = Fp<P>, Qty = Fp<Q>>,
+    {
+        match event {
+            MarketEvent::Book(update) => {
+                update.apply_to(book);
+                self.on_book(update.time, book)
+            }
+            MarketEvent::Trade(trade) => self.on_trade(trade),
+        }
+    }
+
+    /// A row per event, keeping `book` up to date along the way
+    pub fn stream<B, I>(mut self, mut book: B, events: I) -> impl Iterator<Item = Features<Q>>
+    where
+        B: OrderBook<Price = Fp<P>, Qty = Fp<Q>>,
+        I: IntoIterator<Item = MarketEvent<P, Q>>,
+    {
+        events
+            .into_iter()
+            .map(move |event| self.on_event(&event, &mut book))
+    }
+
+    fn push(&mut self, sample: Sample<Q>) -> Features<Q> {
+        let now = sample.time;
+        self.sum.add(&sample, true);
+        self.top_changes += sample.top_changed as usize;
+        self.samples.push_back(sample);
+        while let Some(oldest) = self.samples.front() {
+            let expired = match self.window {
+                Window::Events(n) => self.samples.len() > n,
+                Window::Millis(ms) => oldest.time + ms <= now,
+            };
+            if !expired {
+                break;
+            }
+            let oldest = self.samples.pop_front().unwrap();
+            self.sum.add(&oldest, false);
+            self.top_changes -= oldest.top_changed as usize;
+        }
+        self.features(now)
+    }
+
+    fn features(&self, now: u64) -> Features<Q> {
+        let span = match self.window {
+            Window::Millis(ms) => ms,
+            Window::Events(_) => now - self.samples.front().map_or(now, |s| s.time),
+        };
+        let per_second = |x: f64| match span {
+            0 => 0.0,
+            ms => x * 1000.0 / ms as f64,
+        };
+        let traded = self.sum.buy + self.sum.sell;
+        Features {
+            time: now,
+            events: self.samples.len(),
+            ofi: self.sum.ofi.clone(),
+            buy_volume: self.sum.buy,
+            sell_volume: self.sum.sell,
+            trade_imbalance: match traded.is_zero() {
+                true => 0.0,
+                false => ratio(self.sum.buy - self.sum.sell, traded),
+            },
+            bid_depletion: per_second(float(self.sum.bid_depleted)),
+            ask_depletion: per_second(float(self.sum.ask_depleted)),
+            intensity: per_second(self.top_changes as f64),
+        }
+    }
+}
+
+impl<const Q: usize> Sample<Q> {
+    fn empty(time: u64, levels: usize) -> Self {
+        Sample {
+            time,
+            ofi: vec![Fp::ZERO; levels],
+            buy: Fp::ZERO,
+            sell: Fp::ZERO,
+            bid_depleted: Fp::ZERO,
+            ask_depleted: Fp::ZERO,
+            top_changed: false,
+        }
+    }
+
+    // Adds `other` to a running sum, or takes it off when `add` is false
+    fn add(&mut self, other: &Sample<Q>, add: bool) {
+        let signed = |v: Fp<Q>| if add { v } else { -v };
+        for (sum, ofi) in self.ofi.iter_mut().zip(&other.ofi) {
+            *sum += signed(*ofi);
+        }
+        self.buy += signed(other.buy);
+        self.sell += signed(other.sell);
+        self.bid_depleted += signed(other.bid_depleted);
+        self.ask_depleted += signed(other.ask_depleted);
+    }
+}
+
+impl<const Q: usize> Features<Q> {
+    /// Column names for `levels` OFI levels
+    pub fn csv_header(levels: usize) -> String {
+        let mut header = "time,events".to_string();
+        (0..levels).for_each(|level| header += &format!(",ofi_{level}"));
+        header + ",buy_volume,sell_volume,trade_imbalance,bid_depletion,ask_depletion,intensity"
+    }
+
+    pub fn write_csv<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        write!(w, "{},{}", self.time, self.events)?;
+        for ofi in &self.ofi {
+            write!(w, ",{ofi}")?;
+        }
+        writeln!(
+            w,
+            ",{},{},{},{},{},{}",
+            self.buy_volume,
+            self.sell_volume,
+            self.trade_imbalance,
+            self.bid_depletion,
+            self.ask_depletion,
+            self.intensity
+        )
+    }
+}
+
+// The top `levels` levels, padded with `None`
+fn depth<'a, const P: usize, const Q: usize>(
+    levels: impl Iterator<Item = (&'a Fp<P>, &'a Fp<Q>)>,
+    n: usize,
+) -> Depth<P, Q> {
+    let mut depth: Depth<P, Q> = levels.take(n).map(|(p, q)| Some((*p, *q))).collect();
+    depth.resize(n, None);
+    depth
+}
+
+// Flow at one level of one side; a missing level is as far from the touch
+// as it gets
+fn flow<const P: usize, const Q: usize>(
+    side: &Side,
+    old: Option<(Fp<P>, Fp<Q>)>,
+    new: Option<(Fp<P>, Fp<Q>)>,
+) -> Fp<Q> {
+    match (old, new) {
+        (None, None) => Fp::ZERO,
+        (None, Some((_, q))) => q,
+        (Some((_, q)), None) => -q,
+        (Some((old_p, old_q)), Some((new_p, new_q))) => {
+            let better = match side {
+                Side::Bid => new_p > old_p,
+                Side::Ask => new_p < old_p,
+            };
+            match (better, new_p == old_p) {
+                (true, _) => new_q,
+                (false, true) => new_q - old_q,
+                (false, false) => -old_q,
+            }
+        }
+    }
+}
+
+// Quantity gone from the best queue
+fn depleted<const P: usize, const Q: usize>(
+    side: &Side,
+    old: Option<(Fp<P>, Fp<Q>)>,
+    new: Option<(Fp<P>, Fp<Q>)>,
+) -> Fp<Q> {
+    let Some((old_p, old_q)) = old else {
+        return Fp::ZERO;
+    };
+    match new {
+        None => old_q,
+        Some((new_p, new_q)) if new_p == old_p => (old_q - new_q).max(Fp::ZERO),
+        Some((new_p, _)) => match (side, new_p < old_p) {
+            (Side::Bid, true) | (Side::Ask, false) => old_q,
+            _ => Fp::ZERO,
+        },
+    }
+}
+
+fn float<const Q: usize>(x: Fp<Q>) -> f64 {
+    x.raw() as f64 / 10f64.powi(Q as i32)
+}
+
+fn ratio<const Q: usize>(a: Fp<Q>, b: Fp<Q>) -> f64 {
+    a.raw() as f64 / b.raw() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fp;
+    use crate::testutil::{book, trade};
+    use e001::hybrid::HybridBook;
+
+    #[test]
+    fn test_ofi() {
+        let events = vec![
+            book(
+                1000,
+                false,
+                &[(fp!(99.00), fp!(1.000)), (fp!(98.00), fp!(2.000))],
+                &[(fp!(101.00), fp!(1.000)), (fp!(102.00), fp!(2.000))],
+            ),
+            // Bid grows: +0.5
+            book(1100, false, &[(fp!(99.00), fp!(1.500))], &[]),
+            // Ask cleared, the next one is worse: +1.0, all of it depleted
+            book(1200, false, &[], &[(fp!(101.00), fp!(0.000))]),
+            // New best bid: +0.3, and level 1 moves up from 98.00: +1.5
+            book(1300, false, &[(fp!(99.50), fp!(0.300))], &[]),
+            trade(1400, fp!(100.00), fp!(0.200), Side::Bid),
+            trade(1500, fp!(100.00), fp!(0.600), Side::Ask),
+        ];
+        let rows: Vec<_> = OrderFlow::new(2, Window::Millis(1000))
+            .stream(HybridBook::new(), events.clone())
+            .collect();
+
+        assert_eq!(rows[0].ofi, [Fp::ZERO, Fp::ZERO]);
+        assert_eq!(rows[1].ofi, [fp!(0.500), Fp::ZERO]);
+        // Level 1 ask went 102.00 -> none, level 0 ask 101.00 -> 102.00
+        assert_eq!(rows[2].ofi, [fp!(1.500), fp!(2.000)]);
+        assert_eq!(rows[2].ask_depletion, 1.0);
+        assert_eq!(rows[3].ofi, [fp!(1.800), fp!(3.500)]);
+        assert_eq!(rows[3].intensity, 3.0);
+
+        let last = rows.last().unwrap();
+        assert_eq!(last.events, 6);
+        assert_eq!(last.buy_volume, fp!(0.200));
+        assert_eq!(last.trade_imbalance, -0.5);
+
+        // The first events fall out of a shorter window
+        let rows: Vec<_> = OrderFlow::new(1, Window::Millis(250))
+            .stream(HybridBook::new(), events.clone())
+            .collect();
+        assert_eq!(rows[3].events, 3);
+        assert_eq!(rows[3].ofi, [fp!(1.800)]);
+        assert_eq!(rows[5].ofi, [fp!(0.300)]);
+        assert_eq!(rows[5].intensity, 4.0);
+
+        // Event windows take rates over the events' span
+        let rows: Vec<_> = OrderFlow::new(1, Window::Events(2))
+            .stream(HybridBook::new(), events)
+            .collect();
+        assert_eq!(rows[0].intensity, 0.0);
+        assert_eq!(rows[3].ofi, [fp!(1.300)]);
+        assert_eq!(rows[3].intensity, 20.0);
+    }
+
+    #[test]
+    fn test_csv() {
+        let mut flow = OrderFlow::new(2, Window::Events(10));
+        let mut hybrid = HybridBook::new();
+        let mut out = Features::<3>::csv_header(2).into_bytes();
+        out.push(b'\n');
+        for event in [
+            book(
+                1000,
+                false,
+                &[(fp!(99.00), fp!(1.000))],
+                &[(fp!(101.00), fp!(1.000))],
+            ),
+            book(1500, false, &[(fp!(99.00), fp!(0.400))], &[]),
+        ] {
+            flow.on_event(&event, &mut hybrid)
+                .write_csv(&mut out)
+                .unwrap();
+        }
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "time,events,ofi_0,ofi_1,buy_volume,sell_volume,trade_imbalance,bid_depletion,ask_depletion,intensity\n\
+             1000,1,0.000,0.000,0.000,0.000,0,0,0,0\n\
+             1500,2,-0.600,0.000,0.000,0.000,0,1.2,0,2\n"
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use crate::fp::{Fp, Rounding, rescale};
+use e001::orderbook::Side;
+
+// Trading fees. A fill's rate depends on whether it added or took
+// liquidity and on the account's tier, picked by 30-day volume; a negative
+// rate is a rebate. The fee is charged in the quote or the base asset and
+// kept at the commission precision `M`, rounded the way the exchange does
+// it: charges up, rebates down, so neither is ever in our favour.
+
+/// Fee rate as a fraction of the notional, `0.00100000` is 0.1%
+pub type Rate = Fp<RATE_DECIMALS>;
+
+const RATE_DECIMALS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Liquidity {
+    Maker,
+    Taker,
+}
+
+impl Liquidity {
+    /// From a trade print: our fill is the maker side when the resting
+    /// order was ours. `buyer_is_maker` is the `m` flag of trade messages.
+    pub fn from_trade(side: &Side, buyer_is_maker: bool) -> Self {
+        match (side, buyer_is_maker) {
+            (Side::Bid, true) | (Side::Ask, false) => Liquidity::Maker,
+            _ => Liquidity::Taker,
+        }
+    }
+}
+
+/// Rates that apply from `min_volume` of 30-day traded notional up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tier<const M: usize> {
+    pub min_volume: Fp<M>,
+    pub maker: Rate,
+    pub taker: Rate,
+}
+
+/// Which asset fees are taken in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FeeAsset {
+    #[default]
+    Quote,
+    Base,
+    /// The asset the fill pays out: base on buys, quote on sells
+    Received,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Asset {
+    Base,
+    Quote,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fee<const M: usize> {
+    pub asset: Asset,
+    /// Negative for a rebate
+    pub amount: Fp<M>,
+}
+
+impl<const M: usize> Fee<M> {
+    /// The fee valued in the quote asset, for PnL. Base fees are converted
+    /// at `price` and truncated like `Mul`.
+    pub fn in_quote<const P: usize>(&self, price: Fp<P>) -> Fp<M> {
+        match self.asset {
+            Asset::Quote => self.amount,
+            Asset::Base => Fp::from_raw(self.amount.raw() * price.raw() / 10i128.pow(P as u32)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeeModel<const M: usize> {
+    /// Sorted by `min_volume`
+    tiers: Vec<Tier<M>>,
+    pub asset: FeeAsset,
+    /// `ToPositiveInfinity` by default, in the exchange's favour: charges
+    /// round up and rebates toward zero
+    pub rounding: Rounding,
+    /// Traded notional over the last 30 days, which picks the tier
+    pub volume: Fp<M>,
+}
+
+impl<const M: usize> FeeModel<M> {
+    /// # Panics
+    ///
+    /// If `tiers` is empty
+    pub fn new(mut tiers: Vec<Tier<M>>) -> Self {
+        assert!(!tiers.is_empty(), "a fee model needs at least one tier");
+        tiers.sort_by_key(|tier| tier.min_volume);
+        FeeModel {
+            tiers,
+            asset: FeeAsset::default(),
+            rounding: Rounding::ToPositiveInfinity,
+            volume: Fp::ZERO,
+        }
+    }
+
+    /// One rate for everything
+    pub fn flat(maker: Rate, taker: Rate) -> Self {
+        FeeModel::new(vec![Tier {
+            min_volume: Fp::ZERO,
+            maker,
+            taker,
+        }])
+    }
+
+    /// The tier the current volume is in; the lowest one below it
+    pub fn tier(&self) -> &Tier<M> {
+        let above = self
+            .tiers
+            .partition_point(|tier| tier.min_volume <= self.volume);
+        &self.tiers[above.saturating_sub(1)]
+    }
+
+    pub fn rate(&self, liquidity: Liquidity) -> Rate {
+        match liquidity {
+            Liquidity::Maker => self.tier().maker,
+            Liquidity::Taker => self.tier().taker,
+        }
+    }
+
+    /// Fee on a fill of `qty` at `price`
+    pub fn fee<const P: usize, const Q: usize>(
+        &self,
+        side: &Side,
+        liquidity: Liquidity,
+        price: Fp<P>,
+        qty: Fp<Q>,
+    ) -> Fee<M> {
+        let asset = match (self.asset, side) {
+            (FeeAsset::Quote, _) | (FeeAsset::Received, Side::Ask) => Asset::Quote,
+            (FeeAsset::Base, _) | (FeeAsset::Received, Side::Bid) => Asset::Base,
+        };
+        let rate = self.rate(liquidity).raw();
+        // Exact at the combined decimals, then rounded once
+        let (amount, decimals) = match asset {
+            Asset::Quote => (price.raw() * qty.raw() * rate, P + Q + RATE_DECIMALS),
+            Asset::Base => (qty.raw() * rate, Q + RATE_DECIMALS),
+        };
+        Fee {
+            asset,
+            amount: Fp::from_raw(rescale(amount, decimals, M, self.rounding)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fp;
+
+    #[test]
+    fn test_tiers() {
+        let mut model = FeeModel::<2>::new(vec![
+            Tier {
+                min_volume: Fp::from_int(1_000_000),
+                maker: fp!(0.00090000),
+                taker: fp!(0.00100000),
+            },
+            Tier {
+                min_volume: Fp::ZERO,
+                maker: fp!(0.00100000),
+                taker: fp!(0.00100000),
+            },
+            Tier {
+                min_volume: Fp::from_int(20_000_000),
+                maker: fp!(-0.00005000),
+                taker: fp!(0.00060000),
+            },
+        ]);
+        assert_eq!(model.rate(Liquidity::Maker), fp!(0.00100000));
+        model.volume = Fp::from_int(1_000_000);
+        assert_eq!(model.rate(Liquidity::Maker), fp!(0.00090000));
+        model.volume = Fp::from_int(50_000_000);
+        assert_eq!(model.rate(Liquidity::Maker), fp!(-0.00005000));
+        assert_eq!(model.rate(Liquidity::Taker), fp!(0.00060000));
+
+        assert_eq!(Liquidity::from_trade(&Side::Bid, true), Liquidity::Maker);
+        assert_eq!(Liquidity::from_trade(&Side::Bid, false), Liquidity::Taker);
+        assert_eq!(Liquidity::from_trade(&Side::Ask, false), Liquidity::Maker);
+        assert_eq!(Liquidity::from_trade(&Side::Ask, true), Liquidity::Taker);
+    }
+
+    #[test]
+    fn test_fees() {
+        let price: Fp<2> = fp!(104276.90);
+        let qty: Fp<3> = fp!(0.013);
+
+        // 1355.5997 of notional at 0.1%
+        let mut model = FeeModel::<8>::flat(fp!(-0.00005000), fp!(0.00100000));
+        let fee = model.fee(&Side::Bid, Liquidity::Taker, price, qty);
+        assert_eq!(fee.asset, Asset::Quote);
+        assert_eq!(fee.amount, fp!(1.35559970));
+
+        let mut cents = FeeModel::<2>::flat(fp!(-0.00005000), fp!(0.00100000));
+        let fee = |model: &FeeModel<2>, liquidity| model.fee(&Side::Bid, liquidity, price, qty);
+        assert_eq!(fee(&cents, Liquidity::Taker).amount, fp!(1.36));
+        // -0.067779985 back, never rounded up
+        assert_eq!(fee(&cents, Liquidity::Maker).amount, fp!(-0.06));
+        cents.rounding = Rounding::MidpointAwayFromZero;
+        assert_eq!(fee(&cents, Liquidity::Taker).amount, fp!(1.36));
+        assert_eq!(fee(&cents, Liquidity::Maker).amount, fp!(-0.07));
+        cents.rounding = Rounding::ToZero;
+        assert_eq!(fee(&cents, Liquidity::Taker).amount, fp!(1.35));
+
+        // In the asset received: base on the buy, quote on the sell
+        model.asset = FeeAsset::Received;
+        let fee = model.fee(&Side::Bid, Liquidity::Taker, price, qty);
+        assert_eq!(fee.asset, Asset::Base);
+        assert_eq!(fee.amount, fp!(0.00001300));
+        assert_eq!(fee.in_quote(price), fp!(1.35559970));
+        let fee = model.fee(&Side::Ask, Liquidity::Taker, price, qty);
+        assert_eq!(fee.asset, Asset::Quote);
+
+        model.asset = FeeAsset::Base;
+        let fee = model.fee(&Side::Ask, Liquidity::Maker, price, qty);
+        assert_eq!(fee.amount, fp!(-0.00000065));
+    }
+}
@@ -1,69 +1,361 @@
 use std::{
     error::Error,
     fmt,
-    ops::{Add, Div, Mul, Sub},
+    iter::{Product, Sum},
+    ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Rem, RemAssign, Sub, SubAssign},
     str::FromStr,
 };
 
 use serde::de::{self, Visitor};
 use serde::{Deserialize, Deserializer};
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Fp<const DECIMALS: usize>(i128);
 
 impl<const DECIMALS: usize> Fp<DECIMALS> {
     const SCALE: i128 = 10i128.pow(DECIMALS as u32);
 
+    #[inline]
     pub fn from_bytes<const N: usize>(buf: &[u8]) -> Result<Self, ParseFpError> {
-        let mut i = 0;
-        let mut sign = 1i128;
+        parse_fixed(buf, N, Self::SCALE).map(Fp)
+    }
+
+    /// Parses a decimal with an optional exponent, e.g. `1e-8` or `2.5E+3`.
+    /// Fails with `TooManyDecimals` if the value does not fit in `DECIMALS`
+    /// fraction digits exactly.
+    pub fn from_exp_bytes(buf: &[u8]) -> Result<Self, ParseFpError> {
+        Self::parse_exp(buf, None)
+    }
+
+    /// Same as `from_exp_bytes`, but rounds extra fraction digits away.
+    pub fn from_exp_bytes_rounded(buf: &[u8], rounding: Rounding) -> Result<Self, ParseFpError> {
+        Self::parse_exp(buf, Some(rounding))
+    }
+
+    fn parse_exp(buf: &[u8], rounding: Option<Rounding>) -> Result<Self, ParseFpError> {
+        parse_exp(buf, DECIMALS, rounding).map(Fp)
+    }
+}
 
-        // Check if there is a negative sign in the first element
-        if buf.get(0) == Some(&b'-') {
-            sign = -1;
+/// Parses a decimal with an optional exponent into units of
+/// `10^-decimals`. Extra fraction digits are rounded with `rounding`, or
+/// fail with `TooManyDecimals` without one.
+pub(crate) fn parse_exp(
+    buf: &[u8],
+    decimals: usize,
+    rounding: Option<Rounding>,
+) -> Result<i128, ParseFpError> {
+    let err = |kind| ParseFpError { kind };
+
+    let mut i = 0;
+    let negative = match buf.first() {
+        Some(b'-') => {
+            i += 1;
+            true
+        }
+        Some(b'+') => {
             i += 1;
+            false
         }
+        _ => false,
+    };
 
-        // Parse the integer part
-        let mut int_val: i128 = 0;
-        while buf[i] != b'.' {
-            // Check if the character is a digit
-            if buf[i] >= b'0' && buf[i] <= b'9' {
-                int_val = int_val * 10 + (buf[i] - b'0') as i128;
-            } else {
-                return Err(ParseFpError {
-                    kind: FpErrorKind::InvalidInteger,
-                });
+    // Collect the significant digits into the mantissa. Trailing zeros are
+    // only counted, so `1.50000...` doesn't overflow the mantissa.
+    let mut mantissa: u128 = 0;
+    let mut zeros: i32 = 0;
+    let mut frac_digits: i32 = 0;
+    let mut digits = 0;
+    let mut seen_dot = false;
+
+    while i < buf.len() {
+        match buf[i] {
+            // Leading zeros carry no information
+            b'0' if mantissa == 0 => {}
+            b'0' => zeros += 1,
+            d @ b'1'..=b'9' => {
+                mantissa = pow10(zeros as u32 + 1)
+                    .and_then(|p| mantissa.checked_mul(p))
+                    .and_then(|m| m.checked_add((d - b'0') as u128))
+                    .ok_or(err(FpErrorKind::Overflow))?;
+                zeros = 0;
+            }
+            b'.' if !seen_dot => {
+                seen_dot = true;
+                i += 1;
+                continue;
+            }
+            b'e' | b'E' => break,
+            _ if seen_dot => return Err(err(FpErrorKind::InvalidFraction)),
+            _ => return Err(err(FpErrorKind::InvalidInteger)),
+        }
+
+        if seen_dot {
+            frac_digits += 1;
+        }
+        digits += 1;
+        i += 1;
+    }
+
+    if digits == 0 {
+        return Err(err(FpErrorKind::InvalidFormat));
+    }
+
+    // Parse the exponent, saturating far outside of what i128 can hold
+    let mut exp: i32 = 0;
+    if i < buf.len() {
+        i += 1; // skip 'e'
+
+        let exp_negative = match buf.get(i) {
+            Some(b'-') => {
+                i += 1;
+                true
             }
+            Some(b'+') => {
+                i += 1;
+                false
+            }
+            _ => false,
+        };
 
+        if i >= buf.len() {
+            return Err(err(FpErrorKind::InvalidExponent));
+        }
+
+        while i < buf.len() {
+            match buf[i] {
+                d @ b'0'..=b'9' => exp = (exp * 10 + (d - b'0') as i32).min(10_000),
+                _ => return Err(err(FpErrorKind::InvalidExponent)),
+            }
             i += 1;
-            // Check if we have reached the end of the buffer
-            // If there is no decimal point, return an error
-            if i >= buf.len() {
-                return Err(ParseFpError {
-                    kind: FpErrorKind::InvalidFormat,
-                });
+        }
+
+        if exp_negative {
+            exp = -exp;
+        }
+    }
+
+    if mantissa == 0 {
+        return Ok(0);
+    }
+
+    // Value is mantissa * 10^shift in units of 10^-decimals
+    let shift = decimals as i32 + zeros - frac_digits + exp;
+
+    let magnitude = if shift >= 0 {
+        pow10(shift as u32)
+            .and_then(|p| mantissa.checked_mul(p))
+            .ok_or(err(FpErrorKind::Overflow))?
+    } else {
+        let Some(rounding) = rounding else {
+            return Err(err(FpErrorKind::TooManyDecimals));
+        };
+
+        // A divisor above u128::MAX leaves everything in the remainder
+        let (quot, rem, divisor) = match pow10(-shift as u32) {
+            Some(p) => (mantissa / p, mantissa % p, Some(p)),
+            None => (0, mantissa, None),
+        };
+
+        quot + round_up(rounding, negative, quot, rem, divisor) as u128
+    };
+
+    let value = if negative {
+        0i128.checked_sub_unsigned(magnitude)
+    } else {
+        i128::try_from(magnitude).ok()
+    };
+
+    value.ok_or(err(FpErrorKind::Overflow))
+}
+
+/// Backing integers the fast parser can accumulate into.
+pub(crate) trait FixedInt: Copy {
+    const ZERO: Self;
+
+    /// `self * 10 + digit`, `None` on overflow
+    fn push_digit(self, digit: u8) -> Option<Self>;
+    /// `self * scale + frac`, `None` on overflow
+    fn mul_add(self, scale: Self, frac: Self) -> Option<Self>;
+    fn negate(self) -> Self;
+}
+
+macro_rules! impl_fixed_int {
+    ($($int:ty),*) => {
+        $(
+            impl FixedInt for $int {
+                const ZERO: Self = 0;
+
+                #[inline(always)]
+                fn push_digit(self, digit: u8) -> Option<Self> {
+                    self.checked_mul(10)?.checked_add(digit as $int)
+                }
+
+                #[inline(always)]
+                fn mul_add(self, scale: Self, frac: Self) -> Option<Self> {
+                    self.checked_mul(scale)?.checked_add(frac)
+                }
+
+                #[inline(always)]
+                fn negate(self) -> Self {
+                    -self
+                }
             }
+        )*
+    };
+}
+
+impl_fixed_int!(i32, i64, i128);
+
+/// Fast path shared by `Fp`, its narrow siblings and `DynFp`: parses
+/// `[-]int.frac`, reading exactly `digits` fraction digits, into units of
+/// `1 / scale`.
+#[inline(always)]
+pub(crate) fn parse_fixed<T: FixedInt>(
+    buf: &[u8],
+    digits: usize,
+    scale: T,
+) -> Result<T, ParseFpError> {
+    parse_fixed_prefix(buf, digits, scale).map(|(value, _)| value)
+}
+
+/// Same as `parse_fixed`, also returning how many bytes were read. Anything
+/// after them is left unread.
+#[inline(always)]
+pub(crate) fn parse_fixed_prefix<T: FixedInt>(
+    buf: &[u8],
+    digits: usize,
+    scale: T,
+) -> Result<(T, usize), ParseFpError> {
+    let overflow = ParseFpError {
+        kind: FpErrorKind::Overflow,
+    };
+    let mut i = 0;
+    let mut negative = false;
+
+    // Check if there is a negative sign in the first element
+    if buf.first() == Some(&b'-') {
+        negative = true;
+        i += 1;
+    }
+
+    if i >= buf.len() {
+        return Err(ParseFpError {
+            kind: FpErrorKind::InvalidFormat,
+        });
+    }
+
+    // Parse the integer part
+    let mut int_val = T::ZERO;
+    while buf[i] != b'.' {
+        // Check if the character is a digit
+        if buf[i] >= b'0' && buf[i] <= b'9' {
+            int_val = int_val.push_digit(buf[i] - b'0').ok_or(overflow.clone())?;
+        } else {
+            return Err(ParseFpError {
+                kind: FpErrorKind::InvalidInteger,
+            });
         }
-        i += 1; // skip '.'
 
-        // Parse the fractional part
-        let mut frac_val: i128 = 0;
-        let mut j = 0;
-        while j < N {
-            if buf[i] >= b'0' && buf[i] <= b'9' {
-                frac_val = frac_val * 10 + (buf[i] - b'0') as i128;
-            } else {
+        i += 1;
+        // Check if we have reached the end of the buffer
+        // If there is no decimal point, return an error
+        if i >= buf.len() {
+            return Err(ParseFpError {
+                kind: FpErrorKind::InvalidFormat,
+            });
+        }
+    }
+    i += 1; // skip '.'
+
+    // Parse the fractional part
+    let mut frac_val = T::ZERO;
+    let mut j = 0;
+    while j < digits {
+        match buf.get(i) {
+            Some(&b) if b.is_ascii_digit() => {
+                frac_val = frac_val.push_digit(b - b'0').ok_or(overflow.clone())?
+            }
+            _ => {
                 return Err(ParseFpError {
                     kind: FpErrorKind::InvalidFraction,
                 });
             }
-
-            i += 1;
-            j += 1;
         }
 
-        Ok(Fp(sign * (int_val * Self::SCALE + frac_val)))
+        i += 1;
+        j += 1;
+    }
+
+    let value = int_val.mul_add(scale, frac_val).ok_or(overflow)?;
+    Ok((if negative { value.negate() } else { value }, i))
+}
+
+/// How to drop fraction digits that don't fit in the target precision.
+/// Mirrors `rust_decimal::RoundingStrategy`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Rounding {
+    MidpointNearestEven,
+    MidpointAwayFromZero,
+    MidpointTowardZero,
+    ToZero,
+    AwayFromZero,
+    ToNegativeInfinity,
+    ToPositiveInfinity,
+}
+
+#[inline]
+fn pow10(exp: u32) -> Option<u128> {
+    10u128.checked_pow(exp)
+}
+
+/// Whether the magnitude `quot` has to be bumped to honour `rounding`, given
+/// the remainder `rem` of a division by `divisor` (`None` meaning a divisor
+/// too large for u128).
+pub(crate) fn round_up(
+    rounding: Rounding,
+    negative: bool,
+    quot: u128,
+    rem: u128,
+    divisor: Option<u128>,
+) -> bool {
+    if rem == 0 {
+        return false;
+    }
+
+    // Compare the remainder against half of the divisor without overflowing
+    let half = match divisor {
+        Some(d) => rem.cmp(&(d - rem)) as i8,
+        None => -1,
+    };
+
+    match rounding {
+        Rounding::ToZero => false,
+        Rounding::AwayFromZero => true,
+        Rounding::ToNegativeInfinity => negative,
+        Rounding::ToPositiveInfinity => !negative,
+        Rounding::MidpointAwayFromZero => half >= 0,
+        Rounding::MidpointTowardZero => half > 0,
+        Rounding::MidpointNearestEven => half > 0 || (half == 0 && quot % 2 == 1),
+    }
+}
+
+/// `raw` at `from` decimals moved to `to` decimals, dropped digits rounded
+/// as `rounding` says
+pub(crate) fn rescale(raw: i128, from: usize, to: usize, rounding: Rounding) -> i128 {
+    if from <= to {
+        return raw * 10i128.pow((to - from) as u32);
+    }
+    let divisor = pow10((from - to) as u32);
+    let magnitude = raw.unsigned_abs();
+    let (quot, rem) = match divisor {
+        Some(d) => (magnitude / d, magnitude % d),
+        None => (0, magnitude),
+    };
+    let quot = (quot + round_up(rounding, raw < 0, quot, rem, divisor) as u128) as i128;
+    match raw < 0 {
+        true => -quot,
+        false => quot,
     }
 }
 
@@ -101,11 +393,407 @@ impl<const DECIMALS: usize> Div for Fp<DECIMALS> {
     }
 }
 
+/// Builds an `Fp` from a decimal literal at compile time, inferring the
+/// precision from context or taking it after a semicolon.
+///
+/// ```
+/// use e002::{fp, fp::Fp};
+///
+/// const TICK: Fp<2> = fp!(0.01);
+/// let qty = fp!(-1.5; 3);
+/// assert_eq!(qty, Fp::<3>::from_raw(-1500));
+/// ```
+///
+/// Literals with more decimals than the target fail to compile:
+///
+/// ```compile_fail
+/// use e002::{fp, fp::Fp};
+///
+/// const TICK: Fp<2> = fp!(0.001);
+/// ```
+#[macro_export]
+macro_rules! fp {
+    ($lit:literal) => {
+        const { $crate::fp::Fp::from_literal(stringify!($lit)) }
+    };
+    (-$lit:literal) => {
+        const { $crate::fp::Fp::from_literal(concat!("-", stringify!($lit))) }
+    };
+    ($lit:literal; $decimals:expr) => {
+        const { $crate::fp::Fp::<$decimals>::from_literal(stringify!($lit)) }
+    };
+    (-$lit:literal; $decimals:expr) => {
+        const { $crate::fp::Fp::<$decimals>::from_literal(concat!("-", stringify!($lit))) }
+    };
+}
+
+impl<const DECIMALS: usize> Neg for Fp<DECIMALS> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Fp(-self.0)
+    }
+}
+
+impl<const DECIMALS: usize> Rem for Fp<DECIMALS> {
+    type Output = Self;
+
+    // Both sides share the scale, so the raw remainder is exact
+    fn rem(self, rhs: Self) -> Self {
+        Fp(self.0 % rhs.0)
+    }
+}
+
+macro_rules! impl_assign_op {
+    ($($trait:ident $method:ident $op:tt),*) => {
+        $(
+            impl<const DECIMALS: usize> $trait for Fp<DECIMALS> {
+                #[inline]
+                fn $method(&mut self, rhs: Self) {
+                    *self = *self $op rhs;
+                }
+            }
+        )*
+    };
+}
+
+impl_assign_op!(
+    AddAssign add_assign +,
+    SubAssign sub_assign -,
+    MulAssign mul_assign *,
+    DivAssign div_assign /,
+    RemAssign rem_assign %
+);
+
+impl<const DECIMALS: usize> Sum for Fp<DECIMALS> {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::ZERO, Add::add)
+    }
+}
+
+impl<'a, const DECIMALS: usize> Sum<&'a Fp<DECIMALS>> for Fp<DECIMALS> {
+    fn sum<I: Iterator<Item = &'a Self>>(iter: I) -> Self {
+        iter.copied().sum()
+    }
+}
+
+impl<const DECIMALS: usize> Product for Fp<DECIMALS> {
+    fn product<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::ONE, Mul::mul)
+    }
+}
+
+impl<'a, const DECIMALS: usize> Product<&'a Fp<DECIMALS>> for Fp<DECIMALS> {
+    fn product<I: Iterator<Item = &'a Self>>(iter: I) -> Self {
+        iter.copied().product()
+    }
+}
+
+impl<const DECIMALS: usize> Fp<DECIMALS> {
+    pub const ZERO: Self = Fp(0);
+    pub const ONE: Self = Fp(Self::SCALE);
+    pub const MIN: Self = Fp(i128::MIN);
+    pub const MAX: Self = Fp(i128::MAX);
+
+    /// Wraps a raw value counted in units of `10^-DECIMALS`.
+    #[inline]
+    pub const fn from_raw(raw: i128) -> Self {
+        Fp(raw)
+    }
+
+    #[inline]
+    pub const fn raw(self) -> i128 {
+        self.0
+    }
+
+    #[inline]
+    pub const fn from_int(int: i128) -> Self {
+        Fp(int * Self::SCALE)
+    }
+
+    /// Builds `int.frac` where `frac` counts units of `10^-DECIMALS`, so
+    /// `Fp::<3>::from_parts(-1, 234)` is `-1.234`. Panics if `frac` doesn't
+    /// fit in `DECIMALS` digits.
+    ///
+    /// The sign comes from `int` alone, so values strictly between -1 and 0
+    /// can't be built this way: `from_parts(0, 500)` is always `0.500`.
+    /// Negate it, or use `fp!` or `from_raw`, for `-0.500`.
+    #[inline]
+    pub const fn from_parts(int: i128, frac: u64) -> Self {
+        assert!((frac as i128) < Self::SCALE, "fraction has too many digits");
+
+        let value = int * Self::SCALE;
+        if int < 0 {
+            Fp(value - frac as i128)
+        } else {
+            Fp(value + frac as i128)
+        }
+    }
+
+    /// Parses a plain decimal literal (`-12.5`, `3`) in a const context, with
+    /// at most `DECIMALS` fraction digits. Panics on anything else, which is
+    /// a compile error when evaluated at compile time; see `fp!`.
+    pub const fn from_literal(s: &str) -> Self {
+        let buf = s.as_bytes();
+        let mut i = 0;
+        let negative = !buf.is_empty() && buf[0] == b'-';
+        if negative {
+            i += 1;
+        }
+
+        let mut value: i128 = 0;
+        let mut digits = 0;
+        let mut decimals = 0;
+        let mut seen_dot = false;
+
+        while i < buf.len() {
+            let b = buf[i];
+            if b == b'.' && !seen_dot {
+                seen_dot = true;
+            } else if b == b'_' {
+                // Digit separators as in Rust literals
+            } else if b.is_ascii_digit() {
+                if seen_dot {
+                    decimals += 1;
+                    assert!(decimals <= DECIMALS, "literal has too many decimals");
+                }
+                value = match value.checked_mul(10) {
+                    Some(v) => match v.checked_add((b - b'0') as i128) {
+                        Some(v) => v,
+                        None => panic!("literal out of range"),
+                    },
+                    None => panic!("literal out of range"),
+                };
+                digits += 1;
+            } else {
+                panic!("invalid decimal literal");
+            }
+            i += 1;
+        }
+        assert!(digits > 0, "invalid decimal literal");
+
+        // Pad the missing fraction digits
+        while decimals < DECIMALS {
+            value = match value.checked_mul(10) {
+                Some(v) => v,
+                None => panic!("literal out of range"),
+            };
+            decimals += 1;
+        }
+
+        Fp(if negative { -value } else { value })
+    }
+
+    #[inline]
+    pub fn abs(self) -> Self {
+        Fp(self.0.abs())
+    }
+
+    /// `ONE`, `ZERO` or `-ONE` depending on the sign.
+    #[inline]
+    pub fn signum(self) -> Self {
+        Fp(self.0.signum() * Self::SCALE)
+    }
+
+    #[inline]
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    #[inline]
+    pub fn is_positive(self) -> bool {
+        self.0 > 0
+    }
+
+    #[inline]
+    pub fn is_negative(self) -> bool {
+        self.0 < 0
+    }
+
+    #[inline]
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        self.0.checked_add(rhs.0).map(Fp)
+    }
+
+    #[inline]
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.0.checked_sub(rhs.0).map(Fp)
+    }
+
+    #[inline]
+    pub fn checked_mul(self, rhs: Self) -> Option<Self> {
+        self.0.checked_mul(rhs.0).map(|v| Fp(v / Self::SCALE))
+    }
+
+    #[inline]
+    pub fn checked_div(self, rhs: Self) -> Option<Self> {
+        self.0.checked_mul(Self::SCALE)?.checked_div(rhs.0).map(Fp)
+    }
+
+    #[inline]
+    pub fn checked_rem(self, rhs: Self) -> Option<Self> {
+        self.0.checked_rem(rhs.0).map(Fp)
+    }
+
+    #[inline]
+    pub fn checked_neg(self) -> Option<Self> {
+        self.0.checked_neg().map(Fp)
+    }
+}
+
+impl<const DECIMALS: usize> Fp<DECIMALS> {
+    /// Longest output of `write_to`: a sign, 39 digits and the decimal point.
+    pub const MAX_STR_LEN: usize = 41;
+
+    /// Renders the value with exactly `DECIMALS` fraction digits into `buf`
+    /// and returns the number of bytes written. Panics if `buf` is too short,
+    /// `MAX_STR_LEN` bytes are always enough.
+    #[inline]
+    pub fn write_to(&self, buf: &mut [u8]) -> usize {
+        write_decimal(self.0 < 0, self.0.unsigned_abs(), DECIMALS, buf)
+    }
+
+    /// Same as `write_to`, but drops trailing zeros of the fraction (and the
+    /// decimal point if nothing is left after it).
+    #[inline]
+    pub fn write_trimmed_to(&self, buf: &mut [u8]) -> usize {
+        let (magnitude, decimals) = trim_zeros(self.0.unsigned_abs(), DECIMALS);
+        write_decimal(self.0 < 0, magnitude, decimals, buf)
+    }
+}
+
+#[inline]
+pub(crate) fn trim_zeros(mut magnitude: u128, mut decimals: usize) -> (u128, usize) {
+    while decimals > 0 && magnitude.is_multiple_of(10) {
+        magnitude /= 10;
+        decimals -= 1;
+    }
+    (magnitude, decimals)
+}
+
+/// Writes `magnitude * 10^-decimals` right to left, returns the length.
+pub(crate) fn write_decimal(
+    negative: bool,
+    magnitude: u128,
+    decimals: usize,
+    buf: &mut [u8],
+) -> usize {
+    let digits = match magnitude.checked_ilog10() {
+        Some(log) => (log as usize + 1).max(decimals + 1),
+        None => decimals + 1,
+    };
+    let len = negative as usize + digits + (decimals > 0) as usize;
+    let buf = &mut buf[..len];
+
+    let mut pos = len;
+    let mut n = magnitude;
+    for k in 0..digits {
+        if k == decimals && decimals > 0 {
+            pos -= 1;
+            buf[pos] = b'.';
+        }
+
+        // Stay on 64-bit division as soon as the rest fits
+        let digit = if n <= u64::MAX as u128 {
+            let m = n as u64;
+            n = (m / 10) as u128;
+            (m % 10) as u8
+        } else {
+            let d = (n % 10) as u8;
+            n /= 10;
+            d
+        };
+
+        pos -= 1;
+        buf[pos] = b'0' + digit;
+    }
+
+    if negative {
+        buf[0] = b'-';
+    }
+
+    len
+}
+
+/// Prints exactly `DECIMALS` fraction digits. A precision (`{:.2}`) rounds
+/// half to even or pads with zeros, the alternate flag (`{:#}`) trims
+/// trailing zeros. Width, fill and sign flags behave as for integers.
+impl<const DECIMALS: usize> fmt::Display for Fp<DECIMALS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut magnitude = self.0.unsigned_abs();
+        let mut decimals = DECIMALS;
+        let mut padding = 0;
+
+        match f.precision() {
+            Some(p) if p < DECIMALS => {
+                let divisor = 10u128.pow((DECIMALS - p) as u32);
+                let (quot, rem) = (magnitude / divisor, magnitude % divisor);
+                let negative = self.0 < 0;
+                magnitude = quot
+                    + round_up(
+                        Rounding::MidpointNearestEven,
+                        negative,
+                        quot,
+                        rem,
+                        Some(divisor),
+                    ) as u128;
+                decimals = p;
+            }
+            Some(p) => padding = p - DECIMALS,
+            None => {}
+        }
+
+        if f.alternate() {
+            (magnitude, decimals) = trim_zeros(magnitude, decimals);
+            padding = 0;
+        }
+
+        let nonnegative = self.0 >= 0 || magnitude == 0;
+        let mut buf = [0u8; 128];
+        let len = write_decimal(false, magnitude, decimals, &mut buf);
+
+        if len + padding + (decimals == 0) as usize <= buf.len() {
+            let mut end = len;
+            if padding > 0 {
+                if decimals == 0 {
+                    buf[end] = b'.';
+                    end += 1;
+                }
+                buf[end..end + padding].fill(b'0');
+                end += padding;
+            }
+            // Only ASCII digits and '.' were written
+            let s = std::str::from_utf8(&buf[..end]).unwrap();
+            f.pad_integral(nonnegative, "", s)
+        } else {
+            // Absurd precisions don't fit on the stack
+            let mut s = String::from_utf8_lossy(&buf[..len]).into_owned();
+            if decimals == 0 {
+                s.push('.');
+            }
+            s.extend(std::iter::repeat_n('0', padding));
+            f.pad_integral(nonnegative, "", &s)
+        }
+    }
+}
+
+impl<const DECIMALS: usize> fmt::Debug for Fp<DECIMALS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ParseFpError {
     pub(super) kind: FpErrorKind,
 }
 
+impl ParseFpError {
+    pub fn kind(&self) -> &FpErrorKind {
+        &self.kind
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[non_exhaustive]
 pub enum FpErrorKind {
@@ -113,6 +801,7 @@ pub enum FpErrorKind {
     InvalidFraction,
     InvalidFormat,
     TooManyDecimals,
+    InvalidExponent,
     Overflow,
 }
 
@@ -131,6 +820,7 @@ impl Error for ParseFpError {
             FpErrorKind::InvalidFraction => "invalid digit found in string",
             FpErrorKind::InvalidFormat => "invalid format",
             FpErrorKind::TooManyDecimals => "too many decimals",
+            FpErrorKind::InvalidExponent => "invalid exponent",
             FpErrorKind::Overflow => "number too large to fit in target type",
         }
     }
@@ -155,7 +845,7 @@ impl<'de, const N: usize> Deserialize<'de> for Fp<N> {
             where
                 E: de::Error,
             {
-                Fp::<N>::from_bytes::<N>(s.as_bytes()).map_err(E::custom)
+                Fp::<N>::parse(s.as_bytes()).map_err(E::custom)
             }
         }
 
@@ -167,7 +857,137 @@ impl<const DECIMALS: usize> FromStr for Fp<DECIMALS> {
     type Err = ParseFpError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Fp::<DECIMALS>::from_bytes::<DECIMALS>(s.as_bytes())
+        Fp::<DECIMALS>::parse(s.as_bytes())
+    }
+}
+
+impl<const DECIMALS: usize> Fp<DECIMALS> {
+    // Plain decimals take the fast path, exponent notation the exact one.
+    // Only what the fast path failed on or left unread is looked at for an
+    // exponent.
+    #[inline]
+    pub(crate) fn parse(buf: &[u8]) -> Result<Self, ParseFpError> {
+        parse_decimal(buf, DECIMALS, Self::SCALE).map(Fp)
+    }
+}
+
+/// `Fp::parse` at a precision known at runtime, `scale` being
+/// `10^decimals`
+#[inline]
+pub(crate) fn parse_decimal(
+    buf: &[u8],
+    decimals: usize,
+    scale: i128,
+) -> Result<i128, ParseFpError> {
+    match parse_fixed_prefix(buf, decimals, scale) {
+        Ok((raw, read)) if !has_exponent(&buf[read..]) => Ok(raw),
+        Err(err) if !has_exponent(buf) => Err(err),
+        _ => parse_exp(buf, decimals, None),
+    }
+}
+
+pub(crate) fn has_exponent(buf: &[u8]) -> bool {
+    buf.iter().any(|&b| b == b'e' || b == b'E')
+}
+
+#[cfg(feature = "num-traits")]
+mod num_impls {
+    use super::*;
+    use num_traits::{
+        Bounded, CheckedAdd, CheckedDiv, CheckedMul, CheckedNeg, CheckedRem, CheckedSub, Num, One,
+        Signed, Zero,
+    };
+
+    impl<const DECIMALS: usize> Zero for Fp<DECIMALS> {
+        fn zero() -> Self {
+            Self::ZERO
+        }
+
+        fn is_zero(&self) -> bool {
+            Fp::is_zero(*self)
+        }
+    }
+
+    impl<const DECIMALS: usize> One for Fp<DECIMALS> {
+        fn one() -> Self {
+            Self::ONE
+        }
+    }
+
+    impl<const DECIMALS: usize> Num for Fp<DECIMALS> {
+        type FromStrRadixErr = ParseFpError;
+
+        // Only decimal strings make sense for a decimal fixed point
+        fn from_str_radix(s: &str, radix: u32) -> Result<Self, Self::FromStrRadixErr> {
+            if radix != 10 {
+                return Err(ParseFpError {
+                    kind: FpErrorKind::InvalidFormat,
+                });
+            }
+            s.parse()
+        }
+    }
+
+    impl<const DECIMALS: usize> Signed for Fp<DECIMALS> {
+        fn abs(&self) -> Self {
+            Fp::abs(*self)
+        }
+
+        fn abs_sub(&self, other: &Self) -> Self {
+            if self <= other {
+                Self::ZERO
+            } else {
+                *self - *other
+            }
+        }
+
+        fn signum(&self) -> Self {
+            Fp::signum(*self)
+        }
+
+        fn is_positive(&self) -> bool {
+            Fp::is_positive(*self)
+        }
+
+        fn is_negative(&self) -> bool {
+            Fp::is_negative(*self)
+        }
+    }
+
+    impl<const DECIMALS: usize> Bounded for Fp<DECIMALS> {
+        fn min_value() -> Self {
+            Self::MIN
+        }
+
+        fn max_value() -> Self {
+            Self::MAX
+        }
+    }
+
+    macro_rules! impl_checked_op {
+        ($($trait:ident $method:ident),*) => {
+            $(
+                impl<const DECIMALS: usize> $trait for Fp<DECIMALS> {
+                    fn $method(&self, rhs: &Self) -> Option<Self> {
+                        Fp::$method(*self, *rhs)
+                    }
+                }
+            )*
+        };
+    }
+
+    impl_checked_op!(
+        CheckedAdd checked_add,
+        CheckedSub checked_sub,
+        CheckedMul checked_mul,
+        CheckedDiv checked_div,
+        CheckedRem checked_rem
+    );
+
+    impl<const DECIMALS: usize> CheckedNeg for Fp<DECIMALS> {
+        fn checked_neg(&self) -> Option<Self> {
+            Fp::checked_neg(*self)
+        }
     }
 }
 
@@ -219,6 +1039,26 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_fp_parsing_overflow() {
+        assert_eq!(
+            Fp::<3>::from_str("170141183460469231731687303715884105.727")
+                .unwrap()
+                .0,
+            i128::MAX
+        );
+        assert_eq!(
+            Fp::<3>::from_str("170141183460469231731687303715884106.000")
+                .unwrap_err()
+                .kind(),
+            &FpErrorKind::Overflow
+        );
+        assert_eq!(
+            Fp::<3>::from_str("1.2").unwrap_err().kind(),
+            &FpErrorKind::InvalidFraction
+        );
+    }
+
     #[test]
     fn test_fp_edge_cases() {
         // Test zero
@@ -254,4 +1094,204 @@ mod tests {
         assert_eq!(one + neg_one, zero);
         assert_eq!(one * neg_one, neg_one);
     }
+
+    #[test]
+    fn test_fp_exponent_parsing() {
+        assert_eq!(Fp::<8>::from_str("1e-8").unwrap().0, 1);
+        assert_eq!(Fp::<3>::from_str("2.5E+3").unwrap().0, 2_500_000);
+        assert_eq!(Fp::<3>::from_str("-1.5e2").unwrap().0, -150_000);
+        assert_eq!(Fp::<3>::from_str("12345e-3").unwrap().0, 12345);
+        assert_eq!(Fp::<3>::from_str("0.00e99").unwrap().0, 0);
+        // The exponent comes after the digits the fast path reads
+        assert_eq!(Fp::<3>::from_str("1.2345e2").unwrap().0, 123_450);
+        assert_eq!(
+            Fp::<3>::from_str("0.0000000000000000000000000000000000000000001e43")
+                .unwrap()
+                .0,
+            1000
+        );
+        assert_eq!(
+            Fp::<3>::from_str("1.50000000000000000000000000000000000000000e0")
+                .unwrap()
+                .0,
+            1500
+        );
+
+        // Not representable in 3 decimals
+        assert_eq!(
+            Fp::<3>::from_str("1e-4").unwrap_err().kind(),
+            &FpErrorKind::TooManyDecimals
+        );
+
+        // Outside of the i128 range
+        assert_eq!(
+            Fp::<3>::from_str("1e36").unwrap_err().kind(),
+            &FpErrorKind::Overflow
+        );
+        assert_eq!(
+            Fp::<3>::from_str("1e10000000").unwrap_err().kind(),
+            &FpErrorKind::Overflow
+        );
+
+        // Malformed exponents
+        assert_eq!(
+            Fp::<3>::from_str("1e").unwrap_err().kind(),
+            &FpErrorKind::InvalidExponent
+        );
+        assert_eq!(
+            Fp::<3>::from_str("1e+x").unwrap_err().kind(),
+            &FpErrorKind::InvalidExponent
+        );
+        assert_eq!(
+            Fp::<3>::from_str("e5").unwrap_err().kind(),
+            &FpErrorKind::InvalidFormat
+        );
+    }
+
+    #[test]
+    fn test_fp_exponent_rounding() {
+        let parse = |s: &str, r| Fp::<2>::from_exp_bytes_rounded(s.as_bytes(), r).unwrap().0;
+
+        assert_eq!(parse("1.005e0", Rounding::MidpointNearestEven), 100);
+        assert_eq!(parse("1.015e0", Rounding::MidpointNearestEven), 102);
+        assert_eq!(parse("1.005e0", Rounding::MidpointAwayFromZero), 101);
+        assert_eq!(parse("1.005e0", Rounding::MidpointTowardZero), 100);
+        assert_eq!(parse("1.0051e0", Rounding::MidpointTowardZero), 101);
+        assert_eq!(parse("-1.001e0", Rounding::ToZero), -100);
+        assert_eq!(parse("-1.001e0", Rounding::AwayFromZero), -101);
+        assert_eq!(parse("-1.001e0", Rounding::ToNegativeInfinity), -101);
+        assert_eq!(parse("-1.001e0", Rounding::ToPositiveInfinity), -100);
+        assert_eq!(parse("1e-50", Rounding::ToPositiveInfinity), 1);
+        assert_eq!(parse("1e-50", Rounding::MidpointNearestEven), 0);
+    }
+
+    #[test]
+    fn test_fp_display() {
+        let a = Fp::<3>::from_str("1.230").unwrap();
+        let b = Fp::<3>::from_str("-0.005").unwrap();
+
+        assert_eq!(a.to_string(), "1.230");
+        assert_eq!(b.to_string(), "-0.005");
+        assert_eq!(format!("{:?}", a), "1.230");
+        assert_eq!(Fp::<0>(-42).to_string(), "-42");
+
+        // Precision rounds half to even or pads
+        assert_eq!(format!("{:.2}", a), "1.23");
+        assert_eq!(format!("{:.2}", b), "0.00");
+        assert_eq!(format!("{:.2}", Fp::<3>(-15)), "-0.02");
+        assert_eq!(format!("{:.0}", a), "1");
+        assert_eq!(format!("{:.5}", a), "1.23000");
+        assert_eq!(format!("{:.2}", Fp::<0>(7)), "7.00");
+
+        // Width, fill and sign
+        assert_eq!(format!("{:>8}", a), "   1.230");
+        assert_eq!(format!("{:<8}|", a), "1.230   |");
+        assert_eq!(format!("{:08}", b), "-000.005");
+        assert_eq!(format!("{:+}", a), "+1.230");
+        assert_eq!(format!("{:*^9.1}", a), "***1.2***");
+
+        // Trimmed
+        assert_eq!(format!("{:#}", a), "1.23");
+        assert_eq!(format!("{:#}", Fp::<3>(5000)), "5");
+        assert_eq!(format!("{:#}", Fp::<3>(0)), "0");
+    }
+
+    #[test]
+    fn test_fp_write_to() {
+        let mut buf = [0u8; Fp::<2>::MAX_STR_LEN];
+
+        let n = Fp::<2>::from_str("104276.90").unwrap().write_to(&mut buf);
+        assert_eq!(&buf[..n], b"104276.90");
+
+        let n = Fp::<2>::from_str("104276.90")
+            .unwrap()
+            .write_trimmed_to(&mut buf);
+        assert_eq!(&buf[..n], b"104276.9");
+
+        let n = Fp::<2>::from_str("-0.07").unwrap().write_to(&mut buf);
+        assert_eq!(&buf[..n], b"-0.07");
+
+        let n = Fp::<2>(i128::MIN).write_to(&mut buf);
+        assert_eq!(
+            &buf[..n],
+            i128::MIN.to_string().replace("728", "7.28").as_bytes()
+        );
+        assert_eq!(n, Fp::<2>::MAX_STR_LEN);
+    }
+
+    #[test]
+    fn test_fp_ordering_and_ops() {
+        let a = Fp::<3>::from_str("1.500").unwrap();
+        let b = Fp::<3>::from_str("-0.250").unwrap();
+
+        assert!(b < a);
+        assert_eq!(a.max(b), a);
+        assert_eq!(a.min(b), b);
+        assert_eq!(Fp::<3>::default(), Fp::<3>::ZERO);
+
+        assert_eq!(-a, Fp::<3>::from_str("-1.500").unwrap());
+        assert_eq!(b.abs(), Fp::<3>::from_str("0.250").unwrap());
+        assert_eq!(b.signum(), -Fp::<3>::ONE);
+        assert_eq!(Fp::<3>::ZERO.signum(), Fp::<3>::ZERO);
+        assert_eq!(a % Fp::<3>::from_str("0.400").unwrap().abs(), Fp(300));
+
+        let mut c = a;
+        c += a;
+        c -= b;
+        c *= Fp::<3>::from_str("2.000").unwrap();
+        c /= Fp::<3>::from_str("0.500").unwrap();
+        c %= Fp::<3>::from_str("10.000").unwrap();
+        assert_eq!(c, Fp::<3>::from_str("3.000").unwrap());
+
+        let levels = [a, b, a];
+        assert_eq!(levels.iter().sum::<Fp<3>>(), Fp(2750));
+        assert_eq!(levels.into_iter().product::<Fp<3>>(), Fp(-562));
+
+        assert_eq!(Fp::<3>::MAX.checked_add(Fp::<3>::ONE), None);
+        assert_eq!(Fp::<3>::MAX.checked_mul(a), None);
+        assert_eq!(a.checked_div(Fp::<3>::ZERO), None);
+        assert_eq!(a.checked_div(a), Some(Fp::<3>::ONE));
+
+        let map = std::collections::BTreeMap::from([(a, 1), (b, 2)]);
+        assert_eq!(map.keys().next(), Some(&b));
+    }
+
+    #[cfg(feature = "num-traits")]
+    #[test]
+    fn test_fp_num_traits() {
+        use num_traits::{Bounded, CheckedMul, Num, One, Signed, Zero};
+
+        fn total<T: Num + Copy>(xs: &[T]) -> T {
+            xs.iter().fold(T::zero(), |acc, &x| acc + x)
+        }
+
+        let a = Fp::<2>::from_str_radix("1.25", 10).unwrap();
+        assert!(Fp::<2>::from_str_radix("1.25", 16).is_err());
+        assert_eq!(total(&[a, a]), Fp(250));
+        assert!(<Fp<2> as Zero>::zero().is_zero());
+        assert_eq!(<Fp<2> as One>::one(), Fp(100));
+        assert_eq!(Signed::abs(&-a), a);
+        assert_eq!(<Fp<2> as Bounded>::max_value(), Fp::<2>::MAX);
+        assert_eq!(CheckedMul::checked_mul(&Fp::<2>::MAX, &a), None);
+    }
+
+    #[test]
+    fn test_fp_const_constructors() {
+        const TICK: Fp<2> = fp!(0.01);
+        const LOT: Fp<3> = fp!(1_000.5);
+        const NEG: Fp<3> = fp!(-0.005);
+
+        assert_eq!(TICK, Fp::<2>::from_str("0.01").unwrap());
+        assert_eq!(LOT, Fp::<3>::from_str("1000.500").unwrap());
+        assert_eq!(NEG, Fp::<3>::from_str("-0.005").unwrap());
+        assert_eq!(fp!(7; 2), Fp::<2>::from_int(7));
+        assert_eq!(fp!(-7; 2).raw(), -700);
+
+        const PARTS: Fp<3> = Fp::from_parts(-1, 234);
+        assert_eq!(PARTS, Fp::<3>::from_str("-1.234").unwrap());
+        assert_eq!(Fp::<3>::from_parts(2, 5), Fp::from_raw(2005));
+        // No sign for a zero integer part
+        assert_eq!(Fp::<3>::from_parts(0, 500), fp!(0.500));
+        assert_eq!(-Fp::<3>::from_parts(0, 500), fp!(-0.500));
+    }
 }
@@ -0,0 +1,567 @@
+use std::{error::Error, fmt};
+
+use crate::dynfp::DynFp;
+use crate::fp::Fp;
+use e001::orderbook::Side;
+use serde::{Deserialize, Deserializer, de};
+
+// Instrument metadata from Binance `exchangeInfo` (spot `/api/v3` and
+// futures `/fapi/v1`), and order checks against it. Filter values come in
+// whatever precision the exchange picked for the symbol ("0.00000100", "5",
+// "0.10"), so they decode into `DynFp` first. `ExchangeInfo::spec` then
+// converts one symbol into an `InstrumentSpec` at the precisions we trade
+// it at, failing if a filter value does not fit exactly.
+
+/// Scale of the percent-price multipliers
+pub type Multiplier = Fp<8>;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExchangeInfo {
+    pub symbols: Vec<SymbolInfo>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SymbolInfo {
+    pub symbol: String,
+    pub status: String,
+    pub base_asset: String,
+    pub quote_asset: String,
+    pub filters: Vec<Filter>,
+}
+
+/// The filters we enforce locally; any other filter type is kept as `Other`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "filterType", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Filter {
+    #[serde(rename_all = "camelCase")]
+    PriceFilter {
+        #[serde(deserialize_with = "decimal")]
+        min_price: DynFp,
+        #[serde(deserialize_with = "decimal")]
+        max_price: DynFp,
+        #[serde(deserialize_with = "decimal")]
+        tick_size: DynFp,
+    },
+    #[serde(rename_all = "camelCase")]
+    LotSize {
+        #[serde(deserialize_with = "decimal")]
+        min_qty: DynFp,
+        #[serde(deserialize_with = "decimal")]
+        max_qty: DynFp,
+        #[serde(deserialize_with = "decimal")]
+        step_size: DynFp,
+    },
+    /// Futures calls the field `notional`
+    #[serde(rename_all = "camelCase")]
+    MinNotional {
+        #[serde(alias = "notional", deserialize_with = "decimal")]
+        min_notional: DynFp,
+    },
+    /// Spot's replacement for `MIN_NOTIONAL`, with an upper bound
+    #[serde(rename_all = "camelCase")]
+    Notional {
+        #[serde(deserialize_with = "decimal")]
+        min_notional: DynFp,
+        #[serde(deserialize_with = "decimal")]
+        max_notional: DynFp,
+    },
+    #[serde(rename_all = "camelCase")]
+    PercentPrice {
+        #[serde(deserialize_with = "decimal")]
+        multiplier_up: DynFp,
+        #[serde(deserialize_with = "decimal")]
+        multiplier_down: DynFp,
+    },
+    #[serde(other)]
+    Other,
+}
+
+// Filter values, with or without a fraction
+fn decimal<'de, D>(deserializer: D) -> Result<DynFp, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    match s.contains('.') {
+        true => s.parse().map_err(de::Error::custom),
+        false => s
+            .parse()
+            .map(|v| DynFp::from_raw(v, 0))
+            .map_err(de::Error::custom),
+    }
+}
+
+impl ExchangeInfo {
+    pub fn from_serde_json(buf: &[u8]) -> serde_json::Result<Self> {
+        serde_json::from_slice(buf)
+    }
+
+    pub fn symbol(&self, symbol: &str) -> Option<&SymbolInfo> {
+        self.symbols.iter().find(|s| s.symbol == symbol)
+    }
+
+    /// Order rules for `symbol` with prices in `P` and quantities in `Q`
+    /// decimals
+    pub fn spec<const P: usize, const Q: usize>(
+        &self,
+        symbol: &str,
+    ) -> Result<InstrumentSpec<P, Q>, SpecError> {
+        self.symbol(symbol).ok_or(SpecError::UnknownSymbol)?.spec()
+    }
+}
+
+/// Order rules for one instrument. A zero bound means the exchange does not
+/// enforce it, as in the filters themselves.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstrumentSpec<const P: usize, const Q: usize> {
+    pub symbol: String,
+    pub trading: bool,
+    pub tick_size: Fp<P>,
+    pub min_price: Fp<P>,
+    pub max_price: Fp<P>,
+    pub step_size: Fp<Q>,
+    pub min_qty: Fp<Q>,
+    pub max_qty: Fp<Q>,
+    /// In the quote asset, at the price precision
+    pub min_notional: Fp<P>,
+    pub max_notional: Fp<P>,
+    /// Allowed price range around a reference price, as
+    /// `(multiplier_down, multiplier_up)`
+    pub price_band: Option<(Multiplier, Multiplier)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpecError {
+    UnknownSymbol,
+    MissingFilter(&'static str),
+    /// The named value does not fit the requested precision
+    Precision(&'static str),
+}
+
+impl fmt::Display for SpecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpecError::UnknownSymbol => f.write_str("unknown symbol"),
+            SpecError::MissingFilter(name) => write!(f, "missing {} filter", name),
+            SpecError::Precision(name) => write!(f, "{} does not fit the precision", name),
+        }
+    }
+}
+
+impl Error for SpecError {}
+
+// Exact conversion, or the name of the value that does not fit
+fn fit<const N: usize>(value: DynFp, name: &'static str) -> Result<Fp<N>, SpecError> {
+    let value = value.rescale(N as u8).ok_or(SpecError::Precision(name))?;
+    Ok(Fp::from_raw(value.raw()))
+}
+
+impl SymbolInfo {
+    pub fn spec<const P: usize, const Q: usize>(&self) -> Result<InstrumentSpec<P, Q>, SpecError> {
+        let mut spec = InstrumentSpec {
+            symbol: self.symbol.clone(),
+            trading: self.status == "TRADING",
+            tick_size: Fp::ZERO,
+            min_price: Fp::ZERO,
+            max_price: Fp::ZERO,
+            step_size: Fp::ZERO,
+            min_qty: Fp::ZERO,
+            max_qty: Fp::ZERO,
+            min_notional: Fp::ZERO,
+            max_notional: Fp::ZERO,
+            price_band: None,
+        };
+        let (mut price, mut lot) = (false, false);
+
+        for filter in &self.filters {
+            match *filter {
+                Filter::PriceFilter {
+                    min_price,
+                    max_price,
+                    tick_size,
+                } => {
+                    spec.min_price = fit(min_price, "minPrice")?;
+                    spec.max_price = fit(max_price, "maxPrice")?;
+                    spec.tick_size = fit(tick_size, "tickSize")?;
+                    price = true;
+                }
+                Filter::LotSize {
+                    min_qty,
+                    max_qty,
+                    step_size,
+                } => {
+                    spec.min_qty = fit(min_qty, "minQty")?;
+                    spec.max_qty = fit(max_qty, "maxQty")?;
+                    spec.step_size = fit(step_size, "stepSize")?;
+                    lot = true;
+                }
+                Filter::MinNotional { min_notional } => {
+                    spec.min_notional = fit(min_notional, "minNotional")?;
+                }
+                Filter::Notional {
+                    min_notional,
+                    max_notional,
+                } => {
+                    spec.min_notional = fit(min_notional, "minNotional")?;
+                    spec.max_notional = fit(max_notional, "maxNotional")?;
+                }
+                Filter::PercentPrice {
+                    multiplier_up,
+                    multiplier_down,
+                } => {
+                    spec.price_band = Some((
+                        fit(multiplier_down, "multiplierDown")?,
+                        fit(multiplier_up, "multiplierUp")?,
+                    ));
+                }
+                Filter::Other => {}
+            }
+        }
+
+        if !price {
+            return Err(SpecError::MissingFilter("PRICE_FILTER"));
+        }
+        if !lot {
+            return Err(SpecError::MissingFilter("LOT_SIZE"));
+        }
+        Ok(spec)
+    }
+}
+
+/// Why an order would be rejected, named after the filter that rejects it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderReject {
+    NotTrading,
+    /// Zero or negative, whatever the filters allow
+    PriceNotPositive,
+    QtyNotPositive,
+    PriceBelowMin,
+    PriceAboveMax,
+    PriceOffTick,
+    QtyBelowMin,
+    QtyAboveMax,
+    QtyOffStep,
+    NotionalBelowMin,
+    NotionalAboveMax,
+    PriceOutsideBand,
+}
+
+impl fmt::Display for OrderReject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OrderReject::NotTrading => "symbol is not trading",
+            OrderReject::PriceNotPositive => "price not positive",
+            OrderReject::QtyNotPositive => "quantity not positive",
+            OrderReject::PriceBelowMin => "price below minPrice",
+            OrderReject::PriceAboveMax => "price above maxPrice",
+            OrderReject::PriceOffTick => "price not a multiple of tickSize",
+            OrderReject::QtyBelowMin => "quantity below minQty",
+            OrderReject::QtyAboveMax => "quantity above maxQty",
+            OrderReject::QtyOffStep => "quantity not a multiple of stepSize",
+            OrderReject::NotionalBelowMin => "notional below minNotional",
+            OrderReject::NotionalAboveMax => "notional above maxNotional",
+            OrderReject::PriceOutsideBand => "price outside the percent price band",
+        })
+    }
+}
+
+impl Error for OrderReject {}
+
+impl<const P: usize, const Q: usize> InstrumentSpec<P, Q> {
+    /// Checks a limit order the way the exchange would. `reference` is the
+    /// price the percent band is taken around (the exchange uses its
+    /// average price); without one the band is not checked.
+    pub fn validate(
+        &self,
+        price: Fp<P>,
+        qty: Fp<Q>,
+        reference: Option<Fp<P>>,
+    ) -> Result<(), OrderReject> {
+        if !self.trading {
+            return Err(OrderReject::NotTrading);
+        }
+        // A zero minimum in the filters lets these through
+        if !price.is_positive() {
+            return Err(OrderReject::PriceNotPositive);
+        }
+        if !qty.is_positive() {
+            return Err(OrderReject::QtyNotPositive);
+        }
+
+        if !self.min_price.is_zero() && price < self.min_price {
+            return Err(OrderReject::PriceBelowMin);
+        }
+        if !self.max_price.is_zero() && price > self.max_price {
+            return Err(OrderReject::PriceAboveMax);
+        }
+        // Both grids start at the minimum, as on the exchange
+        if !self.tick_size.is_zero() && !(price - self.min_price).is_multiple_of(self.tick_size) {
+            return Err(OrderReject::PriceOffTick);
+        }
+
+        if qty < self.min_qty {
+            return Err(OrderReject::QtyBelowMin);
+        }
+        if !self.max_qty.is_zero() && qty > self.max_qty {
+            return Err(OrderReject::QtyAboveMax);
+        }
+        if !self.step_size.is_zero() && !(qty - self.min_qty).is_multiple_of(self.step_size) {
+            return Err(OrderReject::QtyOffStep);
+        }
+
+        // Notional at P + Q decimals, against the bounds scaled to match;
+        // `None` is beyond any bound
+        let notional = price.raw().checked_mul(qty.raw());
+        let scale = 10i128.pow(Q as u32);
+        let min = self.min_notional.raw().checked_mul(scale);
+        let max = self.max_notional.raw().checked_mul(scale);
+        let below = match (notional, min) {
+            (Some(n), Some(min)) => n < min,
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+        if below {
+            return Err(OrderReject::NotionalBelowMin);
+        }
+        let above = match (notional, max) {
+            (Some(n), Some(max)) => n > max,
+            (None, Some(_)) => true,
+            (_, None) => false,
+        };
+        if !self.max_notional.is_zero() && above {
+            return Err(OrderReject::NotionalAboveMax);
+        }
+
+        if let (Some((down, up)), Some(reference)) = (self.price_band, reference) {
+            // price / reference within [down, up], at the multiplier scale
+            let price = price.raw() * Multiplier::ONE.raw();
+            if price < reference.raw() * down.raw() || price > reference.raw() * up.raw() {
+                return Err(OrderReject::PriceOutsideBand);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Snaps an order onto the grid, without making the price more
+    /// aggressive or the quantity larger, then validates the result
+    pub fn snap(
+        &self,
+        side: Side,
+        price: Fp<P>,
+        qty: Fp<Q>,
+        reference: Option<Fp<P>>,
+    ) -> Result<(Fp<P>, Fp<Q>), OrderReject> {
+        let price = match self.tick_size.is_zero() {
+            true => price,
+            false => (price - self.min_price).snap_price(side, self.tick_size) + self.min_price,
+        };
+        // Floored, and a quantity under the minimum left for `validate`
+        let qty = match self.step_size.is_zero() || qty < self.min_qty {
+            true => qty,
+            false => (qty - self.min_qty).floor_to(self.step_size) + self.min_qty,
+        };
+        self.validate(price, qty, reference)?;
+        Ok((price, qty))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXCHANGE_INFO: &[u8] = br#"{"timezone":"UTC","serverTime":1565246363776,"rateLimits":[],"exchangeFilters":[],"symbols":[
+        {"symbol":"ETHBTC","status":"TRADING","baseAsset":"ETH","baseAssetPrecision":8,"quoteAsset":"BTC","quotePrecision":8,"orderTypes":["LIMIT","MARKET"],"filters":[
+            {"filterType":"PRICE_FILTER","minPrice":"0.00001000","maxPrice":"922327.00000000","tickSize":"0.00001000"},
+            {"filterType":"LOT_SIZE","minQty":"0.00010000","maxQty":"100000.00000000","stepSize":"0.00010000"},
+            {"filterType":"ICEBERG_PARTS","limit":10},
+            {"filterType":"PERCENT_PRICE","multiplierUp":"5","multiplierDown":"0.2","avgPriceMins":5},
+            {"filterType":"NOTIONAL","minNotional":"0.00010000","applyMinToMarket":true,"maxNotional":"9000000.00000000","applyMaxToMarket":false,"avgPriceMins":5}]},
+        {"symbol":"BTCUSDT","status":"TRADING","baseAsset":"BTC","quoteAsset":"USDT","pricePrecision":2,"quantityPrecision":3,"filters":[
+            {"filterType":"PRICE_FILTER","minPrice":"556.80","maxPrice":"4529764","tickSize":"0.10"},
+            {"filterType":"LOT_SIZE","stepSize":"0.001","maxQty":"1000","minQty":"0.001"},
+            {"filterType":"MIN_NOTIONAL","notional":"100"},
+            {"filterType":"PERCENT_PRICE","multiplierUp":"1.0500","multiplierDown":"0.9500","multiplierDecimal":"4"}]},
+        {"symbol":"OLDUSDT","status":"BREAK","baseAsset":"OLD","quoteAsset":"USDT","filters":[
+            {"filterType":"PRICE_FILTER","minPrice":"0","maxPrice":"0","tickSize":"0.01"},
+            {"filterType":"LOT_SIZE","stepSize":"1","maxQty":"0","minQty":"1"}]}]}"#;
+
+    fn fp<const N: usize>(s: &str) -> Fp<N> {
+        Fp::from_exp_bytes(s.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_spec() {
+        let info = ExchangeInfo::from_serde_json(EXCHANGE_INFO).unwrap();
+        assert_eq!(info.symbols.len(), 3);
+        assert_eq!(info.symbol("ETHBTC").unwrap().filters[2], Filter::Other);
+
+        let spot = info.spec::<8, 8>("ETHBTC").unwrap();
+        assert!(spot.trading);
+        assert_eq!(spot.tick_size, fp("0.00001"));
+        assert_eq!(spot.max_qty, Fp::from_int(100000));
+        assert_eq!(spot.max_notional, Fp::from_int(9000000));
+        assert_eq!(spot.price_band, Some((fp("0.2"), Fp::from_int(5))));
+
+        let futures = info.spec::<2, 3>("BTCUSDT").unwrap();
+        assert_eq!(
+            (futures.min_price, futures.tick_size),
+            (fp("556.8"), fp("0.1"))
+        );
+        assert_eq!(futures.max_price, Fp::from_int(4529764));
+        assert_eq!(
+            (futures.min_qty, futures.step_size),
+            (fp("0.001"), fp("0.001"))
+        );
+        assert_eq!(futures.min_notional, Fp::from_int(100));
+        assert_eq!(futures.max_notional, Fp::ZERO);
+
+        // More precision than the exchange uses is fine, less is not
+        assert!(info.spec::<4, 6>("BTCUSDT").is_ok());
+        assert_eq!(
+            info.spec::<2, 2>("BTCUSDT"),
+            Err(SpecError::Precision("minQty"))
+        );
+        assert_eq!(
+            info.spec::<2, 3>("ETHBTC"),
+            Err(SpecError::Precision("minPrice"))
+        );
+        assert_eq!(info.spec::<2, 3>("XRPUSDT"), Err(SpecError::UnknownSymbol));
+
+        let json = br#"{"symbols":[{"symbol":"X","status":"TRADING","baseAsset":"X","quoteAsset":"Y","filters":[]}]}"#;
+        let info = ExchangeInfo::from_serde_json(json).unwrap();
+        assert_eq!(
+            info.spec::<2, 3>("X"),
+            Err(SpecError::MissingFilter("PRICE_FILTER"))
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let info = ExchangeInfo::from_serde_json(EXCHANGE_INFO).unwrap();
+        let spec = info.spec::<2, 3>("BTCUSDT").unwrap();
+        let check = |price, qty, reference: Option<&str>| {
+            spec.validate(fp(price), fp(qty), reference.map(fp))
+        };
+
+        assert_eq!(check("60000.1", "0.002", None), Ok(()));
+        assert_eq!(check("556.7", "1", None), Err(OrderReject::PriceBelowMin));
+        assert_eq!(
+            check("4529764.1", "1", None),
+            Err(OrderReject::PriceAboveMax)
+        );
+        assert_eq!(
+            check("60000.15", "0.002", None),
+            Err(OrderReject::PriceOffTick)
+        );
+        assert_eq!(check("60000", "0", None), Err(OrderReject::QtyNotPositive));
+        assert_eq!(
+            check("60000", "1000.001", None),
+            Err(OrderReject::QtyAboveMax)
+        );
+        assert_eq!(
+            check("60000", "0.001", None),
+            Err(OrderReject::NotionalBelowMin)
+        );
+        assert_eq!(check("100000", "0.001", None), Ok(()));
+
+        // 5% either side of the reference
+        assert_eq!(check("63000", "0.002", Some("60000")), Ok(()));
+        assert_eq!(check("57000", "0.002", Some("60000")), Ok(()));
+        assert_eq!(
+            check("63000.1", "0.002", Some("60000")),
+            Err(OrderReject::PriceOutsideBand)
+        );
+        assert_eq!(
+            check("56999.9", "0.002", Some("60000")),
+            Err(OrderReject::PriceOutsideBand)
+        );
+
+        let spot = info.spec::<8, 8>("ETHBTC").unwrap();
+        assert_eq!(
+            spot.validate(fp("0.05"), fp("0.00009"), None),
+            Err(OrderReject::QtyBelowMin)
+        );
+        assert_eq!(
+            spot.validate(fp("0.05"), fp("2.00015"), None),
+            Err(OrderReject::QtyOffStep)
+        );
+        assert_eq!(
+            spot.validate(fp("0.05"), fp("200000"), None),
+            Err(OrderReject::QtyAboveMax)
+        );
+        assert_eq!(
+            spot.validate(fp("900000"), fp("11"), None),
+            Err(OrderReject::NotionalAboveMax)
+        );
+
+        let halted = info.spec::<2, 0>("OLDUSDT").unwrap();
+        assert_eq!(
+            halted.validate(fp("1"), fp("1"), None),
+            Err(OrderReject::NotTrading)
+        );
+    }
+
+    #[test]
+    fn test_snap() {
+        let info = ExchangeInfo::from_serde_json(EXCHANGE_INFO).unwrap();
+        let spec = info.spec::<2, 3>("BTCUSDT").unwrap();
+
+        assert_eq!(
+            spec.snap(Side::Bid, fp("60000.19"), fp("0.002"), None),
+            Ok((fp("60000.1"), fp("0.002")))
+        );
+        assert_eq!(
+            spec.snap(Side::Ask, fp("60000.11"), fp("0.002"), None),
+            Ok((fp("60000.2"), fp("0.002")))
+        );
+
+        let spot = info.spec::<8, 8>("ETHBTC").unwrap();
+        assert_eq!(
+            spot.snap(Side::Ask, fp("0.050001"), fp("1.23456"), None),
+            Ok((fp("0.05001"), fp("1.2345")))
+        );
+        // A quantity under the minimum is not sized up to it
+        assert_eq!(
+            spot.snap(Side::Bid, fp("0.05"), fp("0.00009"), None),
+            Err(OrderReject::QtyBelowMin)
+        );
+
+        // The grid starts at the minimum price, not at zero
+        let json = br#"{"symbols":[{"symbol":"X","status":"TRADING","baseAsset":"X","quoteAsset":"Y","filters":[
+            {"filterType":"PRICE_FILTER","minPrice":"0.05","maxPrice":"0","tickSize":"0.10"},
+            {"filterType":"LOT_SIZE","stepSize":"1","maxQty":"0","minQty":"1"}]}]}"#;
+        let info = ExchangeInfo::from_serde_json(json).unwrap();
+        let spec = info.spec::<2, 0>("X").unwrap();
+        assert_eq!(spec.validate(fp("1.05"), fp("3"), None), Ok(()));
+        assert_eq!(
+            spec.validate(fp("1.00"), fp("3"), None),
+            Err(OrderReject::PriceOffTick)
+        );
+        assert_eq!(
+            spec.snap(Side::Bid, fp("1.14"), fp("3"), None),
+            Ok((fp("1.05"), fp("3")))
+        );
+
+        // Zero minimums still keep out zero and negative values
+        let json = br#"{"symbols":[{"symbol":"X","status":"TRADING","baseAsset":"X","quoteAsset":"Y","filters":[
+            {"filterType":"PRICE_FILTER","minPrice":"0","maxPrice":"0","tickSize":"0"},
+            {"filterType":"LOT_SIZE","stepSize":"0","maxQty":"0","minQty":"0"}]}]}"#;
+        let info = ExchangeInfo::from_serde_json(json).unwrap();
+        let spec = info.spec::<2, 0>("X").unwrap();
+        assert_eq!(spec.validate(fp("0.01"), fp("1"), None), Ok(()));
+        assert_eq!(
+            spec.validate(fp("0"), fp("1"), None),
+            Err(OrderReject::PriceNotPositive)
+        );
+        assert_eq!(
+            spec.validate(fp("-1"), fp("1"), None),
+            Err(OrderReject::PriceNotPositive)
+        );
+        assert_eq!(
+            spec.validate(fp("1"), fp("0"), None),
+            Err(OrderReject::QtyNotPositive)
+        );
+    }
+}
@@ -1 +1,23 @@
+pub mod apply;
+pub mod backtest;
+pub mod batch;
+pub mod binance;
+pub mod dynfp;
+pub mod features;
+pub mod fees;
 pub mod fp;
+pub mod instrument;
+pub mod manager;
+pub mod narrow;
+pub mod order;
+pub mod overlay;
+pub mod position;
+pub mod queue;
+pub mod risk;
+pub mod scan;
+pub mod strategy;
+pub mod stream;
+#[cfg(test)]
+mod testutil;
+pub mod tick;
+pub mod units;
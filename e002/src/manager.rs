@@ -0,0 +1,390 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::fp::Fp;
+use crate::strategy::BookUpdate;
+use e001::orderbook::OrderBook;
+
+// One book per symbol. Symbols are interned once into `SymbolId`s, small
+// indexes into a `Vec`, so routing an update costs no hashing. Each book is
+// sequenced by update id: diffs that skip one put it back to `Resyncing`,
+// where diffs are buffered until a snapshot comes in, and the buffered ones
+// after it are then replayed. At most `max_buffered` are kept per book, the
+// oldest dropped first: a snapshot taken later makes them redundant anyway.
+// The first diff after a snapshot only has to overlap it; later ones must
+// follow on exactly, by `pu` where the stream has it and by `U` otherwise.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SymbolId(u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookStatus {
+    /// Up to date with the stream
+    Synced,
+    /// In sequence, but nothing came for longer than `stale_after`
+    Stale,
+    /// Waiting for a snapshot; the book is not to be trusted
+    Resyncing,
+}
+
+struct Slot<B, const P: usize, const Q: usize> {
+    book: B,
+    status: BookStatus,
+    /// Last update id applied
+    update_id: u64,
+    /// Last event time seen
+    time: u64,
+    /// Whether nothing followed the snapshot yet
+    from_snapshot: bool,
+    buffered: VecDeque<BookUpdate<P, Q>>,
+}
+
+pub struct BookManager<B, const P: usize, const Q: usize> {
+    /// Books without an update for this many milliseconds go stale in
+    /// `expire`
+    pub stale_after: Option<u64>,
+    /// Diffs kept per book while it waits for a snapshot, 1000 by default
+    pub max_buffered: usize,
+    ids: HashMap<Box<str>, SymbolId>,
+    symbols: Vec<Box<str>>,
+    slots: Vec<Slot<B, P, Q>>,
+}
+
+enum Sequence {
+    Apply,
+    /// Already in the book
+    Skip,
+    Gap,
+}
+
+impl<B, const P: usize, const Q: usize> BookManager<B, P, Q>
+where
+    B: OrderBook<Price = Fp<P>, Qty = Fp<Q>> + Default,
+{
+    pub fn new() -> Self {
+        BookManager {
+            stale_after: None,
+            max_buffered: 1000,
+            ids: HashMap::new(),
+            symbols: Vec::new(),
+            slots: Vec::new(),
+        }
+    }
+
+    /// The id for `symbol`, adding an empty book waiting for a snapshot the
+    /// first time
+    pub fn intern(&mut self, symbol: &str) -> SymbolId {
+        if let Some(&id) = self.ids.get(symbol) {
+            return id;
+        }
+        let id = SymbolId(self.symbols.len() as u32);
+        self.ids.insert(symbol.into(), id);
+        self.symbols.push(symbol.into());
+        self.slots.push(Slot {
+            book: B::default(),
+            status: BookStatus::Resyncing,
+            update_id: 0,
+            time: 0,
+            from_snapshot: false,
+            buffered: VecDeque::new(),
+        });
+        id
+    }
+
+    pub fn id(&self, symbol: &str) -> Option<SymbolId> {
+        self.ids.get(symbol).copied()
+    }
+
+    pub fn symbol(&self, id: SymbolId) -> &str {
+        &self.symbols[id.0 as usize]
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<&B> {
+        self.id(symbol).map(|id| self.book(id))
+    }
+
+    pub fn book(&self, id: SymbolId) -> &B {
+        &self.slots[id.0 as usize].book
+    }
+
+    pub fn status(&self, id: SymbolId) -> BookStatus {
+        self.slots[id.0 as usize].status
+    }
+
+    /// Last update id applied to the book
+    pub fn update_id(&self, id: SymbolId) -> u64 {
+        self.slots[id.0 as usize].update_id
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Every book with its id, symbol and status, in interning order
+    pub fn iter(&self) -> impl Iterator<Item = (SymbolId, &str, &B, BookStatus)> {
+        self.slots.iter().enumerate().map(|(i, slot)| {
+            let id = SymbolId(i as u32);
+            (id, self.symbol(id), &slot.book, slot.status)
+        })
+    }
+
+    /// Routes an update to its book and returns the book's status after
+    /// it. `Resyncing` means a snapshot is needed.
+    pub fn apply(&mut self, id: SymbolId, update: &BookUpdate<P, Q>) -> BookStatus {
+        let slot = &mut self.slots[id.0 as usize];
+        slot.time = slot.time.max(update.time);
+        if update.snapshot {
+            update.apply_to(&mut slot.book);
+            slot.update_id = update.update_id;
+            slot.from_snapshot = true;
+            slot.status = BookStatus::Synced;
+            let buffered = std::mem::take(&mut slot.buffered);
+            let mut replay = buffered.iter();
+            for update in replay.by_ref() {
+                if !slot.sequence(update) {
+                    break;
+                }
+            }
+            slot.buffered.extend(replay.cloned());
+        } else if slot.status == BookStatus::Resyncing {
+            slot.buffered.push_back(update.clone());
+            if slot.buffered.len() > self.max_buffered {
+                slot.buffered.pop_front();
+            }
+        } else {
+            slot.sequence(update);
+        }
+        slot.status
+    }
+
+    /// Drops the book's sequencing, as after a reconnect, until the next
+    /// snapshot
+    pub fn resync(&mut self, id: SymbolId) {
+        let slot = &mut self.slots[id.0 as usize];
+        slot.status = BookStatus::Resyncing;
+        slot.buffered.clear();
+    }
+
+    /// Marks synced books as stale when `stale_after` has passed since
+    /// their last update. Returns how many went stale.
+    pub fn expire(&mut self, now: u64) -> usize {
+        let Some(after) = self.stale_after else {
+            return 0;
+        };
+        let mut expired = 0;
+        for slot in &mut self.slots {
+            if slot.status == BookStatus::Synced && slot.time + after <= now {
+                slot.status = BookStatus::Stale;
+                expired += 1;
+            }
+        }
+        expired
+    }
+}
+
+impl<B, const P: usize, const Q: usize> Default for BookManager<B, P, Q>
+where
+    B: OrderBook<Price = Fp<P>, Qty = Fp<Q>> + Default,
+{
+    fn default() -> Self {
+        BookManager::new()
+    }
+}
+
+impl<B, const P: usize, const Q: usize> Slot<B, P, Q>
+where
+    B: OrderBook<Price = Fp<P>, Qty = Fp<Q>>,
+{
+    // Applies a diff if it follows on, or starts buffering for a resync.
+    // Returns false on a gap.
+    fn sequence(&mut self, update: &BookUpdate<P, Q>) -> bool {
+        match self.follows(update) {
+            Sequence::Skip => true,
+            Sequence::Apply => {
+                update.apply_to(&mut self.book);
+                self.update_id = update.update_id;
+                self.from_snapshot = false;
+                self.status = BookStatus::Synced;
+                true
+            }
+            Sequence::Gap => {
+                self.status = BookStatus::Resyncing;
+                self.buffered.push_back(update.clone());
+                false
+            }
+        }
+    }
+
+    fn follows(&self, update: &BookUpdate<P, Q>) -> Sequence {
+        let last = self.update_id;
+        if update.update_id <= last {
+            return Sequence::Skip;
+        }
+        let follows = match (self.from_snapshot, update.prev_update_id) {
+            (true, _) => update.first_update_id <= last + 1,
+            (false, Some(prev)) => prev == last,
+            (false, None) => update.first_update_id == last + 1,
+        };
+        match follows {
+            true => Sequence::Apply,
+            false => Sequence::Gap,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fp;
+    use e001::hybrid::HybridBook;
+
+    type Books = BookManager<HybridBook<Fp<2>, Fp<3>>, 2, 3>;
+
+    fn update(time: u64, ids: (u64, u64), snapshot: bool, bid: Fp<2>) -> BookUpdate<2, 3> {
+        BookUpdate {
+            time,
+            snapshot,
+            first_update_id: ids.0,
+            update_id: ids.1,
+            prev_update_id: None,
+            bids: vec![(bid, fp!(1.000))],
+            asks: vec![],
+        }
+    }
+
+    fn best(books: &Books, symbol: &str) -> Option<String> {
+        books.get(symbol)?.top().0.map(|(p, _)| p.to_string())
+    }
+
+    #[test]
+    fn test_routing() {
+        let mut books = Books::new();
+        let btc = books.intern("BTCUSDT");
+        let eth = books.intern("ETHUSDT");
+        assert_eq!(books.intern("BTCUSDT"), btc);
+        assert_eq!(books.symbol(eth), "ETHUSDT");
+        assert_eq!(books.get("XRPUSDT").map(|_| ()), None);
+
+        assert_eq!(
+            books.apply(btc, &update(1, (10, 10), true, fp!(100.00))),
+            BookStatus::Synced
+        );
+        assert_eq!(
+            books.apply(eth, &update(1, (20, 20), true, fp!(5.00))),
+            BookStatus::Synced
+        );
+        books.apply(btc, &update(2, (11, 12), false, fp!(101.00)));
+        assert_eq!(best(&books, "BTCUSDT").as_deref(), Some("101.00"));
+        assert_eq!(best(&books, "ETHUSDT").as_deref(), Some("5.00"));
+        assert_eq!(books.update_id(btc), 12);
+
+        let symbols: Vec<_> = books.iter().map(|(_, symbol, _, _)| symbol).collect();
+        assert_eq!(symbols, ["BTCUSDT", "ETHUSDT"]);
+
+        // A snapshot replaces the book
+        books.apply(btc, &update(3, (15, 15), true, fp!(99.00)));
+        let bids: Vec<_> = books.book(btc).bids().map(|(p, _)| p.to_string()).collect();
+        assert_eq!(bids, ["99.00"]);
+    }
+
+    #[test]
+    fn test_resync() {
+        let mut books = Books::new();
+        let btc = books.intern("BTCUSDT");
+
+        // Diffs before the first snapshot wait for it
+        assert_eq!(
+            books.apply(btc, &update(1, (5, 8), false, fp!(98.00))),
+            BookStatus::Resyncing
+        );
+        books.apply(btc, &update(2, (9, 12), false, fp!(99.00)));
+        assert_eq!(best(&books, "BTCUSDT"), None);
+        // Only the ones after the snapshot are replayed, the first overlapping
+        assert_eq!(
+            books.apply(btc, &update(3, (10, 10), true, fp!(97.00))),
+            BookStatus::Synced
+        );
+        assert_eq!(books.update_id(btc), 12);
+        let bids: Vec<_> = books.book(btc).bids().map(|(p, _)| p.to_string()).collect();
+        assert_eq!(bids, ["99.00", "97.00"]);
+
+        // Duplicates are skipped, a gap needs a new snapshot
+        assert_eq!(
+            books.apply(btc, &update(4, (9, 12), false, fp!(100.00))),
+            BookStatus::Synced
+        );
+        assert_eq!(
+            books.apply(btc, &update(5, (14, 15), false, fp!(100.00))),
+            BookStatus::Resyncing
+        );
+        books.apply(btc, &update(6, (16, 16), false, fp!(101.00)));
+        books.apply(btc, &update(7, (15, 15), true, fp!(96.00)));
+        assert_eq!(books.status(btc), BookStatus::Synced);
+        assert_eq!(best(&books, "BTCUSDT").as_deref(), Some("101.00"));
+
+        // Futures streams chain by `pu`
+        let mut next = update(8, (17, 20), false, fp!(102.00));
+        next.prev_update_id = Some(16);
+        assert_eq!(books.apply(btc, &next), BookStatus::Synced);
+        let mut next = update(9, (21, 25), false, fp!(103.00));
+        next.prev_update_id = Some(21);
+        assert_eq!(books.apply(btc, &next), BookStatus::Resyncing);
+
+        books.resync(btc);
+        books.apply(btc, &update(10, (30, 30), true, fp!(90.00)));
+        assert_eq!(best(&books, "BTCUSDT").as_deref(), Some("90.00"));
+    }
+
+    #[test]
+    fn test_stale() {
+        let mut books = Books::new();
+        let btc = books.intern("BTCUSDT");
+        let eth = books.intern("ETHUSDT");
+        books.apply(btc, &update(1000, (1, 1), true, fp!(100.00)));
+        books.apply(eth, &update(1000, (1, 1), true, fp!(5.00)));
+        assert_eq!(books.expire(10_000), 0);
+
+        books.stale_after = Some(5000);
+        books.apply(eth, &update(4000, (2, 2), false, fp!(5.01)));
+        assert_eq!(books.expire(6000), 1);
+        assert_eq!(books.status(btc), BookStatus::Stale);
+        assert_eq!(books.status(eth), BookStatus::Synced);
+
+        // Back in sync on the next update
+        books.apply(btc, &update(6500, (2, 2), false, fp!(100.50)));
+        assert_eq!(books.status(btc), BookStatus::Synced);
+        assert_eq!(books.book(btc).top().0.unwrap().1, &fp!(1.000));
+        assert_eq!(
+            books.iter().map(|(.., status)| status).collect::<Vec<_>>(),
+            [BookStatus::Synced, BookStatus::Synced]
+        );
+    }
+
+    #[test]
+    fn test_buffer_cap() {
+        let mut books = Books::new();
+        books.max_buffered = 2;
+        let btc = books.intern("BTCUSDT");
+
+        // The first diff is dropped for the last two
+        books.apply(btc, &update(1, (1, 1), false, fp!(97.00)));
+        books.apply(btc, &update(2, (2, 2), false, fp!(98.00)));
+        books.apply(btc, &update(3, (3, 3), false, fp!(99.00)));
+        // so a snapshot from before it can't be caught up
+        assert_eq!(
+            books.apply(btc, &update(4, (0, 0), true, fp!(96.00))),
+            BookStatus::Resyncing
+        );
+
+        // A later snapshot picks up from the buffered ones
+        assert_eq!(
+            books.apply(btc, &update(5, (2, 2), true, fp!(96.00))),
+            BookStatus::Synced
+        );
+        assert_eq!(books.update_id(btc), 3);
+        assert_eq!(best(&books, "BTCUSDT").as_deref(), Some("99.00"));
+    }
+}
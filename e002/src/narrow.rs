@@ -0,0 +1,742 @@
+use std::{
+    fmt,
+    iter::{Product, Sum},
+    num::TryFromIntError,
+    ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Rem, RemAssign, Sub, SubAssign},
+    str::FromStr,
+};
+
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer};
+
+use crate::fp::{
+    Fp, FpErrorKind, ParseFpError, Rounding, has_exponent, parse_fixed, parse_fixed_prefix,
+    round_up,
+};
+use e001::orderbook::Side;
+
+/// Generates an `Fp` sibling backed by a narrower integer. Parsing,
+/// formatting, arithmetic and the tick helpers mirror `Fp`; products and
+/// quotients go through `$wide`. Results that don't fit the narrow width
+/// panic, or are `None` from the checked operations. `fp64!` and `fp32!`
+/// are the `fp!` for each.
+macro_rules! narrow_fp {
+    ($(#[$meta:meta])* $name:ident, $int:ty, $wide:ty) => {
+        $(#[$meta])*
+        #[derive(Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name<const DECIMALS: usize>($int);
+
+        impl<const DECIMALS: usize> $name<DECIMALS> {
+            const SCALE: $int = (10 as $int).pow(DECIMALS as u32);
+
+            pub const ZERO: Self = $name(0);
+            pub const ONE: Self = $name(Self::SCALE);
+            pub const MIN: Self = $name(<$int>::MIN);
+            pub const MAX: Self = $name(<$int>::MAX);
+
+            #[inline]
+            pub const fn from_raw(raw: $int) -> Self {
+                $name(raw)
+            }
+
+            #[inline]
+            pub const fn raw(self) -> $int {
+                self.0
+            }
+
+            #[inline]
+            pub const fn from_int(int: $int) -> Self {
+                $name(int * Self::SCALE)
+            }
+
+            /// See `Fp::from_literal`. Also panics if the value doesn't fit
+            /// the narrow width.
+            pub const fn from_literal(s: &str) -> Self {
+                let raw = Fp::<DECIMALS>::from_literal(s).raw();
+                assert!(
+                    raw >= <$int>::MIN as i128 && raw <= <$int>::MAX as i128,
+                    "literal out of range"
+                );
+                $name(raw as $int)
+            }
+
+            #[inline]
+            pub fn from_bytes<const N: usize>(buf: &[u8]) -> Result<Self, ParseFpError> {
+                parse_fixed(buf, N, Self::SCALE).map($name)
+            }
+
+            /// See `Fp::from_exp_bytes`
+            pub fn from_exp_bytes(buf: &[u8]) -> Result<Self, ParseFpError> {
+                Fp::<DECIMALS>::from_exp_bytes(buf).and_then(Self::narrow)
+            }
+
+            /// See `Fp::from_exp_bytes_rounded`
+            pub fn from_exp_bytes_rounded(
+                buf: &[u8],
+                rounding: Rounding,
+            ) -> Result<Self, ParseFpError> {
+                Fp::<DECIMALS>::from_exp_bytes_rounded(buf, rounding).and_then(Self::narrow)
+            }
+
+            #[inline]
+            fn narrow(fp: Fp<DECIMALS>) -> Result<Self, ParseFpError> {
+                Self::try_from(fp).map_err(|_| ParseFpError {
+                    kind: FpErrorKind::Overflow,
+                })
+            }
+
+            // See `Fp::parse`
+            #[inline]
+            fn parse(buf: &[u8]) -> Result<Self, ParseFpError> {
+                match parse_fixed_prefix(buf, DECIMALS, Self::SCALE) {
+                    Ok((raw, read)) if !has_exponent(&buf[read..]) => Ok($name(raw)),
+                    Err(err) if !has_exponent(buf) => Err(err),
+                    _ => Self::from_exp_bytes(buf),
+                }
+            }
+
+            /// See `Fp::write_to`
+            #[inline]
+            pub fn write_to(&self, buf: &mut [u8]) -> usize {
+                Fp::<DECIMALS>::from(*self).write_to(buf)
+            }
+
+            /// See `Fp::write_trimmed_to`
+            #[inline]
+            pub fn write_trimmed_to(&self, buf: &mut [u8]) -> usize {
+                Fp::<DECIMALS>::from(*self).write_trimmed_to(buf)
+            }
+
+            #[inline]
+            pub fn abs(self) -> Self {
+                $name(self.0.abs())
+            }
+
+            #[inline]
+            pub fn signum(self) -> Self {
+                $name(self.0.signum() * Self::SCALE)
+            }
+
+            #[inline]
+            pub fn is_zero(self) -> bool {
+                self.0 == 0
+            }
+
+            #[inline]
+            pub fn is_positive(self) -> bool {
+                self.0 > 0
+            }
+
+            #[inline]
+            pub fn is_negative(self) -> bool {
+                self.0 < 0
+            }
+
+            #[inline]
+            pub fn checked_add(self, rhs: Self) -> Option<Self> {
+                self.0.checked_add(rhs.0).map($name)
+            }
+
+            #[inline]
+            pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+                self.0.checked_sub(rhs.0).map($name)
+            }
+
+            #[inline]
+            pub fn checked_mul(self, rhs: Self) -> Option<Self> {
+                let result = (self.0 as $wide * rhs.0 as $wide) / Self::SCALE as $wide;
+                <$int>::try_from(result).ok().map($name)
+            }
+
+            #[inline]
+            pub fn checked_div(self, rhs: Self) -> Option<Self> {
+                if rhs.0 == 0 {
+                    return None;
+                }
+                let result = (self.0 as $wide * Self::SCALE as $wide) / rhs.0 as $wide;
+                <$int>::try_from(result).ok().map($name)
+            }
+
+            #[inline]
+            pub fn checked_rem(self, rhs: Self) -> Option<Self> {
+                self.0.checked_rem(rhs.0).map($name)
+            }
+
+            #[inline]
+            pub fn checked_neg(self) -> Option<Self> {
+                self.0.checked_neg().map($name)
+            }
+        }
+
+        impl<const DECIMALS: usize> Add for $name<DECIMALS> {
+            type Output = Self;
+
+            fn add(self, rhs: Self) -> Self {
+                $name(self.0 + rhs.0)
+            }
+        }
+
+        impl<const DECIMALS: usize> Sub for $name<DECIMALS> {
+            type Output = Self;
+
+            fn sub(self, rhs: Self) -> Self {
+                $name(self.0 - rhs.0)
+            }
+        }
+
+        impl<const DECIMALS: usize> Mul for $name<DECIMALS> {
+            type Output = Self;
+
+            fn mul(self, rhs: Self) -> Self {
+                let result = (self.0 as $wide * rhs.0 as $wide) / Self::SCALE as $wide;
+                $name(<$int>::try_from(result).expect("attempt to multiply with overflow"))
+            }
+        }
+
+        impl<const DECIMALS: usize> Div for $name<DECIMALS> {
+            type Output = Self;
+
+            fn div(self, rhs: Self) -> Self {
+                let result = (self.0 as $wide * Self::SCALE as $wide) / rhs.0 as $wide;
+                $name(<$int>::try_from(result).expect("attempt to divide with overflow"))
+            }
+        }
+
+        impl<const DECIMALS: usize> Rem for $name<DECIMALS> {
+            type Output = Self;
+
+            fn rem(self, rhs: Self) -> Self {
+                $name(self.0 % rhs.0)
+            }
+        }
+
+        impl<const DECIMALS: usize> Neg for $name<DECIMALS> {
+            type Output = Self;
+
+            fn neg(self) -> Self {
+                $name(-self.0)
+            }
+        }
+
+        impl<const DECIMALS: usize> AddAssign for $name<DECIMALS> {
+            fn add_assign(&mut self, rhs: Self) {
+                *self = *self + rhs;
+            }
+        }
+
+        impl<const DECIMALS: usize> SubAssign for $name<DECIMALS> {
+            fn sub_assign(&mut self, rhs: Self) {
+                *self = *self - rhs;
+            }
+        }
+
+        impl<const DECIMALS: usize> MulAssign for $name<DECIMALS> {
+            fn mul_assign(&mut self, rhs: Self) {
+                *self = *self * rhs;
+            }
+        }
+
+        impl<const DECIMALS: usize> DivAssign for $name<DECIMALS> {
+            fn div_assign(&mut self, rhs: Self) {
+                *self = *self / rhs;
+            }
+        }
+
+        impl<const DECIMALS: usize> RemAssign for $name<DECIMALS> {
+            fn rem_assign(&mut self, rhs: Self) {
+                *self = *self % rhs;
+            }
+        }
+
+        impl<const DECIMALS: usize> Sum for $name<DECIMALS> {
+            fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+                iter.fold(Self::ZERO, Add::add)
+            }
+        }
+
+        impl<'a, const DECIMALS: usize> Sum<&'a $name<DECIMALS>> for $name<DECIMALS> {
+            fn sum<I: Iterator<Item = &'a Self>>(iter: I) -> Self {
+                iter.copied().sum()
+            }
+        }
+
+        impl<const DECIMALS: usize> Product for $name<DECIMALS> {
+            fn product<I: Iterator<Item = Self>>(iter: I) -> Self {
+                iter.fold(Self::ONE, Mul::mul)
+            }
+        }
+
+        impl<'a, const DECIMALS: usize> Product<&'a $name<DECIMALS>> for $name<DECIMALS> {
+            fn product<I: Iterator<Item = &'a Self>>(iter: I) -> Self {
+                iter.copied().product()
+            }
+        }
+
+        /// See the tick helpers on `Fp`. Steps must be positive, all methods
+        /// panic otherwise.
+        impl<const DECIMALS: usize> $name<DECIMALS> {
+            #[inline]
+            pub fn round_to(self, step: Self, rounding: Rounding) -> Self {
+                assert!(step.is_positive(), "step must be positive");
+
+                let divisor = step.0.unsigned_abs() as u128;
+                let magnitude = self.0.unsigned_abs() as u128;
+                let (quot, rem) = (magnitude / divisor, magnitude % divisor);
+                let quot =
+                    quot + round_up(rounding, self.is_negative(), quot, rem, Some(divisor)) as u128;
+
+                let value = (quot * divisor) as i128;
+                let value = if self.is_negative() { -value } else { value };
+                $name(<$int>::try_from(value).expect("attempt to round with overflow"))
+            }
+
+            #[inline]
+            pub fn floor_to(self, step: Self) -> Self {
+                self.round_to(step, Rounding::ToNegativeInfinity)
+            }
+
+            #[inline]
+            pub fn ceil_to(self, step: Self) -> Self {
+                self.round_to(step, Rounding::ToPositiveInfinity)
+            }
+
+            #[inline]
+            pub fn is_multiple_of(self, step: Self) -> bool {
+                assert!(step.is_positive(), "step must be positive");
+                self.0 % step.0 == 0
+            }
+
+            #[inline]
+            pub fn ticks_to(self, other: Self, tick: Self) -> i128 {
+                assert!(tick.is_positive(), "tick must be positive");
+                (other.0 as i128 - self.0 as i128) / tick.0 as i128
+            }
+
+            #[inline]
+            pub fn offset_ticks(self, ticks: i128, tick: Self) -> Self {
+                assert!(tick.is_positive(), "tick must be positive");
+                let value = self.0 as i128 + ticks * tick.0 as i128;
+                $name(<$int>::try_from(value).expect("attempt to offset with overflow"))
+            }
+
+            #[inline]
+            pub fn snap_price(self, side: Side, tick: Self) -> Self {
+                match side {
+                    Side::Bid => self.floor_to(tick),
+                    Side::Ask => self.ceil_to(tick),
+                }
+            }
+
+            #[inline]
+            pub fn snap_qty(self, lot: Self) -> Self {
+                self.round_to(lot, Rounding::ToZero)
+            }
+
+            #[inline]
+            pub fn passive_ticks(self, side: Side, ticks: i128, tick: Self) -> Self {
+                match side {
+                    Side::Bid => self.offset_ticks(-ticks, tick),
+                    Side::Ask => self.offset_ticks(ticks, tick),
+                }
+            }
+
+            #[inline]
+            pub fn ticks_behind(self, side: Side, other: Self, tick: Self) -> i128 {
+                match side {
+                    Side::Bid => other.ticks_to(self, tick),
+                    Side::Ask => self.ticks_to(other, tick),
+                }
+            }
+        }
+
+        impl<const DECIMALS: usize> From<$name<DECIMALS>> for Fp<DECIMALS> {
+            #[inline]
+            fn from(value: $name<DECIMALS>) -> Self {
+                Fp::from_raw(value.0 as i128)
+            }
+        }
+
+        impl<const DECIMALS: usize> TryFrom<Fp<DECIMALS>> for $name<DECIMALS> {
+            type Error = TryFromIntError;
+
+            #[inline]
+            fn try_from(value: Fp<DECIMALS>) -> Result<Self, Self::Error> {
+                <$int>::try_from(value.raw()).map($name)
+            }
+        }
+
+        impl<const DECIMALS: usize> fmt::Display for $name<DECIMALS> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Display::fmt(&Fp::<DECIMALS>::from(*self), f)
+            }
+        }
+
+        impl<const DECIMALS: usize> fmt::Debug for $name<DECIMALS> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Display::fmt(self, f)
+            }
+        }
+
+        impl<const DECIMALS: usize> FromStr for $name<DECIMALS> {
+            type Err = ParseFpError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Self::parse(s.as_bytes())
+            }
+        }
+
+        impl<'de, const N: usize> Deserialize<'de> for $name<N> {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                struct FixedVisitor<const N: usize>;
+
+                impl<'de, const N: usize> Visitor<'de> for FixedVisitor<N> {
+                    type Value = $name<N>;
+
+                    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                        write!(f, "a decimal string with exactly {} fractional digits", N)
+                    }
+
+                    fn visit_borrowed_str<E>(self, s: &'de str) -> Result<Self::Value, E>
+                    where
+                        E: de::Error,
+                    {
+                        $name::<N>::parse(s.as_bytes()).map_err(E::custom)
+                    }
+                }
+
+                deserializer.deserialize_any(FixedVisitor::<N>)
+            }
+        }
+    };
+}
+
+narrow_fp!(
+    /// `Fp` backed by an `i64`: half the size, with 64-bit add/compare and
+    /// 128-bit intermediates for multiply and divide.
+    Fp64,
+    i64,
+    i128
+);
+
+narrow_fp!(
+    /// `Fp` backed by an `i32`, for prices and sizes with few digits.
+    Fp32,
+    i32,
+    i64
+);
+
+impl<const DECIMALS: usize> From<Fp32<DECIMALS>> for Fp64<DECIMALS> {
+    #[inline]
+    fn from(value: Fp32<DECIMALS>) -> Self {
+        Fp64::from_raw(value.raw() as i64)
+    }
+}
+
+/// `fp!` for `Fp64`: builds one from a decimal literal at compile time.
+///
+/// ```
+/// use e002::{fp64, narrow::Fp64};
+///
+/// const TICK: Fp64<2> = fp64!(0.01);
+/// assert_eq!(fp64!(-1.5; 3), Fp64::<3>::from_raw(-1500));
+/// ```
+#[macro_export]
+macro_rules! fp64 {
+    ($lit:literal) => {
+        const { $crate::narrow::Fp64::from_literal(stringify!($lit)) }
+    };
+    (-$lit:literal) => {
+        const { $crate::narrow::Fp64::from_literal(concat!("-", stringify!($lit))) }
+    };
+    ($lit:literal; $decimals:expr) => {
+        const { $crate::narrow::Fp64::<$decimals>::from_literal(stringify!($lit)) }
+    };
+    (-$lit:literal; $decimals:expr) => {
+        const { $crate::narrow::Fp64::<$decimals>::from_literal(concat!("-", stringify!($lit))) }
+    };
+}
+
+/// `fp!` for `Fp32`. Literals outside the `i32` range fail to compile:
+///
+/// ```compile_fail
+/// use e002::{fp32, narrow::Fp32};
+///
+/// const BIG: Fp32<2> = fp32!(21474836.48);
+/// ```
+#[macro_export]
+macro_rules! fp32 {
+    ($lit:literal) => {
+        const { $crate::narrow::Fp32::from_literal(stringify!($lit)) }
+    };
+    (-$lit:literal) => {
+        const { $crate::narrow::Fp32::from_literal(concat!("-", stringify!($lit))) }
+    };
+    ($lit:literal; $decimals:expr) => {
+        const { $crate::narrow::Fp32::<$decimals>::from_literal(stringify!($lit)) }
+    };
+    (-$lit:literal; $decimals:expr) => {
+        const { $crate::narrow::Fp32::<$decimals>::from_literal(concat!("-", stringify!($lit))) }
+    };
+}
+
+#[cfg(feature = "num-traits")]
+mod num_impls {
+    use super::*;
+    use num_traits::{
+        Bounded, CheckedAdd, CheckedDiv, CheckedMul, CheckedNeg, CheckedRem, CheckedSub, Num, One,
+        Signed, Zero,
+    };
+
+    // Same as the impls on `Fp`
+    macro_rules! impl_num_traits {
+        ($($name:ident),*) => {
+            $(
+                impl<const DECIMALS: usize> Zero for $name<DECIMALS> {
+                    fn zero() -> Self {
+                        Self::ZERO
+                    }
+
+                    fn is_zero(&self) -> bool {
+                        $name::is_zero(*self)
+                    }
+                }
+
+                impl<const DECIMALS: usize> One for $name<DECIMALS> {
+                    fn one() -> Self {
+                        Self::ONE
+                    }
+                }
+
+                impl<const DECIMALS: usize> Num for $name<DECIMALS> {
+                    type FromStrRadixErr = ParseFpError;
+
+                    fn from_str_radix(s: &str, radix: u32) -> Result<Self, Self::FromStrRadixErr> {
+                        if radix != 10 {
+                            return Err(ParseFpError {
+                                kind: FpErrorKind::InvalidFormat,
+                            });
+                        }
+                        s.parse()
+                    }
+                }
+
+                impl<const DECIMALS: usize> Signed for $name<DECIMALS> {
+                    fn abs(&self) -> Self {
+                        $name::abs(*self)
+                    }
+
+                    fn abs_sub(&self, other: &Self) -> Self {
+                        if self <= other {
+                            Self::ZERO
+                        } else {
+                            *self - *other
+                        }
+                    }
+
+                    fn signum(&self) -> Self {
+                        $name::signum(*self)
+                    }
+
+                    fn is_positive(&self) -> bool {
+                        $name::is_positive(*self)
+                    }
+
+                    fn is_negative(&self) -> bool {
+                        $name::is_negative(*self)
+                    }
+                }
+
+                impl<const DECIMALS: usize> Bounded for $name<DECIMALS> {
+                    fn min_value() -> Self {
+                        Self::MIN
+                    }
+
+                    fn max_value() -> Self {
+                        Self::MAX
+                    }
+                }
+
+                impl_num_traits!(@checked $name,
+                    CheckedAdd checked_add,
+                    CheckedSub checked_sub,
+                    CheckedMul checked_mul,
+                    CheckedDiv checked_div,
+                    CheckedRem checked_rem
+                );
+
+                impl<const DECIMALS: usize> CheckedNeg for $name<DECIMALS> {
+                    fn checked_neg(&self) -> Option<Self> {
+                        $name::checked_neg(*self)
+                    }
+                }
+            )*
+        };
+        (@checked $name:ident, $($trait:ident $method:ident),*) => {
+            $(
+                impl<const DECIMALS: usize> $trait for $name<DECIMALS> {
+                    fn $method(&self, rhs: &Self) -> Option<Self> {
+                        $name::$method(*self, *rhs)
+                    }
+                }
+            )*
+        };
+    }
+
+    impl_num_traits!(Fp64, Fp32);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_narrow_parsing() {
+        assert_eq!(Fp64::<2>::from_str("104276.90").unwrap().raw(), 10427690);
+        assert_eq!(Fp32::<3>::from_str("-10.023").unwrap().raw(), -10023);
+        assert_eq!(Fp32::<8>::from_str("1e-8").unwrap().raw(), 1);
+        assert_eq!(Fp32::<3>::from_str("1.2345e2").unwrap().raw(), 123_450);
+
+        // i32::MAX is 2147483647
+        assert_eq!(Fp32::<2>::from_str("21474836.47").unwrap(), Fp32::<2>::MAX);
+        assert_eq!(
+            Fp32::<2>::from_str("21474836.48").unwrap_err().kind(),
+            &FpErrorKind::Overflow
+        );
+        assert_eq!(
+            Fp32::<2>::from_str("1e8").unwrap_err().kind(),
+            &FpErrorKind::Overflow
+        );
+        assert_eq!(
+            Fp64::<3>::from_str("1.2").unwrap_err().kind(),
+            &FpErrorKind::InvalidFraction
+        );
+    }
+
+    #[test]
+    fn test_narrow_matches_fp() {
+        let a = "1.234";
+        let b = "2.345";
+        let wide = |s: &str| Fp::<3>::from_str(s).unwrap();
+        let fp64 = |s: &str| Fp64::<3>::from_str(s).unwrap();
+        let fp32 = |s: &str| Fp32::<3>::from_str(s).unwrap();
+
+        assert_eq!(Fp::from(fp64(a) * fp64(b)), wide(a) * wide(b));
+        assert_eq!(Fp::from(fp32(b) / fp32(a)), wide(b) / wide(a));
+        assert_eq!(Fp::from(fp32(a) - fp32(b)), wide(a) - wide(b));
+        assert_eq!([fp64(a), fp64(b)].iter().sum::<Fp64<3>>(), fp64("3.579"));
+        assert_eq!(
+            Fp::from([fp32(a), fp32(b)].iter().product::<Fp32<3>>()),
+            wide(a) * wide(b)
+        );
+
+        assert_eq!(fp32(a).to_string(), "1.234");
+        assert_eq!(format!("{:>8.1}", fp64(b)), "     2.3");
+        let mut buf = [0u8; Fp::<3>::MAX_STR_LEN];
+        let n = fp32("-0.500").write_trimmed_to(&mut buf);
+        assert_eq!(&buf[..n], b"-0.5");
+    }
+
+    #[test]
+    fn test_narrow_overflow_checks() {
+        let big = Fp32::<2>::from_int(1_000_000);
+        assert_eq!(big.checked_mul(big), None);
+        assert_eq!(big.checked_add(Fp32::MAX), None);
+        assert_eq!(big.checked_div(Fp32::ZERO), None);
+        assert_eq!(
+            big.checked_div(Fp32::from_int(4)),
+            Some(Fp32::from_int(250_000))
+        );
+
+        assert!(Fp32::<2>::try_from(Fp::<2>::from_int(100_000_000)).is_err());
+        assert_eq!(
+            Fp64::<2>::try_from(Fp::<2>::from_int(100_000_000)).map(Fp64::raw),
+            Ok(10_000_000_000)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "multiply with overflow")]
+    fn test_narrow_mul_overflow() {
+        let _ = fp32!(100000.00; 2) * fp32!(1000.00);
+    }
+
+    #[test]
+    #[should_panic(expected = "divide with overflow")]
+    fn test_narrow_div_overflow() {
+        let _ = fp32!(10000000.00; 2) / fp32!(0.01);
+    }
+
+    #[test]
+    #[should_panic(expected = "round with overflow")]
+    fn test_narrow_round_overflow() {
+        let _ = Fp32::<2>::MAX.ceil_to(fp32!(1.00));
+    }
+
+    #[test]
+    #[should_panic(expected = "offset with overflow")]
+    fn test_narrow_offset_overflow() {
+        let _ = fp32!(20000000.00; 2).offset_ticks(200_000_000, fp32!(0.01));
+    }
+
+    #[test]
+    fn test_narrow_const_constructors() {
+        const TICK: Fp64<2> = fp64!(0.01);
+        const LOT: Fp32<3> = fp32!(1_000.5);
+
+        assert_eq!(TICK, Fp64::<2>::from_str("0.01").unwrap());
+        assert_eq!(LOT, Fp32::<3>::from_str("1000.500").unwrap());
+        assert_eq!(fp32!(-0.005; 3).raw(), -5);
+        assert_eq!(fp32!(21474836.47; 2), Fp32::<2>::MAX);
+    }
+
+    #[test]
+    fn test_narrow_ticks() {
+        const TICK: Fp32<2> = fp32!(0.10);
+        let price: Fp32<2> = fp32!(104276.97);
+
+        assert_eq!(price.floor_to(TICK), fp32!(104276.90));
+        assert_eq!(price.ceil_to(TICK), fp32!(104277.00));
+        assert_eq!(fp32!(-1.25; 2).floor_to(TICK), fp32!(-1.30));
+        assert_eq!(fp64!(0.0379; 4).snap_qty(fp64!(0.001)), fp64!(0.0370));
+        assert!(!price.is_multiple_of(TICK));
+
+        // Same results as on `Fp`
+        let wide = Fp::from(price);
+        assert_eq!(
+            Fp::from(price.round_to(TICK, Rounding::MidpointNearestEven)),
+            wide.round_to(TICK.into(), Rounding::MidpointNearestEven)
+        );
+        assert_eq!(price.snap_price(Side::Ask, TICK), fp32!(104277.00));
+        assert_eq!(price.ticks_to(fp32!(104277.30), TICK), 3);
+        assert_eq!(
+            fp32!(100.00; 2).passive_ticks(Side::Bid, 2, TICK),
+            fp32!(99.80)
+        );
+        assert_eq!(
+            fp32!(100.00; 2).ticks_behind(Side::Ask, fp32!(99.80), TICK),
+            -2
+        );
+    }
+
+    #[cfg(feature = "num-traits")]
+    #[test]
+    fn test_narrow_num_traits() {
+        use num_traits::{Bounded, CheckedMul, Num, One, Signed, Zero};
+
+        fn total<T: Num + Copy>(xs: &[T]) -> T {
+            xs.iter().fold(T::zero(), |acc, &x| acc + x)
+        }
+
+        let a = Fp32::<2>::from_str_radix("1.25", 10).unwrap();
+        assert!(Fp64::<2>::from_str_radix("1.25", 16).is_err());
+        assert_eq!(total(&[a, a]), fp32!(2.50));
+        assert!(<Fp64<2> as Zero>::zero().is_zero());
+        assert_eq!(<Fp32<2> as One>::one(), fp32!(1; 2));
+        assert_eq!(Signed::abs(&-a), a);
+        assert_eq!(<Fp32<2> as Bounded>::max_value(), Fp32::<2>::MAX);
+        assert_eq!(CheckedMul::checked_mul(&Fp32::<2>::MAX, &a), None);
+    }
+}
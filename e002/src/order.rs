@@ -0,0 +1,493 @@
+use std::fmt::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::fp::Fp;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use e001::orderbook::Side;
+use ed25519_dalek::{Signer as _, SigningKey};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+// Outbound order messages for the Binance spot API, as signed REST query
+// strings and as WebSocket API requests. Prices and quantities are written
+// at the precision of their `Fp`, so they go out exactly as the instrument
+// expects them (see `InstrumentSpec`). Nothing here does I/O: the caller
+// sends the query with its `X-MBX-APIKEY` header, or the JSON text over
+// the WebSocket, and supplies the timestamp so signing is deterministic.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderType {
+    Limit,
+    Market,
+    /// Post-only limit order, rejected if it would take liquidity
+    LimitMaker,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeInForce {
+    Gtc,
+    Ioc,
+    Fok,
+}
+
+/// How much the exchange sends back for a new order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseType {
+    Ack,
+    Result,
+    Full,
+}
+
+/// The order a cancel or amend applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderRef<'a> {
+    Id(u64),
+    ClientId(&'a str),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewOrder<'a, const P: usize, const Q: usize> {
+    pub symbol: &'a str,
+    pub side: Side,
+    pub order_type: OrderType,
+    /// Required for `Limit`, not sent otherwise
+    pub time_in_force: Option<TimeInForce>,
+    /// Required for `Limit` and `LimitMaker`
+    pub price: Option<Fp<P>>,
+    pub qty: Fp<Q>,
+    pub client_order_id: Option<&'a str>,
+    /// Exchange default when `None`
+    pub response: Option<ResponseType>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CancelOrder<'a> {
+    pub symbol: &'a str,
+    pub order: OrderRef<'a>,
+}
+
+/// Reduces the quantity of a resting order, keeping its queue priority
+#[derive(Debug, Clone, PartialEq)]
+pub struct AmendOrder<'a, const Q: usize> {
+    pub symbol: &'a str,
+    pub order: OrderRef<'a>,
+    pub new_qty: Fp<Q>,
+    pub new_client_order_id: Option<&'a str>,
+}
+
+impl<'a, const P: usize, const Q: usize> NewOrder<'a, P, Q> {
+    /// Good-till-cancel limit order
+    pub fn limit(symbol: &'a str, side: Side, price: Fp<P>, qty: Fp<Q>) -> Self {
+        NewOrder {
+            symbol,
+            side,
+            order_type: OrderType::Limit,
+            time_in_force: Some(TimeInForce::Gtc),
+            price: Some(price),
+            qty,
+            client_order_id: None,
+            response: None,
+        }
+    }
+
+    pub fn market(symbol: &'a str, side: Side, qty: Fp<Q>) -> Self {
+        NewOrder {
+            symbol,
+            side,
+            order_type: OrderType::Market,
+            time_in_force: None,
+            price: None,
+            qty,
+            client_order_id: None,
+            response: None,
+        }
+    }
+
+    pub fn with_client_order_id(self, id: &'a str) -> Self {
+        NewOrder {
+            client_order_id: Some(id),
+            ..self
+        }
+    }
+
+    pub fn with_response(self, response: ResponseType) -> Self {
+        NewOrder {
+            response: Some(response),
+            ..self
+        }
+    }
+}
+
+/// Parameter value, borrowed or formatted into place
+pub enum Value<'a> {
+    Str(&'a str),
+    Int(u64),
+    /// A price or quantity, written at its precision
+    Decimal(&'a dyn fmt::Display),
+}
+
+impl fmt::Display for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Str(s) => f.write_str(s),
+            Value::Int(v) => write!(f, "{}", v),
+            Value::Decimal(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+impl Value<'_> {
+    // Integers stay numbers in JSON, everything else goes as a string
+    fn to_json(&self) -> serde_json::Value {
+        match self {
+            Value::Int(v) => (*v).into(),
+            value => value.to_string().into(),
+        }
+    }
+}
+
+/// A request the exchange takes signed
+pub trait Request {
+    /// HTTP method and path of the REST endpoint
+    const REST: (&'static str, &'static str);
+    /// Method name on the WebSocket API
+    const WS: &'static str;
+
+    /// Calls `param` for each parameter, in the order they are sent
+    fn params(&self, param: &mut dyn FnMut(&'static str, Value));
+}
+
+impl<const P: usize, const Q: usize> Request for NewOrder<'_, P, Q> {
+    const REST: (&'static str, &'static str) = ("POST", "/api/v3/order");
+    const WS: &'static str = "order.place";
+
+    fn params(&self, param: &mut dyn FnMut(&'static str, Value)) {
+        param("symbol", Value::Str(self.symbol));
+        param("side", Value::Str(side(&self.side)));
+        param(
+            "type",
+            Value::Str(match self.order_type {
+                OrderType::Limit => "LIMIT",
+                OrderType::Market => "MARKET",
+                OrderType::LimitMaker => "LIMIT_MAKER",
+            }),
+        );
+        if let Some(tif) = self.time_in_force {
+            param(
+                "timeInForce",
+                Value::Str(match tif {
+                    TimeInForce::Gtc => "GTC",
+                    TimeInForce::Ioc => "IOC",
+                    TimeInForce::Fok => "FOK",
+                }),
+            );
+        }
+        param("quantity", Value::Decimal(&self.qty));
+        if let Some(price) = &self.price {
+            param("price", Value::Decimal(price));
+        }
+        if let Some(id) = self.client_order_id {
+            param("newClientOrderId", Value::Str(id));
+        }
+        if let Some(response) = self.response {
+            param(
+                "newOrderRespType",
+                Value::Str(match response {
+                    ResponseType::Ack => "ACK",
+                    ResponseType::Result => "RESULT",
+                    ResponseType::Full => "FULL",
+                }),
+            );
+        }
+    }
+}
+
+impl Request for CancelOrder<'_> {
+    const REST: (&'static str, &'static str) = ("DELETE", "/api/v3/order");
+    const WS: &'static str = "order.cancel";
+
+    fn params(&self, param: &mut dyn FnMut(&'static str, Value)) {
+        param("symbol", Value::Str(self.symbol));
+        order_ref(&self.order, param);
+    }
+}
+
+impl<const Q: usize> Request for AmendOrder<'_, Q> {
+    const REST: (&'static str, &'static str) = ("PUT", "/api/v3/order/amend/keepPriority");
+    const WS: &'static str = "order.amend.keepPriority";
+
+    fn params(&self, param: &mut dyn FnMut(&'static str, Value)) {
+        param("symbol", Value::Str(self.symbol));
+        order_ref(&self.order, param);
+        if let Some(id) = self.new_client_order_id {
+            param("newClientOrderId", Value::Str(id));
+        }
+        param("newQty", Value::Decimal(&self.new_qty));
+    }
+}
+
+fn side(side: &Side) -> &'static str {
+    match side {
+        Side::Bid => "BUY",
+        Side::Ask => "SELL",
+    }
+}
+
+fn order_ref(order: &OrderRef, param: &mut dyn FnMut(&'static str, Value)) {
+    match *order {
+        OrderRef::Id(id) => param("orderId", Value::Int(id)),
+        OrderRef::ClientId(id) => param("origClientOrderId", Value::Str(id)),
+    }
+}
+
+/// How requests are signed: HMAC-SHA256 with the secret key, hex encoded,
+/// or Ed25519 with the private key, base64 encoded
+pub enum Signer {
+    Hmac(Hmac<Sha256>),
+    Ed25519(SigningKey),
+}
+
+impl Signer {
+    pub fn hmac(secret: &[u8]) -> Self {
+        Signer::Hmac(Hmac::new_from_slice(secret).expect("HMAC takes keys of any length"))
+    }
+
+    /// From the 32-byte Ed25519 private key
+    pub fn ed25519(secret: &[u8; 32]) -> Self {
+        Signer::Ed25519(SigningKey::from_bytes(secret))
+    }
+
+    pub fn sign(&self, payload: &[u8]) -> String {
+        match self {
+            Signer::Hmac(mac) => {
+                let mut mac = mac.clone();
+                mac.update(payload);
+                let mut out = String::with_capacity(64);
+                for b in mac.finalize().into_bytes() {
+                    write!(out, "{:02x}", b).unwrap();
+                }
+                out
+            }
+            Signer::Ed25519(key) => BASE64.encode(key.sign(payload).to_bytes()),
+        }
+    }
+}
+
+pub struct Credentials {
+    pub api_key: String,
+    pub signer: Signer,
+}
+
+/// Milliseconds since the epoch, for the `timestamp` parameter
+pub fn timestamp_ms() -> u64 {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    now.as_millis() as u64
+}
+
+impl Credentials {
+    pub fn new(api_key: impl Into<String>, signer: Signer) -> Self {
+        Credentials {
+            api_key: api_key.into(),
+            signer,
+        }
+    }
+
+    /// Signed query string for the REST endpoint in `R::REST`, ending in
+    /// `timestamp` and `signature`. The API key goes in the `X-MBX-APIKEY`
+    /// header, not in here.
+    pub fn rest_query<R: Request>(
+        &self,
+        request: &R,
+        timestamp: u64,
+        recv_window: Option<u64>,
+    ) -> String {
+        let mut query = String::new();
+        request.params(&mut |key, value| push_param(&mut query, key, &value));
+        if let Some(window) = recv_window {
+            push_param(&mut query, "recvWindow", &Value::Int(window));
+        }
+        push_param(&mut query, "timestamp", &Value::Int(timestamp));
+
+        let signature = self.signer.sign(query.as_bytes());
+        push_param(&mut query, "signature", &Value::Str(&signature));
+        query
+    }
+
+    /// WebSocket API request text for `R::WS`. The signature covers every
+    /// parameter, API key included, sorted by name.
+    pub fn ws_request<R: Request>(
+        &self,
+        id: &str,
+        request: &R,
+        timestamp: u64,
+        recv_window: Option<u64>,
+    ) -> String {
+        let mut params: Vec<(&str, serde_json::Value)> = Vec::with_capacity(12);
+        request.params(&mut |key, value| params.push((key, value.to_json())));
+        params.push(("apiKey", Value::Str(&self.api_key).to_json()));
+        if let Some(window) = recv_window {
+            params.push(("recvWindow", Value::Int(window).to_json()));
+        }
+        params.push(("timestamp", Value::Int(timestamp).to_json()));
+        params.sort_unstable_by_key(|&(key, _)| key);
+
+        let mut payload = String::new();
+        for (key, value) in &params {
+            if !payload.is_empty() {
+                payload.push('&');
+            }
+            match value {
+                serde_json::Value::String(s) => write!(payload, "{}={}", key, s),
+                value => write!(payload, "{}={}", key, value),
+            }
+            .unwrap();
+        }
+        let signature = self.signer.sign(payload.as_bytes());
+        params.push(("signature", signature.into()));
+
+        let map: serde_json::Map<_, _> = params
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect();
+        serde_json::json!({ "id": id, "method": R::WS, "params": map }).to_string()
+    }
+}
+
+// Appends `key=value`, percent-encoding the value
+fn push_param(query: &mut String, key: &str, value: &Value) {
+    if !query.is_empty() {
+        query.push('&');
+    }
+    query.push_str(key);
+    query.push('=');
+
+    let start = query.len();
+    write!(query, "{}", value).unwrap();
+    if query[start..].bytes().all(unreserved) {
+        return;
+    }
+    let raw = query.split_off(start);
+    for b in raw.bytes() {
+        match unreserved(b) {
+            true => query.push(b as char),
+            false => write!(query, "%{:02X}", b).unwrap(),
+        }
+    }
+}
+
+fn unreserved(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b'~')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = b"NhqPtmdSJYdKjVHjA7PZj4Mge3R5YNiP1e3UZjInClVN65XAbvqqM6A7H5fATj0j";
+    const API_KEY: &str = "vmPUZE6mv9SD5VNHk4HlWFsOr6aKE2zvsw0MuIgwCIPy6utIco14y7Ju91duEh8A";
+
+    #[test]
+    fn test_rest_hmac() {
+        // The signed endpoint example of the REST API docs
+        let order =
+            NewOrder::<1, 0>::limit("LTCBTC", Side::Bid, "0.1".parse().unwrap(), Fp::from_int(1));
+        let creds = Credentials::new(API_KEY, Signer::hmac(SECRET));
+        assert_eq!(
+            creds.rest_query(&order, 1499827319559, Some(5000)),
+            "symbol=LTCBTC&side=BUY&type=LIMIT&timeInForce=GTC&quantity=1&price=0.1\
+             &recvWindow=5000&timestamp=1499827319559\
+             &signature=c8db56825ae71d6d79447849e617115f4a920fa2acdcab2b053c4b2838bd6b71"
+        );
+    }
+
+    #[test]
+    fn test_ws_hmac() {
+        // The signed request example of the WebSocket API docs
+        let order = NewOrder::<2, 8>::limit(
+            "BTCUSDT",
+            Side::Ask,
+            "52000.00".parse().unwrap(),
+            "0.01000000".parse().unwrap(),
+        )
+        .with_response(ResponseType::Ack);
+        let creds = Credentials::new(API_KEY, Signer::hmac(SECRET));
+        let text = creds.ws_request("req-1", &order, 1645423376532, Some(100));
+        let json: serde_json::Value = serde_json::from_str(&text).unwrap();
+
+        assert_eq!(json["id"], "req-1");
+        assert_eq!(json["method"], "order.place");
+        assert_eq!(json["params"]["apiKey"], API_KEY);
+        assert_eq!(json["params"]["price"], "52000.00");
+        assert_eq!(json["params"]["quantity"], "0.01000000");
+        assert_eq!(json["params"]["newOrderRespType"], "ACK");
+        assert_eq!(json["params"]["recvWindow"], 100);
+        assert_eq!(json["params"]["timestamp"], 1645423376532u64);
+        assert_eq!(
+            json["params"]["signature"],
+            "cc15477742bd704c29492d96c7ead9414dfd8e0ec4a00f947bb5bb454ddbd08a"
+        );
+    }
+
+    #[test]
+    fn test_ed25519() {
+        // RFC 8032, section 7.1, test 1
+        let mut secret = [0; 32];
+        let hex = "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60";
+        for (i, b) in secret.iter_mut().enumerate() {
+            *b = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap();
+        }
+        let signer = Signer::ed25519(&secret);
+        let signature = BASE64.decode(signer.sign(b"")).unwrap();
+        let mut out = String::new();
+        for b in signature {
+            write!(out, "{:02x}", b).unwrap();
+        }
+        assert_eq!(
+            out,
+            "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065\
+             224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24\
+             655141438e7a100b"
+        );
+
+        // Base64 in the query, so percent-encoded
+        let cancel = CancelOrder {
+            symbol: "BTCUSDT",
+            order: OrderRef::Id(12),
+        };
+        let creds = Credentials::new(API_KEY, signer);
+        let query = creds.rest_query(&cancel, 1, None);
+        let (signed, signature) = query.split_once("&signature=").unwrap();
+        assert_eq!(signed, "symbol=BTCUSDT&orderId=12&timestamp=1");
+        assert!(!signature.contains(['+', '/', '=']));
+    }
+
+    #[test]
+    fn test_amend() {
+        let amend = AmendOrder::<5> {
+            symbol: "ETHUSDT",
+            order: OrderRef::ClientId("my order/1"),
+            new_qty: "0.50000".parse().unwrap(),
+            new_client_order_id: None,
+        };
+        let creds = Credentials::new(API_KEY, Signer::hmac(SECRET));
+        let query = creds.rest_query(&amend, 7, Some(60000));
+        assert!(query.starts_with(
+            "symbol=ETHUSDT&origClientOrderId=my%20order%2F1&newQty=0.50000\
+             &recvWindow=60000&timestamp=7&signature="
+        ));
+        assert_eq!(AmendOrder::<5>::REST.1, "/api/v3/order/amend/keepPriority");
+
+        let text = creds.ws_request("2", &amend, 7, None);
+        assert!(text.contains(r#""origClientOrderId":"my order/1""#));
+        assert!(text.contains(r#""method":"order.amend.keepPriority""#));
+
+        // Order ids go out as numbers
+        let cancel = CancelOrder {
+            symbol: "ETHUSDT",
+            order: OrderRef::Id(12345),
+        };
+        let text = creds.ws_request("3", &cancel, 7, None);
+        assert!(text.contains(r#""orderId":12345"#));
+    }
+}
@@ -0,0 +1,320 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ops::{Add, Sub};
+
+use e001::orderbook::{Level, OrderBook, Side, Top};
+
+// Our working orders on top of a market book. The exchange's depth includes
+// our own quotes, so a strategy reading it straight reacts to itself.
+// `OwnBook` keeps our orders by client order id and, as an `OrderBook`,
+// shows the book without them: each level less our quantity there, and
+// levels that are only ours left out. `raw()` is the market as published.
+//
+// Ex-own sizes are kept per level we are at and refreshed whenever the
+// level or our orders at it change, so reading the book costs a map lookup
+// per level we quote.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OwnOrder<P, Q> {
+    pub side: Side,
+    pub price: P,
+    pub qty: Q,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct OwnLevel<Q> {
+    own: Q,
+    /// Displayed size less ours, never below zero
+    ex: Q,
+}
+
+pub struct OwnBook<B: OrderBook> {
+    book: B,
+    orders: HashMap<String, OwnOrder<B::Price, B::Qty>>,
+    bids: BTreeMap<B::Price, OwnLevel<B::Qty>>,
+    asks: BTreeMap<B::Price, OwnLevel<B::Qty>>,
+}
+
+impl<B> OwnBook<B>
+where
+    B: OrderBook,
+    B::Price: Ord + Clone,
+    B::Qty: Copy + Default + PartialOrd + Add<Output = B::Qty> + Sub<Output = B::Qty>,
+{
+    pub fn new(book: B) -> Self {
+        OwnBook {
+            book,
+            orders: HashMap::new(),
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+        }
+    }
+
+    /// The market as published, our orders included
+    pub fn raw(&self) -> &B {
+        &self.book
+    }
+
+    pub fn into_inner(self) -> B {
+        self.book
+    }
+
+    /// Starts tracking a working order, replacing any with the same id
+    pub fn add_order(&mut self, client_id: &str, side: Side, price: B::Price, qty: B::Qty) {
+        self.remove_order(client_id);
+        self.adjust(&side, &price, qty, true);
+        let order = OwnOrder { side, price, qty };
+        self.orders.insert(client_id.to_string(), order);
+    }
+
+    /// Stops tracking an order, once cancelled or done
+    pub fn remove_order(&mut self, client_id: &str) -> Option<OwnOrder<B::Price, B::Qty>> {
+        let order = self.orders.remove(client_id)?;
+        self.adjust(&order.side, &order.price, order.qty, false);
+        Some(order)
+    }
+
+    /// Takes `qty` off an order, dropping it once nothing is left
+    pub fn fill(&mut self, client_id: &str, qty: B::Qty) {
+        let Some(order) = self.orders.get(client_id) else {
+            return;
+        };
+        let left = match order.qty > qty {
+            true => order.qty - qty,
+            false => B::Qty::default(),
+        };
+        self.amend(client_id, left);
+    }
+
+    /// Sets what is left of an order
+    pub fn amend(&mut self, client_id: &str, qty: B::Qty) {
+        let Some(order) = self.orders.get_mut(client_id) else {
+            return;
+        };
+        let (side, price, old) = (order.side.clone(), order.price.clone(), order.qty);
+        order.qty = qty;
+        if qty <= B::Qty::default() {
+            self.orders.remove(client_id);
+        }
+        self.adjust(&side, &price, old, false);
+        if qty > B::Qty::default() {
+            self.adjust(&side, &price, qty, true);
+        }
+    }
+
+    pub fn order(&self, client_id: &str) -> Option<&OwnOrder<B::Price, B::Qty>> {
+        self.orders.get(client_id)
+    }
+
+    pub fn orders(&self) -> impl Iterator<Item = (&str, &OwnOrder<B::Price, B::Qty>)> {
+        self.orders.iter().map(|(id, o)| (id.as_str(), o))
+    }
+
+    /// Our total quantity at a level
+    pub fn own_qty(&self, side: &Side, price: &B::Price) -> B::Qty {
+        let levels = match side {
+            Side::Bid => &self.bids,
+            Side::Ask => &self.asks,
+        };
+        levels.get(price).map(|l| l.own).unwrap_or_default()
+    }
+
+    // Adds or removes `qty` of ours at a level and refreshes its ex-own size
+    fn adjust(&mut self, side: &Side, price: &B::Price, qty: B::Qty, add: bool) {
+        let shown = displayed(&self.book, side, price);
+        let levels = match side {
+            Side::Bid => &mut self.bids,
+            Side::Ask => &mut self.asks,
+        };
+        let level = levels.entry(price.clone()).or_insert(OwnLevel {
+            own: B::Qty::default(),
+            ex: B::Qty::default(),
+        });
+        level.own = match (add, level.own > qty) {
+            (true, _) => level.own + qty,
+            (false, true) => level.own - qty,
+            (false, false) => B::Qty::default(),
+        };
+        if level.own <= B::Qty::default() {
+            levels.remove(price);
+            return;
+        }
+        level.ex = ex(shown, level.own);
+    }
+}
+
+fn ex<Q: Copy + Default + PartialOrd + Sub<Output = Q>>(shown: Q, own: Q) -> Q {
+    match shown > own {
+        true => shown - own,
+        false => Q::default(),
+    }
+}
+
+// Displayed size at `price` on `side`
+fn displayed<B>(book: &B, side: &Side, price: &B::Price) -> B::Qty
+where
+    B: OrderBook,
+    B::Price: Ord,
+    B::Qty: Copy + Default,
+{
+    let found = match side {
+        Side::Bid => book.bids().find(|(p, _)| *p <= price),
+        Side::Ask => book.asks().find(|(p, _)| *p >= price),
+    };
+    match found {
+        Some((p, q)) if p == price => *q,
+        _ => B::Qty::default(),
+    }
+}
+
+impl<B> OrderBook for OwnBook<B>
+where
+    B: OrderBook,
+    B::Price: Ord + Clone,
+    B::Qty: Copy + Default + PartialOrd + Add<Output = B::Qty> + Sub<Output = B::Qty>,
+{
+    type Price = B::Price;
+    type Qty = B::Qty;
+
+    fn insert(&mut self, side: Side, price: Self::Price, quantity: Self::Qty) {
+        let levels = match side {
+            Side::Bid => &mut self.bids,
+            Side::Ask => &mut self.asks,
+        };
+        if let Some(level) = levels.get_mut(&price) {
+            level.ex = ex(quantity, level.own);
+        }
+        self.book.insert(side, price, quantity);
+    }
+
+    fn delete(&mut self, side: Side, price: Self::Price) {
+        let levels = match side {
+            Side::Bid => &mut self.bids,
+            Side::Ask => &mut self.asks,
+        };
+        if let Some(level) = levels.get_mut(&price) {
+            level.ex = B::Qty::default();
+        }
+        self.book.delete(side, price);
+    }
+
+    fn top(&self) -> Top<'_, Self::Price, Self::Qty> {
+        (self.bids().next(), self.asks().next())
+    }
+
+    fn bids(&self) -> impl Iterator<Item = Level<'_, Self::Price, Self::Qty>> {
+        self.book
+            .bids()
+            .filter_map(|level| without(&self.bids, level))
+    }
+
+    fn asks(&self) -> impl Iterator<Item = Level<'_, Self::Price, Self::Qty>> {
+        self.book
+            .asks()
+            .filter_map(|level| without(&self.asks, level))
+    }
+}
+
+// A market level less our part of it, or nothing if it was all ours
+fn without<'a, P: Ord, Q: Default + PartialOrd>(
+    own: &'a BTreeMap<P, OwnLevel<Q>>,
+    (price, qty): Level<'a, P, Q>,
+) -> Option<Level<'a, P, Q>> {
+    match own.get(price) {
+        None => Some((price, qty)),
+        Some(level) if level.ex > Q::default() => Some((price, &level.ex)),
+        Some(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fp;
+    use crate::fp::Fp;
+    use e001::btree::BTreeBook;
+    use e001::hybrid::HybridBook;
+    use rust_decimal::Decimal;
+
+    fn levels<B: OrderBook<Price = Fp<2>, Qty = Fp<3>>>(
+        book: &B,
+        side: Side,
+    ) -> Vec<(String, String)> {
+        let show = |(p, q): Level<'_, Fp<2>, Fp<3>>| (p.to_string(), q.to_string());
+        match side {
+            Side::Bid => book.bids().map(show).collect(),
+            Side::Ask => book.asks().map(show).collect(),
+        }
+    }
+
+    #[test]
+    fn test_ex_own() {
+        let mut book = OwnBook::new(HybridBook::<Fp<2>, Fp<3>>::new());
+        book.insert(Side::Bid, fp!(99.00), fp!(1.000));
+        book.insert(Side::Bid, fp!(98.00), fp!(2.000));
+        book.insert(Side::Ask, fp!(101.00), fp!(0.500));
+
+        // Our quotes show up in the market data after we add them
+        book.add_order("bid-1", Side::Bid, fp!(99.50), fp!(0.300));
+        book.add_order("bid-2", Side::Bid, fp!(99.00), fp!(0.400));
+        book.add_order("ask-1", Side::Ask, fp!(101.00), fp!(0.500));
+        book.insert(Side::Bid, fp!(99.50), fp!(0.300));
+        book.insert(Side::Bid, fp!(99.00), fp!(1.400));
+
+        assert_eq!(
+            levels(&book, Side::Bid),
+            [
+                ("99.00".into(), "1.000".into()),
+                ("98.00".into(), "2.000".into())
+            ]
+        );
+        assert_eq!(levels(&book, Side::Ask), []);
+        assert_eq!(book.top(), (Some((&fp!(99.00), &fp!(1.000))), None));
+        assert_eq!(book.raw().top().0, Some((&fp!(99.50), &fp!(0.300))));
+        assert_eq!(book.own_qty(&Side::Bid, &fp!(99.00)), fp!(0.400));
+
+        // Someone joins behind our bid
+        book.insert(Side::Bid, fp!(99.50), fp!(1.300));
+        assert_eq!(book.top().0, Some((&fp!(99.50), &fp!(1.000))));
+
+        // Partly filled, then cancelled
+        book.fill("bid-1", fp!(0.100));
+        assert_eq!(book.order("bid-1").unwrap().qty, fp!(0.200));
+        assert_eq!(book.top().0, Some((&fp!(99.50), &fp!(1.100))));
+        book.remove_order("bid-1");
+        assert_eq!(book.top().0, Some((&fp!(99.50), &fp!(1.300))));
+
+        // Filled completely, the ask is gone from both views
+        book.fill("ask-1", fp!(0.500));
+        assert!(book.order("ask-1").is_none());
+        book.delete(Side::Ask, fp!(101.00));
+        assert_eq!(book.top().1, None);
+        assert_eq!(book.orders().count(), 1);
+    }
+
+    #[test]
+    fn test_lagging_book() {
+        // An order is added before the market data shows it
+        let mut book = OwnBook::new(BTreeBook::<Fp<2>, Fp<3>>::new());
+        book.insert(Side::Ask, fp!(101.00), fp!(1.000));
+        book.add_order("a", Side::Ask, fp!(101.00), fp!(0.400));
+        assert_eq!(book.top().1, Some((&fp!(101.00), &fp!(0.600))));
+        book.add_order("b", Side::Ask, fp!(101.00), fp!(0.800));
+        assert_eq!(book.top().1, None);
+
+        book.insert(Side::Ask, fp!(101.00), fp!(2.200));
+        assert_eq!(book.top().1, Some((&fp!(101.00), &fp!(1.000))));
+        // Re-adding an id replaces it
+        book.add_order("b", Side::Ask, fp!(102.00), fp!(0.800));
+        assert_eq!(book.own_qty(&Side::Ask, &fp!(101.00)), fp!(0.400));
+        // The old one shows until the market data catches up
+        assert_eq!(book.top().1, Some((&fp!(101.00), &fp!(1.800))));
+        book.insert(Side::Ask, fp!(101.00), fp!(1.400));
+        assert_eq!(book.top().1, Some((&fp!(101.00), &fp!(1.000))));
+
+        // Any book type works
+        let mut book = OwnBook::new(BTreeBook::<Decimal, Decimal>::new());
+        book.insert(Side::Bid, Decimal::from(10), Decimal::from(5));
+        book.add_order("x", Side::Bid, Decimal::from(10), Decimal::from(2));
+        assert_eq!(book.top().0, Some((&Decimal::from(10), &Decimal::from(3))));
+    }
+}
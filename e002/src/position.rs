@@ -0,0 +1,377 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::fp::{Fp, Rounding, rescale};
+use e001::orderbook::{OrderBook, Side};
+
+// Net position and PnL per symbol, built from our fills. Money (cost, PnL
+// and fees) is kept at its own precision `M`: price times quantity has
+// P + Q decimals, so with `M >= P + Q` every figure is exact and sums to
+// what the exchange reports; a smaller `M` truncates each fill's notional
+// toward zero.
+
+/// How the cost of a position is released when it is reduced
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CostBasis {
+    /// Oldest fills close first
+    #[default]
+    Fifo,
+    /// Every unit carries the running average entry price
+    Average,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fill<const P: usize, const Q: usize, const M: usize> {
+    pub side: Side,
+    pub price: Fp<P>,
+    pub qty: Fp<Q>,
+    /// Commission, valued in the quote asset
+    pub fee: Fp<M>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Position<const P: usize, const Q: usize, const M: usize> {
+    basis: CostBasis,
+    /// Net quantity, negative when short
+    qty: Fp<Q>,
+    /// Entry cost of the open quantity, signed like `qty`
+    cost: Fp<M>,
+    /// Open entries, oldest first, only kept for `Fifo`
+    lots: VecDeque<(Fp<P>, Fp<Q>)>,
+    realized: Fp<M>,
+    fees: Fp<M>,
+}
+
+impl<const P: usize, const Q: usize, const M: usize> Position<P, Q, M> {
+    pub fn new(basis: CostBasis) -> Self {
+        Position {
+            basis,
+            qty: Fp::ZERO,
+            cost: Fp::ZERO,
+            lots: VecDeque::new(),
+            realized: Fp::ZERO,
+            fees: Fp::ZERO,
+        }
+    }
+
+    pub fn apply(&mut self, fill: &Fill<P, Q, M>) {
+        self.fees += fill.fee;
+        self.realized -= fill.fee;
+
+        let sell = fill.side == Side::Ask;
+        let mut left = fill.qty;
+
+        // Close against the open side first
+        if !self.qty.is_zero() && self.qty.is_negative() != sell {
+            let closed = left.min(self.qty.abs());
+            let released = match self.basis {
+                CostBasis::Fifo => {
+                    let mut released = 0;
+                    let mut todo = closed;
+                    while todo.is_positive() {
+                        let lot = self.lots.front_mut().expect("lots cover the position");
+                        let take = todo.min(lot.1);
+                        released += lot.0.raw() * take.raw();
+                        lot.1 -= take;
+                        todo -= take;
+                        if lot.1.is_zero() {
+                            self.lots.pop_front();
+                        }
+                    }
+                    signed(money(released, P + Q), self.qty.is_negative())
+                }
+                CostBasis::Average => {
+                    Fp::from_raw(self.cost.raw() * closed.raw() / self.qty.abs().raw())
+                }
+            };
+
+            // What the closed quantity was sold for (or bought back at),
+            // signed like the position, less what it cost
+            let exit = signed(notional(fill.price, closed), self.qty.is_negative());
+            self.realized += exit - released;
+            self.cost -= released;
+            self.qty += signed(closed, sell);
+            left -= closed;
+        }
+
+        // Whatever is left opens or adds to the position
+        if left.is_positive() {
+            self.qty += signed(left, sell);
+            self.cost += signed(notional(fill.price, left), sell);
+            if self.basis == CostBasis::Fifo {
+                self.lots.push_back((fill.price, left));
+            }
+        }
+    }
+
+    pub fn basis(&self) -> CostBasis {
+        self.basis
+    }
+
+    /// Net quantity, negative when short
+    pub fn qty(&self) -> Fp<Q> {
+        self.qty
+    }
+
+    /// Entry cost of the open quantity, negative when short
+    pub fn cost(&self) -> Fp<M> {
+        self.cost
+    }
+
+    /// Average entry price of the open quantity, truncated to `P`
+    pub fn entry_price(&self) -> Option<Fp<P>> {
+        if self.qty.is_zero() {
+            return None;
+        }
+        let cost = rescale(self.cost.raw().abs(), M, P + Q, Rounding::ToZero);
+        Some(Fp::from_raw(cost / self.qty.abs().raw()))
+    }
+
+    /// Realised PnL, net of every fee paid so far
+    pub fn realized(&self) -> Fp<M> {
+        self.realized
+    }
+
+    pub fn fees(&self) -> Fp<M> {
+        self.fees
+    }
+
+    /// Unrealised PnL with the position marked at the book's mid. `None`
+    /// unless both sides have a level; zero when flat.
+    pub fn unrealized_mid<B>(&self, book: &B) -> Option<Fp<M>>
+    where
+        B: OrderBook<Price = Fp<P>, Qty = Fp<Q>>,
+    {
+        if self.qty.is_zero() {
+            return Some(Fp::ZERO);
+        }
+        let (Some((bid, _)), Some((ask, _))) = book.top() else {
+            return None;
+        };
+        // The mid times quantity at P + Q, exact before the halving
+        let value = (bid.raw() + ask.raw()) * self.qty.raw() / 2;
+        Some(money(value, P + Q) - self.cost)
+    }
+
+    /// Unrealised PnL if the position were closed into the book now, by
+    /// selling into `bids()` or buying from `asks()`. `None` when the book
+    /// is not deep enough; zero when flat.
+    pub fn unrealized_exit<B>(&self, book: &B) -> Option<Fp<M>>
+    where
+        B: OrderBook<Price = Fp<P>, Qty = Fp<Q>>,
+    {
+        let mut left = self.qty.abs();
+        let mut value = 0;
+        let mut take = |(price, size): (&Fp<P>, &Fp<Q>)| {
+            let size = left.min(*size);
+            value += price.raw() * size.raw();
+            left -= size;
+            left.is_positive()
+        };
+        if self.qty.is_positive() {
+            book.bids().take_while(|&level| take(level)).count();
+        } else if self.qty.is_negative() {
+            book.asks().take_while(|&level| take(level)).count();
+        }
+        if left.is_positive() {
+            return None;
+        }
+
+        Some(signed(money(value, P + Q), self.qty.is_negative()) - self.cost)
+    }
+}
+
+/// Positions by symbol, all kept on the same cost basis
+#[derive(Debug, Clone, Default)]
+pub struct Positions<const P: usize, const Q: usize, const M: usize> {
+    basis: CostBasis,
+    symbols: HashMap<String, Position<P, Q, M>>,
+}
+
+impl<const P: usize, const Q: usize, const M: usize> Positions<P, Q, M> {
+    pub fn new(basis: CostBasis) -> Self {
+        Positions {
+            basis,
+            symbols: HashMap::new(),
+        }
+    }
+
+    pub fn apply(&mut self, symbol: &str, fill: &Fill<P, Q, M>) {
+        if let Some(position) = self.symbols.get_mut(symbol) {
+            return position.apply(fill);
+        }
+        let mut position = Position::new(self.basis);
+        position.apply(fill);
+        self.symbols.insert(symbol.to_string(), position);
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<&Position<P, Q, M>> {
+        self.symbols.get(symbol)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Position<P, Q, M>)> {
+        self.symbols.iter().map(|(s, p)| (s.as_str(), p))
+    }
+
+    /// Realised PnL summed over every symbol
+    pub fn realized(&self) -> Fp<M> {
+        self.symbols.values().map(|p| p.realized).sum()
+    }
+}
+
+fn signed<const N: usize>(value: Fp<N>, negative: bool) -> Fp<N> {
+    match negative {
+        true => -value,
+        false => value,
+    }
+}
+
+fn notional<const P: usize, const Q: usize, const M: usize>(price: Fp<P>, qty: Fp<Q>) -> Fp<M> {
+    money(price.raw() * qty.raw(), P + Q)
+}
+
+// A raw amount with `decimals` decimals, at the money precision
+fn money<const M: usize>(raw: i128, decimals: usize) -> Fp<M> {
+    Fp::from_raw(rescale(raw, decimals, M, Rounding::ToZero))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fp;
+    use e001::hybrid::HybridBook;
+
+    type Pos = Position<2, 3, 8>;
+
+    fn fill(side: Side, price: Fp<2>, qty: Fp<3>, fee: Fp<8>) -> Fill<2, 3, 8> {
+        Fill {
+            side,
+            price,
+            qty,
+            fee,
+        }
+    }
+
+    #[test]
+    fn test_fifo_and_average() {
+        let fills = [
+            fill(Side::Bid, fp!(100.00), fp!(1.000), fp!(0.01000000)),
+            fill(Side::Bid, fp!(110.00), fp!(1.000), fp!(0.01100000)),
+            fill(Side::Ask, fp!(120.00), fp!(1.000), fp!(0.01200000)),
+        ];
+        let mut fifo = Pos::new(CostBasis::Fifo);
+        let mut average = Pos::new(CostBasis::Average);
+        for fill in &fills {
+            fifo.apply(fill);
+            average.apply(fill);
+        }
+
+        assert_eq!(fifo.fees(), fp!(0.03300000));
+        // 120 - 100 against 120 - 105
+        assert_eq!(fifo.realized(), fp!(19.96700000));
+        assert_eq!(average.realized(), fp!(14.96700000));
+        assert_eq!(fifo.qty(), fp!(1.000));
+        assert_eq!(fifo.entry_price(), Some(fp!(110.00)));
+        assert_eq!(average.entry_price(), Some(fp!(105.00)));
+
+        // Closing out leaves both with the same total
+        let close = fill(Side::Ask, fp!(90.00), fp!(1.000), fp!(0.00000000));
+        fifo.apply(&close);
+        average.apply(&close);
+        assert_eq!(fifo.realized(), average.realized());
+        assert_eq!(fifo.realized(), fp!(-0.03300000));
+        assert_eq!(fifo.cost(), Fp::ZERO);
+        assert_eq!(fifo.entry_price(), None);
+    }
+
+    #[test]
+    fn test_flip_and_exact() {
+        let mut pos = Pos::new(CostBasis::Fifo);
+        pos.apply(&fill(
+            Side::Bid,
+            fp!(104276.90),
+            fp!(0.013),
+            fp!(0.00000000),
+        ));
+        pos.apply(&fill(
+            Side::Bid,
+            fp!(104276.30),
+            fp!(0.007),
+            fp!(0.00000000),
+        ));
+
+        // Sells through the long into a short of 0.010
+        pos.apply(&fill(
+            Side::Ask,
+            fp!(104277.10),
+            fp!(0.030),
+            fp!(0.00000000),
+        ));
+        assert_eq!(pos.qty(), fp!(-0.010));
+        assert_eq!(pos.cost(), fp!(-1042.77100000));
+        // 0.013 * 0.20 + 0.007 * 0.80, to the last digit
+        assert_eq!(pos.realized(), fp!(0.00820000));
+
+        pos.apply(&fill(
+            Side::Bid,
+            fp!(104270.00),
+            fp!(0.004),
+            fp!(0.00000000),
+        ));
+        assert_eq!(pos.realized(), fp!(0.03660000));
+        assert_eq!(pos.qty(), fp!(-0.006));
+    }
+
+    #[test]
+    fn test_unrealized() {
+        let mut book = HybridBook::<Fp<2>, Fp<3>>::new();
+        book.insert(Side::Bid, fp!(99.00), fp!(1.000));
+        book.insert(Side::Bid, fp!(98.00), fp!(1.000));
+        book.insert(Side::Ask, fp!(101.01), fp!(2.000));
+
+        let mut pos = Pos::new(CostBasis::Average);
+        assert_eq!(pos.unrealized_mid(&book), Some(Fp::ZERO));
+
+        pos.apply(&fill(Side::Bid, fp!(95.00), fp!(1.500), fp!(0.00000000)));
+        // Mid 100.005
+        assert_eq!(pos.unrealized_mid(&book), Some(fp!(7.50750000)));
+        // 99 * 1 + 98 * 0.5 - 142.5
+        assert_eq!(pos.unrealized_exit(&book), Some(fp!(5.50000000)));
+
+        pos.apply(&fill(Side::Ask, fp!(100.00), fp!(4.000), fp!(0.00000000)));
+        assert_eq!(pos.realized(), fp!(7.50000000));
+        // Short 2.5 against 2 on the ask
+        assert_eq!(pos.unrealized_exit(&book), None);
+        pos.apply(&fill(Side::Bid, fp!(100.00), fp!(0.500), fp!(0.00000000)));
+        assert_eq!(pos.unrealized_exit(&book), Some(fp!(-2.02000000)));
+        assert_eq!(pos.unrealized_mid(&book), Some(fp!(-0.01000000)));
+
+        let empty = HybridBook::<Fp<2>, Fp<3>>::new();
+        assert_eq!(pos.unrealized_mid(&empty), None);
+    }
+
+    #[test]
+    fn test_positions() {
+        let mut positions = Positions::<2, 3, 8>::new(CostBasis::Fifo);
+        positions.apply(
+            "BTCUSDT",
+            &fill(Side::Bid, fp!(100.00), fp!(1.000), fp!(0.10000000)),
+        );
+        positions.apply(
+            "ETHUSDT",
+            &fill(Side::Ask, fp!(10.00), fp!(1.000), fp!(0.01000000)),
+        );
+        positions.apply(
+            "BTCUSDT",
+            &fill(Side::Ask, fp!(101.00), fp!(1.000), fp!(0.10000000)),
+        );
+
+        assert_eq!(
+            positions.get("BTCUSDT").unwrap().realized(),
+            fp!(0.80000000)
+        );
+        assert_eq!(positions.get("ETHUSDT").unwrap().qty(), fp!(-1.000));
+        assert_eq!(positions.realized(), fp!(0.79000000));
+        assert_eq!(positions.iter().count(), 2);
+        assert!(positions.get("XRPUSDT").is_none());
+    }
+}
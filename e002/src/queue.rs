@@ -0,0 +1,279 @@
+use crate::fp::Fp;
+use e001::orderbook::{OrderBook, Side};
+
+// Where one of our resting orders stands in its price level. An L2 book
+// only shows the level's total size, so the quantity ahead of us is an
+// estimate: it starts at the size displayed when we joined, trades at our
+// price take it down first-in first-out, and when the level shrinks by more
+// than the trades explain, the difference is cancels, some share of which
+// were ahead of us. The `CancelModel` decides that share.
+//
+// Sizes passed in must exclude our own order; on a live book, take them
+// from an ex-own view.
+
+/// How much of a cancel at our level is assumed to come from ahead of us
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CancelModel {
+    /// From behind us first, the pessimistic end
+    Back,
+    /// From ahead of us first, the optimistic end
+    Front,
+    /// In proportion to the size ahead and behind
+    ProRata,
+    /// In proportion to `ahead^n` and `behind^n`; above one favours the
+    /// larger side
+    Power(f64),
+    /// In proportion to `ln(1 + ahead)` and `ln(1 + behind)`
+    Log,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueuePosition<const P: usize, const Q: usize> {
+    pub side: Side,
+    pub price: Fp<P>,
+    pub model: CancelModel,
+    qty: Fp<Q>,
+    ahead: Fp<Q>,
+    joined_ahead: Fp<Q>,
+    /// Displayed size when last seen
+    level: Fp<Q>,
+    /// Traded out of the displayed size since then
+    traded: Fp<Q>,
+}
+
+impl<const P: usize, const Q: usize> QueuePosition<P, Q> {
+    /// Joins behind `level`, the size displayed at `price`
+    pub fn new(side: Side, price: Fp<P>, qty: Fp<Q>, level: Fp<Q>, model: CancelModel) -> Self {
+        QueuePosition {
+            side,
+            price,
+            model,
+            qty,
+            ahead: level,
+            joined_ahead: level,
+            level,
+            traded: Fp::ZERO,
+        }
+    }
+
+    /// Joins behind whatever `book` shows at `price`
+    pub fn join<B>(side: Side, price: Fp<P>, qty: Fp<Q>, book: &B, model: CancelModel) -> Self
+    where
+        B: OrderBook<Price = Fp<P>, Qty = Fp<Q>>,
+    {
+        let level = level(book, &side, price);
+        QueuePosition::new(side, price, qty, level, model)
+    }
+
+    /// Our quantity still resting
+    pub fn qty(&self) -> Fp<Q> {
+        self.qty
+    }
+
+    /// Estimated quantity ahead of us
+    pub fn ahead(&self) -> Fp<Q> {
+        self.ahead
+    }
+
+    /// Estimated quantity behind us
+    pub fn behind(&self) -> Fp<Q> {
+        (self.level - self.traded - self.ahead).max(Fp::ZERO)
+    }
+
+    /// Share of the queue we joined behind that is gone, from 0 to 1
+    pub fn progress(&self) -> f64 {
+        match self.joined_ahead.is_zero() {
+            true => 1.0,
+            false => 1.0 - ratio(self.ahead, self.joined_ahead),
+        }
+    }
+
+    /// A trade print. Returns how much of it is estimated to have filled
+    /// us, which is not taken off `qty` until `fill` is called.
+    pub fn on_trade(&mut self, price: Fp<P>, qty: Fp<Q>, aggressor: &Side) -> Fp<Q> {
+        let (at, through) = match (&self.side, aggressor) {
+            (Side::Bid, Side::Ask) => (price == self.price, price < self.price),
+            (Side::Ask, Side::Bid) => (price == self.price, price > self.price),
+            _ => return Fp::ZERO,
+        };
+        if through {
+            self.ahead = Fp::ZERO;
+            return self.qty;
+        }
+        if !at {
+            return Fp::ZERO;
+        }
+        let before = qty.min(self.ahead);
+        let ours = (qty - before).min(self.qty);
+        self.ahead -= before;
+        self.traded += qty - ours;
+        ours
+    }
+
+    /// The displayed size at our price, after an update
+    pub fn on_level(&mut self, size: Fp<Q>) {
+        let left = (self.level - self.traded).max(Fp::ZERO);
+        if size < left {
+            let cancelled = left - size;
+            let behind = (left - self.ahead).max(Fp::ZERO);
+            let from_ahead = match self.model {
+                CancelModel::Back => (cancelled - behind).max(Fp::ZERO),
+                CancelModel::Front => cancelled.min(self.ahead),
+                model => {
+                    let share = share(model, self.ahead, behind);
+                    Fp::from_raw((cancelled.raw() as f64 * share).round() as i128)
+                }
+            };
+            self.ahead -= from_ahead.min(self.ahead);
+        }
+        self.ahead = self.ahead.min(size);
+        self.level = size;
+        self.traded = Fp::ZERO;
+    }
+
+    /// Reads the displayed size at our price from `book`
+    pub fn on_book<B>(&mut self, book: &B)
+    where
+        B: OrderBook<Price = Fp<P>, Qty = Fp<Q>>,
+    {
+        self.on_level(level(book, &self.side, self.price));
+    }
+
+    /// Our order was filled for `qty`
+    pub fn fill(&mut self, qty: Fp<Q>) {
+        self.qty -= qty.min(self.qty);
+    }
+
+    /// Our order was amended down to `qty`, keeping its place
+    pub fn amend(&mut self, qty: Fp<Q>) {
+        self.qty = self.qty.min(qty);
+    }
+
+    /// Chance of a complete fill if `volume` is the expected quantity to
+    /// trade at our price over the horizon, taking that quantity to be
+    /// exponentially distributed
+    pub fn fill_probability(&self, volume: Fp<Q>) -> f64 {
+        let needed = self.ahead + self.qty;
+        match (needed.is_positive(), volume.is_positive()) {
+            (false, _) => 1.0,
+            (true, false) => 0.0,
+            (true, true) => (-ratio(needed, volume)).exp(),
+        }
+    }
+}
+
+// Displayed size at `price` on `side`
+pub(crate) fn level<B, const P: usize, const Q: usize>(book: &B, side: &Side, price: Fp<P>) -> Fp<Q>
+where
+    B: OrderBook<Price = Fp<P>, Qty = Fp<Q>>,
+{
+    let found = match side {
+        Side::Bid => book.bids().find(|(p, _)| **p <= price),
+        Side::Ask => book.asks().find(|(p, _)| **p >= price),
+    };
+    match found {
+        Some((p, q)) if *p == price => *q,
+        _ => Fp::ZERO,
+    }
+}
+
+fn ratio<const Q: usize>(a: Fp<Q>, b: Fp<Q>) -> f64 {
+    a.raw() as f64 / b.raw() as f64
+}
+
+// Share of a cancel taken from ahead of us
+fn share<const Q: usize>(model: CancelModel, ahead: Fp<Q>, behind: Fp<Q>) -> f64 {
+    let scale = 10f64.powi(Q as i32);
+    let (a, b) = (ahead.raw() as f64 / scale, behind.raw() as f64 / scale);
+    let (a, b) = match model {
+        CancelModel::Power(n) => (a.powf(n), b.powf(n)),
+        CancelModel::Log => (a.ln_1p(), b.ln_1p()),
+        _ => (a, b),
+    };
+    match a + b > 0.0 {
+        true => a / (a + b),
+        false => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fp;
+    use e001::hybrid::HybridBook;
+
+    fn queue(model: CancelModel) -> QueuePosition<2, 3> {
+        let mut book = HybridBook::<Fp<2>, Fp<3>>::new();
+        book.insert(Side::Bid, fp!(99.00), fp!(3.000));
+        book.insert(Side::Bid, fp!(98.00), fp!(5.000));
+        QueuePosition::join(Side::Bid, fp!(99.00), fp!(1.000), &book, model)
+    }
+
+    #[test]
+    fn test_trades() {
+        let mut queue = queue(CancelModel::Back);
+        assert_eq!(queue.ahead(), fp!(3.000));
+
+        // Other side of the book and other prices do nothing
+        assert_eq!(queue.on_trade(fp!(99.00), fp!(1.000), &Side::Bid), Fp::ZERO);
+        assert_eq!(queue.on_trade(fp!(99.50), fp!(1.000), &Side::Ask), Fp::ZERO);
+        assert_eq!(queue.on_trade(fp!(99.00), fp!(2.500), &Side::Ask), Fp::ZERO);
+        assert_eq!(queue.ahead(), fp!(0.500));
+        assert!((queue.progress() - 2.5 / 3.0).abs() < 1e-12);
+
+        // The trades show up in the next update and are not cancels
+        queue.on_level(fp!(0.500));
+        assert_eq!(queue.ahead(), fp!(0.500));
+
+        assert_eq!(
+            queue.on_trade(fp!(99.00), fp!(0.800), &Side::Ask),
+            fp!(0.300)
+        );
+        queue.fill(fp!(0.300));
+        assert_eq!(queue.qty(), fp!(0.700));
+        assert_eq!(queue.ahead(), Fp::ZERO);
+
+        // Trading through us fills the rest
+        assert_eq!(
+            queue.on_trade(fp!(98.00), fp!(0.100), &Side::Ask),
+            fp!(0.700)
+        );
+    }
+
+    #[test]
+    fn test_cancel_models() {
+        // 3.000 ahead, then 2.000 joins behind and 2.000 is cancelled
+        let cancel = |model| {
+            let mut queue = queue(model);
+            queue.on_level(fp!(5.000));
+            assert_eq!(queue.behind(), fp!(2.000));
+            queue.on_level(fp!(3.000));
+            queue.ahead()
+        };
+        assert_eq!(cancel(CancelModel::Back), fp!(3.000));
+        assert_eq!(cancel(CancelModel::Front), fp!(1.000));
+        assert_eq!(cancel(CancelModel::ProRata), fp!(1.800));
+        // 9 / 13 of the cancel
+        assert_eq!(cancel(CancelModel::Power(2.0)), fp!(1.615));
+        assert!(cancel(CancelModel::Log) > fp!(1.800));
+
+        // Never more ahead than the level shows
+        let mut queue = queue(CancelModel::Back);
+        queue.on_level(fp!(1.000));
+        assert_eq!(queue.ahead(), fp!(1.000));
+    }
+
+    #[test]
+    fn test_fill_probability() {
+        let mut queue = queue(CancelModel::Back);
+        assert_eq!(queue.fill_probability(Fp::ZERO), 0.0);
+        assert_eq!(queue.fill_probability(fp!(4.000)), (-1.0f64).exp());
+
+        // Closer to the front is likelier
+        queue.on_trade(fp!(99.00), fp!(2.000), &Side::Ask);
+        assert_eq!(queue.fill_probability(fp!(4.000)), (-0.5f64).exp());
+        queue.on_trade(fp!(98.99), fp!(0.001), &Side::Ask);
+        queue.fill(queue.qty());
+        assert_eq!(queue.fill_probability(fp!(4.000)), 1.0);
+    }
+}
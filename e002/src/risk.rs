@@ -0,0 +1,443 @@
+use std::collections::HashMap;
+use std::{error::Error, fmt};
+
+use crate::fp::Fp;
+use crate::instrument::Multiplier;
+use crate::order::{NewOrder, OrderType};
+use e001::orderbook::{OrderBook, Side};
+
+// Pre-trade checks of our own limits, run against the live book before an
+// order goes out. These sit on top of the exchange filters in
+// `InstrumentSpec::validate`: an order can be perfectly valid for the
+// exchange and still be one we never want to send. Every limit is optional,
+// and the first one an order breaks is returned.
+
+/// What a price band is measured from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reference {
+    /// Halfway between the best bid and best ask
+    Mid,
+    /// Best ask for a buy, best bid for a sell
+    Opposite,
+}
+
+/// Orders may not be priced further than `width` (a fraction, `0.05` is
+/// 5%) from the reference, either way
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PriceBand {
+    pub reference: Reference,
+    pub width: Multiplier,
+}
+
+/// Limits for one symbol, `None` disables a check
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RiskLimits<const P: usize, const Q: usize> {
+    pub max_qty: Option<Fp<Q>>,
+    /// Price times quantity, at the price precision
+    pub max_notional: Option<Fp<P>>,
+    pub band: Option<PriceBand>,
+    /// Most price levels one order may take liquidity from
+    pub max_levels: Option<usize>,
+    pub max_open_orders: Option<usize>,
+    /// Largest absolute net position once the order is fully filled
+    pub max_position: Option<Fp<Q>>,
+}
+
+/// Where we stand in a symbol before the order
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Exposure<const Q: usize> {
+    pub open_orders: usize,
+    /// Net position, negative when short
+    pub position: Fp<Q>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RiskReject {
+    TooManyOpenOrders,
+    QtyAboveMax,
+    PositionAboveMax,
+    /// A limit order without a price
+    MissingPrice,
+    /// The side of the book the check needs is empty
+    NoReferencePrice,
+    PriceOutsideBand,
+    NotionalAboveMax,
+    /// The order would take liquidity from this many levels
+    SweepsTooDeep(usize),
+}
+
+impl fmt::Display for RiskReject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RiskReject::TooManyOpenOrders => f.write_str("too many open orders"),
+            RiskReject::QtyAboveMax => f.write_str("quantity above the order limit"),
+            RiskReject::PositionAboveMax => f.write_str("fill would exceed the position limit"),
+            RiskReject::MissingPrice => f.write_str("limit order without a price"),
+            RiskReject::NoReferencePrice => f.write_str("no reference price in the book"),
+            RiskReject::PriceOutsideBand => f.write_str("price outside the band"),
+            RiskReject::NotionalAboveMax => f.write_str("notional above the order limit"),
+            RiskReject::SweepsTooDeep(levels) => {
+                write!(f, "order would sweep {} price levels", levels)
+            }
+        }
+    }
+}
+
+impl Error for RiskReject {}
+
+impl<const P: usize, const Q: usize> RiskLimits<P, Q> {
+    /// Checks `order` against these limits, the book it would trade into
+    /// and our current `exposure` in the symbol
+    pub fn check<B>(
+        &self,
+        order: &NewOrder<'_, P, Q>,
+        book: &B,
+        exposure: &Exposure<Q>,
+    ) -> Result<(), RiskReject>
+    where
+        B: OrderBook<Price = Fp<P>, Qty = Fp<Q>>,
+    {
+        if self
+            .max_open_orders
+            .is_some_and(|max| exposure.open_orders >= max)
+        {
+            return Err(RiskReject::TooManyOpenOrders);
+        }
+        if self.max_qty.is_some_and(|max| order.qty > max) {
+            return Err(RiskReject::QtyAboveMax);
+        }
+        if let Some(max) = self.max_position {
+            let position = match order.side {
+                Side::Bid => exposure.position + order.qty,
+                Side::Ask => exposure.position - order.qty,
+            };
+            // Orders that shrink a position already over the limit pass
+            if position.abs() > max && position.abs() > exposure.position.abs() {
+                return Err(RiskReject::PositionAboveMax);
+            }
+        }
+
+        let limit = match order.order_type {
+            OrderType::Market => None,
+            OrderType::Limit | OrderType::LimitMaker => {
+                Some(order.price.ok_or(RiskReject::MissingPrice)?)
+            }
+        };
+        let sweep = Sweep::walk(book, &order.side, limit, order.qty);
+
+        if let Some(band) = self.band {
+            // A market order is held to the worst price it would reach
+            let price = limit.or(sweep.worst).ok_or(RiskReject::NoReferencePrice)?;
+            // Doubled so the mid stays exact
+            let reference = match (band.reference, book.top()) {
+                (Reference::Mid, (Some((bid, _)), Some((ask, _)))) => bid.raw() + ask.raw(),
+                (Reference::Opposite, (_, Some((ask, _)))) if order.side == Side::Bid => {
+                    2 * ask.raw()
+                }
+                (Reference::Opposite, (Some((bid, _)), _)) if order.side == Side::Ask => {
+                    2 * bid.raw()
+                }
+                _ => return Err(RiskReject::NoReferencePrice),
+            };
+            let distance = (2 * price.raw() - reference).abs() * Multiplier::ONE.raw();
+            if distance > reference * band.width.raw() {
+                return Err(RiskReject::PriceOutsideBand);
+            }
+        }
+
+        if let Some(max) = self.max_notional {
+            // At P + Q decimals; a market order at the prices it would take
+            let notional = match limit {
+                Some(price) => price.raw().checked_mul(order.qty.raw()),
+                None => sweep.notional,
+            };
+            let scale = 10i128.pow(Q as u32);
+            if notional.is_none_or(|n| n > max.raw() * scale) {
+                return Err(RiskReject::NotionalAboveMax);
+            }
+        }
+
+        if self.max_levels.is_some_and(|max| sweep.levels > max) {
+            return Err(RiskReject::SweepsTooDeep(sweep.levels));
+        }
+
+        Ok(())
+    }
+}
+
+// What an order would take from the opposite side of the book if it
+// crossed right now
+struct Sweep<const P: usize> {
+    levels: usize,
+    worst: Option<Fp<P>>,
+    /// Raw at P + Q decimals, `None` on overflow or when the book runs out
+    /// before the order is filled
+    notional: Option<i128>,
+}
+
+impl<const P: usize> Sweep<P> {
+    fn walk<B, const Q: usize>(book: &B, side: &Side, limit: Option<Fp<P>>, qty: Fp<Q>) -> Self
+    where
+        B: OrderBook<Price = Fp<P>, Qty = Fp<Q>>,
+    {
+        let crosses = |price: Fp<P>| match (side, limit) {
+            (_, None) => true,
+            (Side::Bid, Some(limit)) => price <= limit,
+            (Side::Ask, Some(limit)) => price >= limit,
+        };
+        let mut sweep = Sweep {
+            levels: 0,
+            worst: None,
+            notional: Some(0),
+        };
+        let mut left = qty;
+        let mut take = |(&price, &size): (&Fp<P>, &Fp<Q>)| {
+            if !left.is_positive() || !crosses(price) {
+                return false;
+            }
+            let size = size.min(left);
+            left -= size;
+            sweep.levels += 1;
+            sweep.worst = Some(price);
+            sweep.notional = sweep
+                .notional
+                .and_then(|n| n.checked_add(price.raw().checked_mul(size.raw())?));
+            true
+        };
+        match side {
+            Side::Bid => book.asks().take_while(|&level| take(level)).count(),
+            Side::Ask => book.bids().take_while(|&level| take(level)).count(),
+        };
+        if left.is_positive() && limit.is_none() {
+            sweep.notional = None;
+        }
+        sweep
+    }
+}
+
+/// Limits by symbol, with a default for symbols not listed
+#[derive(Debug, Clone, Default)]
+pub struct RiskChecks<const P: usize, const Q: usize> {
+    pub default: RiskLimits<P, Q>,
+    symbols: HashMap<String, RiskLimits<P, Q>>,
+}
+
+impl<const P: usize, const Q: usize> RiskChecks<P, Q> {
+    pub fn new(default: RiskLimits<P, Q>) -> Self {
+        RiskChecks {
+            default,
+            symbols: HashMap::new(),
+        }
+    }
+
+    pub fn set(&mut self, symbol: &str, limits: RiskLimits<P, Q>) {
+        self.symbols.insert(symbol.to_string(), limits);
+    }
+
+    pub fn limits(&self, symbol: &str) -> &RiskLimits<P, Q> {
+        self.symbols.get(symbol).unwrap_or(&self.default)
+    }
+
+    /// Checks `order` against the limits of its symbol
+    pub fn check<B>(
+        &self,
+        order: &NewOrder<'_, P, Q>,
+        book: &B,
+        exposure: &Exposure<Q>,
+    ) -> Result<(), RiskReject>
+    where
+        B: OrderBook<Price = Fp<P>, Qty = Fp<Q>>,
+    {
+        self.limits(order.symbol).check(order, book, exposure)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fp;
+    use e001::btree::BTreeBook;
+    use e001::hybrid::HybridBook;
+
+    fn book<B: OrderBook<Price = Fp<2>, Qty = Fp<3>>>(mut book: B) -> B {
+        book.insert(Side::Bid, fp!(99.00), fp!(1.000));
+        book.insert(Side::Bid, fp!(98.00), fp!(2.000));
+        book.insert(Side::Ask, fp!(101.00), fp!(1.000));
+        book.insert(Side::Ask, fp!(102.00), fp!(1.000));
+        book.insert(Side::Ask, fp!(103.00), fp!(5.000));
+        book
+    }
+
+    #[test]
+    fn test_limits() {
+        let book = book(HybridBook::new());
+        let none = Exposure::default();
+        let buy = |price: Fp<2>, qty: Fp<3>| NewOrder::limit("BTCUSDT", Side::Bid, price, qty);
+
+        let limits = RiskLimits {
+            max_qty: Some(fp!(3.000)),
+            max_notional: Some(fp!(250.00)),
+            max_open_orders: Some(2),
+            max_position: Some(fp!(4.000)),
+            ..Default::default()
+        };
+        assert_eq!(
+            limits.check(&buy(fp!(100.00), fp!(2.000)), &book, &none),
+            Ok(())
+        );
+        assert_eq!(
+            limits.check(&buy(fp!(50.00), fp!(3.500)), &book, &none),
+            Err(RiskReject::QtyAboveMax)
+        );
+        assert_eq!(
+            limits.check(&buy(fp!(100.00), fp!(2.600)), &book, &none),
+            Err(RiskReject::NotionalAboveMax)
+        );
+
+        let busy = Exposure {
+            open_orders: 2,
+            position: Fp::ZERO,
+        };
+        assert_eq!(
+            limits.check(&buy(fp!(100.00), fp!(1.000)), &book, &busy),
+            Err(RiskReject::TooManyOpenOrders)
+        );
+
+        // Long 3, so only one more can be bought, but selling is fine
+        let long = Exposure {
+            open_orders: 0,
+            position: fp!(3.000),
+        };
+        assert_eq!(
+            limits.check(&buy(fp!(10.00), fp!(1.500)), &book, &long),
+            Err(RiskReject::PositionAboveMax)
+        );
+        let sell = NewOrder::limit("BTCUSDT", Side::Ask, fp!(80.00), fp!(3.000));
+        assert_eq!(limits.check(&sell, &book, &long), Ok(()));
+
+        // Over the limit already, reducing still passes
+        let over = Exposure {
+            open_orders: 0,
+            position: fp!(-6.000),
+        };
+        assert_eq!(
+            limits.check(&buy(fp!(10.00), fp!(1.000)), &book, &over),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_band_and_sweep() {
+        let book = book(BTreeBook::new());
+        let none = Exposure::default();
+        let buy = |price: Fp<2>, qty: Fp<3>| NewOrder::limit("BTCUSDT", Side::Bid, price, qty);
+
+        // 2% around a mid of 100
+        let mut limits = RiskLimits {
+            band: Some(PriceBand {
+                reference: Reference::Mid,
+                width: fp!(0.02000000),
+            }),
+            max_levels: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            limits.check(&buy(fp!(98.00), fp!(1.000)), &book, &none),
+            Ok(())
+        );
+        assert_eq!(
+            limits.check(&buy(fp!(102.00), fp!(2.000)), &book, &none),
+            Ok(())
+        );
+        assert_eq!(
+            limits.check(&buy(fp!(97.99), fp!(1.000)), &book, &none),
+            Err(RiskReject::PriceOutsideBand)
+        );
+
+        // Two levels are filled completely before the third is touched
+        assert_eq!(
+            limits.check(&buy(fp!(102.00), fp!(9.000)), &book, &none),
+            Ok(())
+        );
+        limits.band = None;
+        assert_eq!(
+            limits.check(&buy(fp!(103.00), fp!(2.001)), &book, &none),
+            Err(RiskReject::SweepsTooDeep(3))
+        );
+
+        // A market sell walks the bids and is banded at the worst of them
+        limits.band = Some(PriceBand {
+            reference: Reference::Opposite,
+            width: fp!(0.01000000),
+        });
+        let sell = NewOrder::market("BTCUSDT", Side::Ask, fp!(1.000));
+        assert_eq!(limits.check(&sell, &book, &none), Ok(()));
+        let sell = NewOrder::market("BTCUSDT", Side::Ask, fp!(2.000));
+        assert_eq!(
+            limits.check(&sell, &book, &none),
+            Err(RiskReject::PriceOutsideBand)
+        );
+
+        // Market notional comes from the levels taken
+        let limits = RiskLimits {
+            max_notional: Some(fp!(203.00)),
+            ..Default::default()
+        };
+        let market = |qty: Fp<3>| NewOrder::market("BTCUSDT", Side::Bid, qty);
+        assert_eq!(limits.check(&market(fp!(2.000)), &book, &none), Ok(()));
+        assert_eq!(
+            limits.check(&market(fp!(2.010)), &book, &none),
+            Err(RiskReject::NotionalAboveMax)
+        );
+        // Deeper than the book, so the notional is unknown
+        assert_eq!(
+            limits.check(&market(fp!(8.000)), &book, &none),
+            Err(RiskReject::NotionalAboveMax)
+        );
+
+        let mut maker = buy(fp!(100.00), fp!(1.000));
+        maker.order_type = OrderType::LimitMaker;
+        maker.price = None;
+        assert_eq!(
+            limits.check(&maker, &book, &none),
+            Err(RiskReject::MissingPrice)
+        );
+    }
+
+    #[test]
+    fn test_per_symbol() {
+        let book = book(HybridBook::new());
+        let empty = HybridBook::<Fp<2>, Fp<3>>::new();
+        let mut checks = RiskChecks::new(RiskLimits {
+            max_qty: Some(fp!(1.000)),
+            ..Default::default()
+        });
+        checks.set(
+            "ETHUSDT",
+            RiskLimits {
+                band: Some(PriceBand {
+                    reference: Reference::Mid,
+                    width: fp!(0.10000000),
+                }),
+                ..Default::default()
+            },
+        );
+
+        let btc = NewOrder::limit("BTCUSDT", Side::Bid, fp!(100.00), fp!(2.000));
+        let eth = NewOrder::limit("ETHUSDT", Side::Bid, fp!(100.00), fp!(2.000));
+        let none = Exposure::default();
+        assert_eq!(
+            checks.check(&btc, &book, &none),
+            Err(RiskReject::QtyAboveMax)
+        );
+        assert_eq!(checks.check(&eth, &book, &none), Ok(()));
+        assert_eq!(
+            checks.check(&eth, &empty, &none),
+            Err(RiskReject::NoReferencePrice)
+        );
+        assert_eq!(
+            RiskReject::SweepsTooDeep(4).to_string(),
+            "order would sweep 4 price levels"
+        );
+    }
+}
@@ -0,0 +1,308 @@
+use crate::batch::{Kernel, parse_levels_with};
+use crate::binance::{Depth, Level};
+use crate::fp::Fp;
+
+// Levels handed to the batch parser at a time
+const CHUNK: usize = 32;
+
+// Hand-written scanner for depth snapshots in the layout Binance sends:
+//
+//   {"lastUpdateId":N,"E":N,"T":N,"bids":[["p","q"],..],"asks":[..]}
+//
+// with `E` and `T` optional (spot omits them) and whitespace allowed between
+// tokens. Levels are parsed straight into `Fp` as the input is walked, with
+// no visitor tree and no allocation beyond the level vectors. Anything the
+// scanner does not expect (other keys or key order, escaped strings,
+// numbers in another notation) hands the whole message to serde_json, so
+// the result is always the same as `Depth::from_serde_json`.
+
+impl<const P: usize, const Q: usize> Depth<P, Q> {
+    pub fn from_scan(buf: &[u8]) -> serde_json::Result<Self> {
+        let mut depth = Depth {
+            last_update_id: 0,
+            event_time: None,
+            tx_time: None,
+            bids: Vec::new(),
+            asks: Vec::new(),
+        };
+        depth.scan_from(buf)?;
+        Ok(depth)
+    }
+
+    /// Overwrites `self` with the message in `buf`, reusing the capacity of
+    /// the level vectors, so a long-lived `Depth` decodes without touching
+    /// the allocator once it has grown to the book depth.
+    ///
+    /// The scanner writes into `self` as it goes, so on an error `self` is
+    /// left empty, with update id 0, rather than holding half a message.
+    pub fn scan_from(&mut self, buf: &[u8]) -> serde_json::Result<()> {
+        if scan(buf, self).is_none() {
+            match serde_json::from_slice(buf) {
+                Ok(depth) => *self = depth,
+                Err(err) => {
+                    self.last_update_id = 0;
+                    self.event_time = None;
+                    self.tx_time = None;
+                    self.bids.clear();
+                    self.asks.clear();
+                    return Err(err);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// On `None` the contents of `depth` are unspecified and the caller falls back
+fn scan<const P: usize, const Q: usize>(buf: &[u8], depth: &mut Depth<P, Q>) -> Option<()> {
+    let mut c = Cursor { buf, pos: 0 };
+    let kernel = Kernel::detect();
+
+    c.byte(b'{')?;
+    c.key(b"lastUpdateId")?;
+    depth.last_update_id = c.uint()?;
+    c.byte(b',')?;
+
+    let mut key = c.string()?;
+    depth.event_time = None;
+    if key == b"E" {
+        c.byte(b':')?;
+        depth.event_time = Some(c.uint()?);
+        c.byte(b',')?;
+        key = c.string()?;
+    }
+    depth.tx_time = None;
+    if key == b"T" {
+        c.byte(b':')?;
+        depth.tx_time = Some(c.uint()?);
+        c.byte(b',')?;
+        key = c.string()?;
+    }
+
+    if key != b"bids" {
+        return None;
+    }
+    c.byte(b':')?;
+    c.levels(kernel, &mut depth.bids)?;
+    c.byte(b',')?;
+    c.key(b"asks")?;
+    c.levels(kernel, &mut depth.asks)?;
+    c.byte(b'}')?;
+
+    c.skip_ws();
+    (c.pos == buf.len()).then_some(())
+}
+
+struct Cursor<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    #[inline]
+    fn skip_ws(&mut self) {
+        while let Some(b' ' | b'\n' | b'\r' | b'\t') = self.buf.get(self.pos) {
+            self.pos += 1;
+        }
+    }
+
+    // Next non-whitespace byte, without consuming it
+    #[inline]
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_ws();
+        self.buf.get(self.pos).copied()
+    }
+
+    #[inline]
+    fn byte(&mut self, b: u8) -> Option<()> {
+        if self.peek()? != b {
+            return None;
+        }
+        self.pos += 1;
+        Some(())
+    }
+
+    // Contents of a string without escapes
+    #[inline]
+    fn string(&mut self) -> Option<&'a [u8]> {
+        self.byte(b'"')?;
+        let start = self.pos;
+        let len = self.buf[start..].iter().position(|&b| b == b'"')?;
+        let s = &self.buf[start..start + len];
+        if s.contains(&b'\\') {
+            return None;
+        }
+        self.pos = start + len + 1;
+        Some(s)
+    }
+
+    #[inline]
+    fn key(&mut self, name: &[u8]) -> Option<()> {
+        if self.string()? != name {
+            return None;
+        }
+        self.byte(b':')
+    }
+
+    // Plain unsigned integer; fractions, exponents and overflow fall back
+    #[inline]
+    fn uint(&mut self) -> Option<u64> {
+        self.skip_ws();
+        let start = self.pos;
+        let mut v: u64 = 0;
+        while let Some(&b) = self.buf.get(self.pos) {
+            if !b.is_ascii_digit() {
+                break;
+            }
+            v = v.checked_mul(10)?.checked_add((b - b'0') as u64)?;
+            self.pos += 1;
+        }
+        match self.buf.get(self.pos) {
+            _ if self.pos == start => None,
+            Some(b'.' | b'e' | b'E') => None,
+            _ => Some(v),
+        }
+    }
+
+    // Level strings are gathered on the stack and parsed a chunk at a time
+    // with the batch kernels, which is where most of the time goes
+    fn levels<const P: usize, const Q: usize>(
+        &mut self,
+        kernel: Kernel,
+        out: &mut Vec<Level<P, Q>>,
+    ) -> Option<()> {
+        let mut chunk: [(&[u8], &[u8]); CHUNK] = [(&[], &[]); CHUNK];
+        let mut n = 0;
+        out.clear();
+
+        self.byte(b'[')?;
+        let mut more = self.peek()? != b']';
+        if !more {
+            self.pos += 1;
+        }
+        while more {
+            self.byte(b'[')?;
+            let price = self.string()?;
+            self.byte(b',')?;
+            let qty = self.string()?;
+            self.byte(b']')?;
+            if !exact(price, P) || !exact(qty, Q) {
+                return None;
+            }
+            chunk[n] = (price, qty);
+            n += 1;
+
+            match self.peek()? {
+                b',' => self.pos += 1,
+                b']' => {
+                    self.pos += 1;
+                    more = false;
+                }
+                _ => return None,
+            }
+            if n == CHUNK || !more {
+                let start = out.len();
+                out.resize(start + n, (Fp::ZERO, Fp::ZERO));
+                parse_levels_with(kernel, &chunk[..n], &mut out[start..]).ok()?;
+                n = 0;
+            }
+        }
+        Some(())
+    }
+}
+
+// Whether `s` ends in exactly `digits` fraction digits after its point.
+// The kernels stop reading there, so anything longer (extra digits, an
+// exponent) has to go through `Fp::parse` to come out the same. What is
+// left before the point and in the fraction the kernels check themselves.
+#[inline]
+fn exact(s: &[u8], digits: usize) -> bool {
+    s.len() > digits && s[s.len() - digits - 1] == b'.'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FUTURES_DEPTH: &[u8] = br#"{"lastUpdateId":7488596254027,"E":1746977566204,"T":1746977566198,"bids":[["104276.90","10.023"],["104276.80","0.032"]],"asks":[["104277.00","15.341"]]}"#;
+
+    // Scans without falling back and checks against serde_json
+    fn check<const P: usize, const Q: usize>(buf: &[u8]) {
+        let mut depth = Depth::<P, Q>::from_serde_json(buf).unwrap();
+        depth.bids.clear();
+        depth.asks.clear();
+        assert!(
+            scan(buf, &mut depth).is_some(),
+            "{}",
+            String::from_utf8_lossy(buf)
+        );
+        assert_eq!(depth, Depth::from_serde_json(buf).unwrap());
+    }
+
+    #[test]
+    fn test_scan() {
+        check::<2, 3>(FUTURES_DEPTH);
+        check::<8, 8>(
+            br#"{"lastUpdateId":1027024,"bids":[["4.00000000","431.00000000"]],"asks":[]}"#,
+        );
+        check::<2, 3>(br#"{"lastUpdateId":1,"T":2,"bids":[],"asks":[["1.00","-2.000"]]}"#);
+        check::<2, 3>(
+            b" {\n  \"lastUpdateId\" : 5 ,\n  \"bids\" : [ [ \"1.00\" , \"2.000\" ] , [\"0.50\",\"1.000\"] ],\n  \"asks\" : [ ]\n}\n",
+        );
+    }
+
+    #[test]
+    fn test_scan_fallback() {
+        // Each of these is valid for serde but not for the scanner
+        for buf in [
+            &br#"{"bids":[["1.00","2.000"]],"lastUpdateId":1,"asks":[]}"#[..],
+            br#"{"lastUpdateId":1,"bids":[["1.00","2.000"]],"asks":[],"x":0}"#,
+            br#"{"lastUpdateId":1,"T":2,"E":3,"bids":[["1.00","2.000"]],"asks":[]}"#,
+            br#"{"lastUpdateId":1,"bids":[["1e2","2.000"]],"asks":[]}"#,
+            br#"{"lastUpdateId":1,"bids":[["1.23e1","1.000"]],"asks":[]}"#,
+            br#"{"lastUpdateId":1,"bids":[["1.23E1","1.000"]],"asks":[]}"#,
+            br#"{"lastUpdateId":1,"bids":[["1.00","2.0005"]],"asks":[]}"#,
+        ] {
+            let expected = Depth::<2, 3>::from_serde_json(buf).unwrap();
+            let mut depth = Depth::from_serde_json(FUTURES_DEPTH).unwrap();
+            assert!(scan(buf, &mut depth).is_none());
+            depth.scan_from(buf).unwrap();
+            assert_eq!(depth, expected);
+        }
+
+        // Errors come from the fallback
+        for buf in [
+            &br#"{"lastUpdateId":1,"bids":[["1.0","2.000"]],"asks":[]}"#[..],
+            br#"{"lastUpdateId":1,"bids":[],"asks":[]"#,
+            br#"{"lastUpdateId":1,"bids":[],"asks":[]} x"#,
+            br#"{"lastUpdateId":1.5,"bids":[],"asks":[]}"#,
+            b"",
+        ] {
+            assert!(Depth::<2, 3>::from_scan(buf).is_err());
+        }
+    }
+
+    #[test]
+    fn test_scan_reuse() {
+        let mut depth = Depth::<2, 3>::from_scan(FUTURES_DEPTH).unwrap();
+        let (bids, asks) = (depth.bids.as_ptr(), depth.asks.as_ptr());
+
+        depth
+            .scan_from(br#"{"lastUpdateId":9,"bids":[["3.00","1.000"]],"asks":[]}"#)
+            .unwrap();
+        assert_eq!(depth.last_update_id, 9);
+        assert_eq!((depth.event_time, depth.tx_time), (None, None));
+        assert_eq!(depth.bids, vec![(Fp::from_int(3), Fp::ONE)]);
+        assert!(depth.asks.is_empty());
+        assert_eq!((depth.bids.as_ptr(), depth.asks.as_ptr()), (bids, asks));
+
+        // Failing after the bids leaves nothing of them behind
+        assert!(
+            depth
+                .scan_from(br#"{"lastUpdateId":10,"bids":[["4.00","1.000"]],"asks":[["x"]]}"#)
+                .is_err()
+        );
+        assert_eq!(depth.last_update_id, 0);
+        assert!(depth.bids.is_empty() && depth.asks.is_empty());
+    }
+}
@@ -0,0 +1,646 @@
+use std::collections::VecDeque;
+use std::fmt::Write;
+
+use crate::binance::{AggTrade, Depth, DepthUpdate, Level, Trade};
+use crate::fees::Liquidity;
+use crate::fp::Fp;
+use crate::order::{
+    AmendOrder, CancelOrder, Credentials, NewOrder, OrderRef, OrderType, Request, TimeInForce,
+    timestamp_ms,
+};
+use e001::orderbook::{OrderBook, Side};
+
+// The event-driven core. An `EventLoop` takes market data in time order,
+// keeps the book up to date and calls the `Strategy`, which answers with
+// order intents through its `Context`. Intents go to an `Executor`, which
+// either sends them to the exchange or simulates them, and hands fills back
+// to the strategy. The loop only sees `MarketEvent`s, so a strategy runs
+// the same against a recorded replay as against the live feed.
+//
+// Times are exchange milliseconds, taken from the events themselves.
+
+/// Levels to apply to one book, zero quantities delete
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookUpdate<const P: usize, const Q: usize> {
+    pub time: u64,
+    /// Replaces the book rather than updating it
+    pub snapshot: bool,
+    pub first_update_id: u64,
+    pub update_id: u64,
+    pub prev_update_id: Option<u64>,
+    pub bids: Vec<Level<P, Q>>,
+    pub asks: Vec<Level<P, Q>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TradeTick<const P: usize, const Q: usize> {
+    pub time: u64,
+    pub price: Fp<P>,
+    pub qty: Fp<Q>,
+    /// The side that took liquidity
+    pub aggressor: Side,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MarketEvent<const P: usize, const Q: usize> {
+    Book(BookUpdate<P, Q>),
+    Trade(TradeTick<P, Q>),
+}
+
+impl<const P: usize, const Q: usize> MarketEvent<P, Q> {
+    pub fn time(&self) -> u64 {
+        match self {
+            MarketEvent::Book(update) => update.time,
+            MarketEvent::Trade(trade) => trade.time,
+        }
+    }
+}
+
+impl<const P: usize, const Q: usize> BookUpdate<P, Q> {
+    /// Applies the levels to `book`, clearing it first for a snapshot
+    pub fn apply_to<B>(&self, book: &mut B)
+    where
+        B: OrderBook<Price = Fp<P>, Qty = Fp<Q>>,
+    {
+        if self.snapshot {
+            let bids: Vec<_> = book.bids().map(|(p, _)| *p).collect();
+            let asks: Vec<_> = book.asks().map(|(p, _)| *p).collect();
+            bids.into_iter().for_each(|p| book.delete(Side::Bid, p));
+            asks.into_iter().for_each(|p| book.delete(Side::Ask, p));
+        }
+        for (side, levels) in [(Side::Bid, &self.bids), (Side::Ask, &self.asks)] {
+            for &(price, qty) in levels {
+                match qty.is_zero() {
+                    true => book.delete(side.clone(), price),
+                    false => book.insert(side.clone(), price, qty),
+                }
+            }
+        }
+    }
+}
+
+impl<const P: usize, const Q: usize> From<Depth<P, Q>> for BookUpdate<P, Q> {
+    fn from(depth: Depth<P, Q>) -> Self {
+        BookUpdate {
+            time: depth.event_time.unwrap_or_default(),
+            snapshot: true,
+            first_update_id: depth.last_update_id,
+            update_id: depth.last_update_id,
+            prev_update_id: None,
+            bids: depth.bids,
+            asks: depth.asks,
+        }
+    }
+}
+
+impl<const P: usize, const Q: usize> From<DepthUpdate<'_, P, Q>> for BookUpdate<P, Q> {
+    fn from(update: DepthUpdate<'_, P, Q>) -> Self {
+        BookUpdate {
+            time: update.event_time,
+            snapshot: false,
+            first_update_id: update.first_update_id,
+            update_id: update.final_update_id,
+            prev_update_id: update.prev_final_update_id,
+            bids: update.bids,
+            asks: update.asks,
+        }
+    }
+}
+
+impl<const P: usize, const Q: usize> From<Trade<'_, P, Q>> for TradeTick<P, Q> {
+    fn from(trade: Trade<'_, P, Q>) -> Self {
+        TradeTick {
+            time: trade.trade_time,
+            aggressor: trade.aggressor(),
+            price: trade.price,
+            qty: trade.qty,
+        }
+    }
+}
+
+impl<const P: usize, const Q: usize> From<AggTrade<'_, P, Q>> for TradeTick<P, Q> {
+    fn from(trade: AggTrade<'_, P, Q>) -> Self {
+        TradeTick {
+            time: trade.trade_time,
+            aggressor: trade.aggressor(),
+            price: trade.price,
+            qty: trade.qty,
+        }
+    }
+}
+
+impl<const P: usize, const Q: usize> From<BookUpdate<P, Q>> for MarketEvent<P, Q> {
+    fn from(update: BookUpdate<P, Q>) -> Self {
+        MarketEvent::Book(update)
+    }
+}
+
+impl<const P: usize, const Q: usize> From<TradeTick<P, Q>> for MarketEvent<P, Q> {
+    fn from(trade: TradeTick<P, Q>) -> Self {
+        MarketEvent::Trade(trade)
+    }
+}
+
+/// Our id for an order, unique within a loop
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OrderId(pub u64);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderIntent<const P: usize, const Q: usize> {
+    pub id: OrderId,
+    pub side: Side,
+    pub order_type: OrderType,
+    pub time_in_force: Option<TimeInForce>,
+    pub price: Option<Fp<P>>,
+    pub qty: Fp<Q>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Intent<const P: usize, const Q: usize> {
+    Place(OrderIntent<P, Q>),
+    Cancel(OrderId),
+    /// Reduce the quantity left on an order
+    Amend(OrderId, Fp<Q>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderFill<const P: usize, const Q: usize> {
+    pub id: OrderId,
+    pub time: u64,
+    pub side: Side,
+    pub price: Fp<P>,
+    pub qty: Fp<Q>,
+    pub liquidity: Liquidity,
+    /// Nothing is left of the order
+    pub done: bool,
+}
+
+/// What a strategy can do from its callbacks
+#[derive(Debug, Default)]
+pub struct Context<const P: usize, const Q: usize> {
+    now: u64,
+    next_id: u64,
+    timer: Option<u64>,
+    intents: Vec<Intent<P, Q>>,
+}
+
+impl<const P: usize, const Q: usize> Context<P, Q> {
+    /// Time of the event being handled
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    pub fn place(
+        &mut self,
+        side: Side,
+        order_type: OrderType,
+        time_in_force: Option<TimeInForce>,
+        price: Option<Fp<P>>,
+        qty: Fp<Q>,
+    ) -> OrderId {
+        self.next_id += 1;
+        let id = OrderId(self.next_id);
+        self.intents.push(Intent::Place(OrderIntent {
+            id,
+            side,
+            order_type,
+            time_in_force,
+            price,
+            qty,
+        }));
+        id
+    }
+
+    /// Good-till-cancel limit order
+    pub fn limit(&mut self, side: Side, price: Fp<P>, qty: Fp<Q>) -> OrderId {
+        let tif = Some(TimeInForce::Gtc);
+        self.place(side, OrderType::Limit, tif, Some(price), qty)
+    }
+
+    pub fn market(&mut self, side: Side, qty: Fp<Q>) -> OrderId {
+        self.place(side, OrderType::Market, None, None, qty)
+    }
+
+    pub fn cancel(&mut self, id: OrderId) {
+        self.intents.push(Intent::Cancel(id));
+    }
+
+    pub fn amend(&mut self, id: OrderId, qty: Fp<Q>) {
+        self.intents.push(Intent::Amend(id, qty));
+    }
+
+    /// Calls `on_timer` once `at` is reached, replacing any earlier timer.
+    /// Timers always fire later than now: one for now or earlier fires a
+    /// millisecond from now.
+    pub fn set_timer(&mut self, at: u64) {
+        self.timer = Some(at.max(self.now + 1));
+    }
+}
+
+pub trait Strategy<const P: usize, const Q: usize> {
+    /// After each update has been applied to the book
+    fn on_book_update(
+        &mut self,
+        book: &impl OrderBook<Price = Fp<P>, Qty = Fp<Q>>,
+        ctx: &mut Context<P, Q>,
+    );
+
+    fn on_trade(&mut self, _trade: &TradeTick<P, Q>, _ctx: &mut Context<P, Q>) {}
+
+    fn on_fill(&mut self, _fill: &OrderFill<P, Q>, _ctx: &mut Context<P, Q>) {}
+
+    fn on_timer(&mut self, _ctx: &mut Context<P, Q>) {}
+}
+
+/// Where intents go: the exchange, or a simulation of it
+pub trait Executor<const P: usize, const Q: usize> {
+    fn submit(&mut self, now: u64, intent: Intent<P, Q>);
+
+    /// Moves the fills that happened by `now` into `fills`
+    fn poll(&mut self, now: u64, fills: &mut Vec<OrderFill<P, Q>>);
+
+    /// Sees the book after each update, before the strategy does
+    fn on_book(&mut self, _now: u64, _book: &impl OrderBook<Price = Fp<P>, Qty = Fp<Q>>) {}
+
+    fn on_trade(&mut self, _trade: &TradeTick<P, Q>) {}
+}
+
+pub struct EventLoop<B, S, E, const P: usize, const Q: usize> {
+    pub book: B,
+    pub strategy: S,
+    pub executor: E,
+    ctx: Context<P, Q>,
+    fills: Vec<OrderFill<P, Q>>,
+}
+
+impl<B, S, E, const P: usize, const Q: usize> EventLoop<B, S, E, P, Q>
+where
+    B: OrderBook<Price = Fp<P>, Qty = Fp<Q>>,
+    S: Strategy<P, Q>,
+    E: Executor<P, Q>,
+{
+    pub fn new(book: B, strategy: S, executor: E) -> Self {
+        EventLoop {
+            book,
+            strategy,
+            executor,
+            ctx: Context::default(),
+            fills: Vec::new(),
+        }
+    }
+
+    pub fn now(&self) -> u64 {
+        self.ctx.now
+    }
+
+    /// Feeds every event, in order
+    pub fn run(&mut self, events: impl IntoIterator<Item = MarketEvent<P, Q>>) {
+        for event in events {
+            self.handle(event);
+        }
+    }
+
+    /// Handles one event, after the timers and fills due before it
+    pub fn handle(&mut self, event: MarketEvent<P, Q>) {
+        self.advance(event.time());
+
+        match event {
+            MarketEvent::Book(update) => {
+                update.apply_to(&mut self.book);
+                self.executor.on_book(self.ctx.now, &self.book);
+                self.strategy.on_book_update(&self.book, &mut self.ctx);
+            }
+            MarketEvent::Trade(trade) => {
+                self.executor.on_trade(&trade);
+                self.strategy.on_trade(&trade, &mut self.ctx);
+            }
+        }
+        self.flush();
+    }
+
+    /// Moves the clock to `now`, firing the timer and delivering fills
+    /// due by then. Time never goes backwards.
+    pub fn advance(&mut self, now: u64) {
+        while let Some(at) = self.ctx.timer.filter(|&at| at <= now) {
+            self.ctx.timer = None;
+            self.deliver(at.max(self.ctx.now));
+            // A fill may have set a new timer in place of this one
+            if self.ctx.timer.is_some() {
+                continue;
+            }
+            self.strategy.on_timer(&mut self.ctx);
+            self.flush();
+        }
+        self.deliver(now.max(self.ctx.now));
+    }
+
+    fn deliver(&mut self, now: u64) {
+        self.executor.poll(now, &mut self.fills);
+        for fill in self.fills.drain(..) {
+            // The strategy hears of each fill at the time it happened
+            self.ctx.now = fill.time.clamp(self.ctx.now, now);
+            self.strategy.on_fill(&fill, &mut self.ctx);
+            for intent in self.ctx.intents.drain(..) {
+                self.executor.submit(self.ctx.now, intent);
+            }
+        }
+        self.ctx.now = now;
+    }
+
+    fn flush(&mut self) {
+        for intent in self.ctx.intents.drain(..) {
+            self.executor.submit(self.ctx.now, intent);
+        }
+    }
+}
+
+/// Signed REST request for the live executor to send
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedRequest {
+    pub method: &'static str,
+    pub path: &'static str,
+    /// Goes in the `X-MBX-APIKEY` header
+    pub api_key: String,
+    pub query: String,
+}
+
+/// Executor for a live account. Intents become signed REST requests handed
+/// to `send`; fills come back from the user data stream through `fill`.
+/// Client order ids are `prefix` followed by the `OrderId`.
+pub struct SignedExecutor<F, const P: usize, const Q: usize> {
+    pub symbol: String,
+    pub prefix: String,
+    pub credentials: Credentials,
+    pub recv_window: Option<u64>,
+    send: F,
+    fills: VecDeque<OrderFill<P, Q>>,
+}
+
+impl<F, const P: usize, const Q: usize> SignedExecutor<F, P, Q>
+where
+    F: FnMut(SignedRequest),
+{
+    pub fn new(symbol: &str, prefix: &str, credentials: Credentials, send: F) -> Self {
+        SignedExecutor {
+            symbol: symbol.to_string(),
+            prefix: prefix.to_string(),
+            credentials,
+            recv_window: None,
+            send,
+            fills: VecDeque::new(),
+        }
+    }
+
+    /// Queues a fill reported by the exchange
+    pub fn fill(&mut self, fill: OrderFill<P, Q>) {
+        self.fills.push_back(fill);
+    }
+
+    fn send<R: Request>(&mut self, request: &R) {
+        let query = self
+            .credentials
+            .rest_query(request, timestamp_ms(), self.recv_window);
+        (self.send)(SignedRequest {
+            method: R::REST.0,
+            path: R::REST.1,
+            api_key: self.credentials.api_key.clone(),
+            query,
+        });
+    }
+}
+
+impl<F, const P: usize, const Q: usize> Executor<P, Q> for SignedExecutor<F, P, Q>
+where
+    F: FnMut(SignedRequest),
+{
+    fn submit(&mut self, _now: u64, intent: Intent<P, Q>) {
+        let mut client_id = self.prefix.clone();
+        let id = match &intent {
+            Intent::Place(order) => order.id,
+            Intent::Cancel(id) | Intent::Amend(id, _) => *id,
+        };
+        write!(client_id, "{}", id.0).unwrap();
+        let symbol = self.symbol.clone();
+
+        match intent {
+            Intent::Place(order) => self.send(&NewOrder {
+                symbol: &symbol,
+                side: order.side,
+                order_type: order.order_type,
+                time_in_force: order.time_in_force,
+                price: order.price,
+                qty: order.qty,
+                client_order_id: Some(&client_id),
+                response: None,
+            }),
+            Intent::Cancel(_) => self.send(&CancelOrder {
+                symbol: &symbol,
+                order: OrderRef::ClientId(&client_id),
+            }),
+            Intent::Amend(_, qty) => self.send(&AmendOrder {
+                symbol: &symbol,
+                order: OrderRef::ClientId(&client_id),
+                new_qty: qty,
+                new_client_order_id: None,
+            }),
+        }
+    }
+
+    fn poll(&mut self, now: u64, fills: &mut Vec<OrderFill<P, Q>>) {
+        while self.fills.front().is_some_and(|fill| fill.time <= now) {
+            fills.extend(self.fills.pop_front());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fp;
+    use crate::order::Signer;
+    use e001::hybrid::HybridBook;
+
+    type Book = HybridBook<Fp<2>, Fp<3>>;
+
+    const SNAPSHOT: &[u8] = br#"{"lastUpdateId":100,"E":1000,"bids":[["99.00","1.000"],["98.00","2.000"]],"asks":[["101.00","1.000"]]}"#;
+    const UPDATE: &[u8] = br#"{"e":"depthUpdate","E":1010,"s":"BTCUSDT","U":101,"u":102,"b":[["99.00","0.000"],["99.50","3.000"]],"a":[]}"#;
+    const TRADE: &[u8] = br#"{"e":"trade","E":1021,"s":"BTCUSDT","t":1,"p":"101.00","q":"0.500","T":1020,"m":false}"#;
+
+    fn replay() -> Vec<MarketEvent<2, 3>> {
+        let snapshot = Depth::<2, 3>::from_serde_json(SNAPSHOT).unwrap();
+        let update = DepthUpdate::<2, 3>::from_serde_json(UPDATE).unwrap();
+        let trade = Trade::<2, 3>::from_serde_json(TRADE).unwrap();
+        vec![
+            BookUpdate::from(snapshot).into(),
+            BookUpdate::from(update).into(),
+            TradeTick::from(trade).into(),
+        ]
+    }
+
+    // Joins the best bid, and cancels on the timer
+    #[derive(Default)]
+    struct Joiner {
+        calls: Vec<String>,
+        resting: Option<OrderId>,
+    }
+
+    impl Strategy<2, 3> for Joiner {
+        fn on_book_update(
+            &mut self,
+            book: &impl OrderBook<Price = Fp<2>, Qty = Fp<3>>,
+            ctx: &mut Context<2, 3>,
+        ) {
+            let bid = *book.top().0.unwrap().0;
+            self.calls.push(format!("book {} {}", ctx.now(), bid));
+            if self.resting.is_none() {
+                self.resting = Some(ctx.limit(Side::Bid, bid, fp!(0.100)));
+                ctx.set_timer(ctx.now() + 15);
+            }
+        }
+
+        fn on_trade(&mut self, trade: &TradeTick<2, 3>, ctx: &mut Context<2, 3>) {
+            self.calls
+                .push(format!("trade {} {:?}", ctx.now(), trade.aggressor));
+        }
+
+        fn on_fill(&mut self, fill: &OrderFill<2, 3>, ctx: &mut Context<2, 3>) {
+            self.calls.push(format!("fill {} {}", ctx.now(), fill.qty));
+        }
+
+        fn on_timer(&mut self, ctx: &mut Context<2, 3>) {
+            self.calls.push(format!("timer {}", ctx.now()));
+            ctx.cancel(self.resting.take().unwrap());
+        }
+    }
+
+    // Records intents and fills everything placed 5ms later
+    #[derive(Default)]
+    struct Recorder {
+        intents: Vec<(u64, Intent<2, 3>)>,
+        pending: Vec<OrderFill<2, 3>>,
+        books: usize,
+    }
+
+    impl Executor<2, 3> for Recorder {
+        fn submit(&mut self, now: u64, intent: Intent<2, 3>) {
+            if let Intent::Place(order) = &intent {
+                self.pending.push(OrderFill {
+                    id: order.id,
+                    time: now + 5,
+                    side: order.side.clone(),
+                    price: order.price.unwrap(),
+                    qty: order.qty,
+                    liquidity: Liquidity::Maker,
+                    done: true,
+                });
+            }
+            self.intents.push((now, intent));
+        }
+
+        fn poll(&mut self, now: u64, fills: &mut Vec<OrderFill<2, 3>>) {
+            let (due, later) = self.pending.drain(..).partition(|f| f.time <= now);
+            self.pending = later;
+            fills.extend::<Vec<_>>(due);
+        }
+
+        fn on_book(&mut self, _now: u64, _book: &impl OrderBook<Price = Fp<2>, Qty = Fp<3>>) {
+            self.books += 1;
+        }
+    }
+
+    #[test]
+    fn test_event_loop() {
+        let mut ev = EventLoop::new(Book::new(), Joiner::default(), Recorder::default());
+        ev.run(replay());
+
+        assert_eq!(
+            ev.strategy.calls,
+            [
+                "book 1000 99.00",
+                "fill 1005 0.100",
+                "book 1010 99.50",
+                "timer 1015",
+                "trade 1020 Bid",
+            ]
+        );
+        assert_eq!(ev.executor.books, 2);
+        assert_eq!(ev.executor.intents.len(), 2);
+        assert_eq!(ev.executor.intents[1], (1015, Intent::Cancel(OrderId(1))));
+        assert_eq!(
+            ev.book
+                .bids()
+                .map(|(p, _)| p.to_string())
+                .collect::<Vec<_>>(),
+            ["99.50", "98.00"]
+        );
+
+        // A new snapshot replaces the book
+        ev.handle(replay().swap_remove(0));
+        assert_eq!(ev.book.bids().count(), 2);
+        assert_eq!(ev.now(), 1020);
+    }
+
+    // Re-arms its timer from fills and from the timer itself
+    #[derive(Default)]
+    struct Rearm {
+        timers: Vec<u64>,
+    }
+
+    impl Strategy<2, 3> for Rearm {
+        fn on_book_update(
+            &mut self,
+            book: &impl OrderBook<Price = Fp<2>, Qty = Fp<3>>,
+            ctx: &mut Context<2, 3>,
+        ) {
+            if ctx.now() == 1000 {
+                ctx.limit(Side::Bid, *book.top().0.unwrap().0, fp!(0.100));
+                ctx.set_timer(1005);
+            }
+        }
+
+        fn on_fill(&mut self, _fill: &OrderFill<2, 3>, ctx: &mut Context<2, 3>) {
+            ctx.set_timer(1050);
+        }
+
+        fn on_timer(&mut self, ctx: &mut Context<2, 3>) {
+            self.timers.push(ctx.now());
+            if self.timers.len() == 1 {
+                ctx.set_timer(ctx.now());
+            }
+        }
+    }
+
+    #[test]
+    fn test_timers() {
+        let mut ev = EventLoop::new(Book::new(), Rearm::default(), Recorder::default());
+        ev.handle(replay().swap_remove(0));
+        // The fill at 1005 replaces the timer due then
+        ev.advance(2000);
+        assert_eq!(ev.strategy.timers, [1050, 1051]);
+        assert_eq!(ev.now(), 2000);
+    }
+
+    #[test]
+    fn test_signed_executor() {
+        let mut sent = Vec::new();
+        let creds = Credentials::new("key", Signer::hmac(b"secret"));
+        let executor = SignedExecutor::new("BTCUSDT", "j-", creds, |r| sent.push(r));
+        let mut ev = EventLoop::new(Book::new(), Joiner::default(), executor);
+        ev.run(replay());
+        drop(ev);
+
+        assert_eq!(sent.len(), 2);
+        assert_eq!((sent[0].method, sent[0].path), ("POST", "/api/v3/order"));
+        assert!(sent[0].query.starts_with(
+            "symbol=BTCUSDT&side=BUY&type=LIMIT&timeInForce=GTC&quantity=0.100&price=99.00\
+             &newClientOrderId=j-1&timestamp="
+        ));
+        assert_eq!(sent[1].method, "DELETE");
+        assert!(
+            sent[1]
+                .query
+                .starts_with("symbol=BTCUSDT&origClientOrderId=j-1&")
+        );
+        assert_eq!(sent[1].api_key, "key");
+    }
+}
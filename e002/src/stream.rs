@@ -0,0 +1,564 @@
+use std::{error::Error, fmt};
+
+use crate::apply::DepthHeader;
+use crate::fp::{Fp, ParseFpError};
+use e001::orderbook::Side;
+
+// Resumable parser for depth messages arriving in pieces, as they do from a
+// TCP or WebSocket read loop. Chunks are fed as they come and levels are
+// emitted the moment their closing bracket is seen, so nothing waits for the
+// end of the frame and nothing is scanned twice. Tokens that fit in the chunk
+// are read in place; only a token cut by a chunk boundary is carried over, in
+// a fixed scratch buffer. The input may hold any number of messages back to
+// back, with whitespace between them.
+//
+// Messages are snapshots or diff updates, with the keys `ApplyDepth` knows.
+// Unknown keys are skipped whatever their value; nested values are only
+// checked for balanced brackets.
+
+/// Longest token carried across chunks. Only prices, quantities, ids and the
+/// symbol need to fit; longer strings in skipped fields are fine.
+const SCRATCH: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DepthEvent<'a, const P: usize, const Q: usize> {
+    Level(Side, Fp<P>, Fp<Q>),
+    /// The message is complete. `update_id` is there for snapshots and diff
+    /// updates alike.
+    End(DepthHeader<'a>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamError {
+    /// Where the offending token starts, counted from the first byte fed
+    pub offset: u64,
+    pub kind: StreamErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamErrorKind {
+    Syntax,
+    Price(ParseFpError),
+    Qty(ParseFpError),
+    Integer,
+    TooLong,
+    MissingUpdateId,
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "byte {}: ", self.offset)?;
+        match &self.kind {
+            StreamErrorKind::Syntax => f.write_str("unexpected token"),
+            StreamErrorKind::Price(e) => write!(f, "price: {}", e),
+            StreamErrorKind::Qty(e) => write!(f, "quantity: {}", e),
+            StreamErrorKind::Integer => f.write_str("invalid integer"),
+            StreamErrorKind::TooLong => f.write_str("token too long"),
+            StreamErrorKind::MissingUpdateId => f.write_str("missing update id"),
+        }
+    }
+}
+
+impl Error for StreamError {}
+
+/// Parser state between chunks. After an error the position within the
+/// message is lost: `reset` it and feed from the next message boundary.
+pub struct DepthStream<const P: usize, const Q: usize> {
+    lex: Lex,
+    scratch: heapless::Vec<u8, SCRATCH>,
+    // A token overran the scratch buffer and lost its tail
+    truncated: bool,
+    parser: Parser<P, Q>,
+    // Bytes fed so far, and where the carried token started
+    offset: u64,
+    start: u64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Lex {
+    Between,
+    Str { escape: bool },
+    Bare,
+}
+
+enum Token<'t> {
+    Punct(u8),
+    Str(&'t [u8]),
+    Bare(&'t [u8]),
+    // A string or bare value that did not fit in the scratch buffer
+    TooLong,
+}
+
+impl<const P: usize, const Q: usize> Default for DepthStream<P, Q> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const P: usize, const Q: usize> DepthStream<P, Q> {
+    pub fn new() -> Self {
+        DepthStream {
+            lex: Lex::Between,
+            scratch: heapless::Vec::new(),
+            truncated: false,
+            parser: Parser::new(),
+            offset: 0,
+            start: 0,
+        }
+    }
+
+    /// Drops any half-received message
+    pub fn reset(&mut self) {
+        self.lex = Lex::Between;
+        self.scratch.clear();
+        self.truncated = false;
+        self.parser = Parser::new();
+    }
+
+    /// True between messages, when a chunk boundary cuts nothing
+    pub fn is_idle(&self) -> bool {
+        self.lex == Lex::Between && self.parser.state == State::Start
+    }
+
+    /// Parses the next piece of the stream, calling `emit` for every level
+    /// and every completed message in it. On an error the rest of the chunk
+    /// is dropped, though still counted in later offsets.
+    pub fn feed<F>(&mut self, chunk: &[u8], emit: F) -> Result<(), StreamError>
+    where
+        F: FnMut(DepthEvent<'_, P, Q>),
+    {
+        let result = self.lex(chunk, emit);
+        self.offset += chunk.len() as u64;
+        result
+    }
+
+    fn lex<F>(&mut self, chunk: &[u8], mut emit: F) -> Result<(), StreamError>
+    where
+        F: FnMut(DepthEvent<'_, P, Q>),
+    {
+        let mut i = 0;
+        while i < chunk.len() {
+            let at = self.offset + i as u64;
+            let fail = |kind| StreamError { offset: at, kind };
+            let carried = self.start;
+            let fail_carried = |kind| StreamError {
+                offset: carried,
+                kind,
+            };
+
+            match self.lex {
+                Lex::Between => {
+                    let b = chunk[i];
+                    i += 1;
+                    match b {
+                        b' ' | b'\n' | b'\r' | b'\t' => {}
+                        b'{' | b'}' | b'[' | b']' | b':' | b',' => self
+                            .parser
+                            .token(Token::Punct(b), &mut emit)
+                            .map_err(fail)?,
+                        b'"' => match string_end(&chunk[i..], false) {
+                            Ok(len) => {
+                                let s = &chunk[i..i + len];
+                                i += len + 1;
+                                self.parser.token(Token::Str(s), &mut emit).map_err(fail)?;
+                            }
+                            Err(escape) => {
+                                self.carry(&chunk[i..]);
+                                self.lex = Lex::Str { escape };
+                                self.start = at;
+                                i = chunk.len();
+                            }
+                        },
+                        _ => match bare_end(&chunk[i - 1..]) {
+                            Some(len) => {
+                                let s = &chunk[i - 1..i - 1 + len];
+                                i += len - 1;
+                                self.parser.token(Token::Bare(s), &mut emit).map_err(fail)?;
+                            }
+                            None => {
+                                self.carry(&chunk[i - 1..]);
+                                self.lex = Lex::Bare;
+                                self.start = at;
+                                i = chunk.len();
+                            }
+                        },
+                    }
+                }
+                Lex::Str { escape } => match string_end(&chunk[i..], escape) {
+                    Ok(len) => {
+                        self.carry(&chunk[i..i + len]);
+                        i += len + 1;
+                        self.lex = Lex::Between;
+                        let token = match self.truncated {
+                            false => Token::Str(&self.scratch),
+                            true => Token::TooLong,
+                        };
+                        self.parser.token(token, &mut emit).map_err(fail_carried)?;
+                        self.scratch.clear();
+                        self.truncated = false;
+                    }
+                    Err(escape) => {
+                        self.carry(&chunk[i..]);
+                        self.lex = Lex::Str { escape };
+                        i = chunk.len();
+                    }
+                },
+                Lex::Bare => match bare_end(&chunk[i..]) {
+                    Some(len) => {
+                        self.carry(&chunk[i..i + len]);
+                        i += len;
+                        self.lex = Lex::Between;
+                        let token = match self.truncated {
+                            false => Token::Bare(&self.scratch),
+                            true => Token::TooLong,
+                        };
+                        self.parser.token(token, &mut emit).map_err(fail_carried)?;
+                        self.scratch.clear();
+                        self.truncated = false;
+                    }
+                    None => {
+                        self.carry(&chunk[i..]);
+                        i = chunk.len();
+                    }
+                },
+            }
+        }
+        Ok(())
+    }
+
+    fn carry(&mut self, bytes: &[u8]) {
+        if self.scratch.extend_from_slice(bytes).is_err() {
+            self.truncated = true;
+        }
+    }
+}
+
+// Length of the string up to its closing quote, or whether the input ends
+// inside an escape
+fn string_end(buf: &[u8], mut escape: bool) -> Result<usize, bool> {
+    for (i, &b) in buf.iter().enumerate() {
+        if escape {
+            escape = false;
+        } else if b == b'\\' {
+            escape = true;
+        } else if b == b'"' {
+            return Ok(i);
+        }
+    }
+    Err(escape)
+}
+
+// Length of a number or literal, if its end is in `buf`
+fn bare_end(buf: &[u8]) -> Option<usize> {
+    buf.iter().position(|b| {
+        matches!(
+            b,
+            b' ' | b'\n' | b'\r' | b'\t' | b',' | b':' | b'{' | b'}' | b'[' | b']' | b'"'
+        )
+    })
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Field {
+    UpdateId,
+    FirstUpdateId,
+    PrevUpdateId,
+    EventTime,
+    TxTime,
+    Symbol,
+    Bids,
+    Asks,
+    Ignore,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Start,
+    KeyOrEnd,
+    Key,
+    Colon(Field),
+    Value(Field),
+    Skip(u32),
+    Next,
+    // Inside a side's array; the flag is true for bids
+    LevelOrEnd(bool),
+    Level(bool),
+    Price(bool),
+    Comma(bool),
+    Qty(bool),
+    Close(bool),
+    LevelNext(bool),
+}
+
+struct Parser<const P: usize, const Q: usize> {
+    state: State,
+    header: DepthHeader<'static>,
+    update_id: Option<u64>,
+    symbol: heapless::String<SCRATCH>,
+    has_symbol: bool,
+    price: Fp<P>,
+}
+
+impl<const P: usize, const Q: usize> Parser<P, Q> {
+    fn new() -> Self {
+        Parser {
+            state: State::Start,
+            header: DepthHeader::default(),
+            update_id: None,
+            symbol: heapless::String::new(),
+            has_symbol: false,
+            price: Fp::ZERO,
+        }
+    }
+
+    fn token<F>(&mut self, token: Token, emit: &mut F) -> Result<(), StreamErrorKind>
+    where
+        F: FnMut(DepthEvent<'_, P, Q>),
+    {
+        use StreamErrorKind::*;
+
+        self.state = match (self.state, token) {
+            (State::Start, Token::Punct(b'{')) => {
+                self.header = DepthHeader::default();
+                self.update_id = None;
+                self.has_symbol = false;
+                State::KeyOrEnd
+            }
+            (State::KeyOrEnd, Token::Punct(b'}')) | (State::Next, Token::Punct(b'}')) => {
+                self.header.update_id = self.update_id.ok_or(MissingUpdateId)?;
+                let header = DepthHeader {
+                    symbol: self.has_symbol.then_some(self.symbol.as_str()),
+                    ..self.header
+                };
+                emit(DepthEvent::End(header));
+                State::Start
+            }
+            (State::KeyOrEnd | State::Key, Token::Str(key)) => State::Colon(field(key)),
+            (State::KeyOrEnd | State::Key, Token::TooLong) => State::Colon(Field::Ignore),
+            (State::Colon(f), Token::Punct(b':')) => State::Value(f),
+            (State::Next, Token::Punct(b',')) => State::Key,
+
+            (State::Value(Field::Bids), Token::Punct(b'[')) => State::LevelOrEnd(true),
+            (State::Value(Field::Asks), Token::Punct(b'[')) => State::LevelOrEnd(false),
+            (State::Value(Field::Symbol), Token::Str(s)) => {
+                let s = std::str::from_utf8(s).map_err(|_| Syntax)?;
+                self.symbol.clear();
+                self.symbol.push_str(s).map_err(|_| TooLong)?;
+                self.has_symbol = true;
+                State::Next
+            }
+            (State::Value(Field::Ignore), Token::Punct(b'{' | b'[')) => State::Skip(1),
+            (State::Value(Field::Ignore), Token::Str(_) | Token::Bare(_) | Token::TooLong) => {
+                State::Next
+            }
+            (State::Value(f), Token::Bare(s)) => {
+                let v = Some(uint(s).ok_or(Integer)?);
+                match f {
+                    Field::UpdateId => self.update_id = v,
+                    Field::FirstUpdateId => self.header.first_update_id = v,
+                    Field::PrevUpdateId => self.header.prev_update_id = v,
+                    Field::EventTime => self.header.event_time = v,
+                    Field::TxTime => self.header.tx_time = v,
+                    _ => return Err(Syntax),
+                }
+                State::Next
+            }
+            (State::Value(_), Token::TooLong) => return Err(TooLong),
+
+            (State::Skip(depth), Token::Punct(b'{' | b'[')) => State::Skip(depth + 1),
+            (State::Skip(1), Token::Punct(b'}' | b']')) => State::Next,
+            (State::Skip(depth), Token::Punct(b'}' | b']')) => State::Skip(depth - 1),
+            (State::Skip(depth), _) => State::Skip(depth),
+
+            (State::LevelOrEnd(_), Token::Punct(b']')) => State::Next,
+            (State::LevelOrEnd(bids) | State::Level(bids), Token::Punct(b'[')) => {
+                State::Price(bids)
+            }
+            (State::Price(bids), Token::Str(s)) => {
+                self.price = Fp::parse(s).map_err(Price)?;
+                State::Comma(bids)
+            }
+            (State::Comma(bids), Token::Punct(b',')) => State::Qty(bids),
+            (State::Qty(bids), Token::Str(s)) => {
+                let qty = Fp::parse(s).map_err(Qty)?;
+                let side = if bids { Side::Bid } else { Side::Ask };
+                emit(DepthEvent::Level(side, self.price, qty));
+                State::Close(bids)
+            }
+            (State::Price(_) | State::Qty(_), Token::TooLong) => return Err(TooLong),
+            (State::Close(bids), Token::Punct(b']')) => State::LevelNext(bids),
+            (State::LevelNext(bids), Token::Punct(b',')) => State::Level(bids),
+            (State::LevelNext(_), Token::Punct(b']')) => State::Next,
+
+            _ => return Err(Syntax),
+        };
+        Ok(())
+    }
+}
+
+fn field(key: &[u8]) -> Field {
+    match key {
+        b"lastUpdateId" | b"u" => Field::UpdateId,
+        b"U" => Field::FirstUpdateId,
+        b"pu" => Field::PrevUpdateId,
+        b"E" => Field::EventTime,
+        b"T" => Field::TxTime,
+        b"s" => Field::Symbol,
+        b"bids" | b"b" => Field::Bids,
+        b"asks" | b"a" => Field::Asks,
+        _ => Field::Ignore,
+    }
+}
+
+fn uint(buf: &[u8]) -> Option<u64> {
+    if buf.is_empty() {
+        return None;
+    }
+    buf.iter().try_fold(0u64, |v, &b| {
+        if !b.is_ascii_digit() {
+            return None;
+        }
+        v.checked_mul(10)?.checked_add((b - b'0') as u64)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binance::{Depth, DepthUpdate};
+
+    const SNAPSHOT: &[u8] = br#"{"lastUpdateId":7488596254027,"E":1746977566204,"T":1746977566198,"bids":[["104276.90","10.023"],["104276.80","0.032"]],"asks":[["104277.00","15.341"]]}"#;
+
+    const UPDATE: &[u8] = br#"{"e":"depthUpdate","E":1571889248277,"T":1571889248276,"s":"BTCUSDT","U":390497796,"u":390497878,"pu":390497794,"b":[["7403.89","0.002"],["7403.90","0.000"]],"a":[]}"#;
+
+    // Feeds `buf` cut into pieces of `size` bytes and renders the events
+    fn events(buf: &[u8], size: usize) -> Vec<String> {
+        let mut stream = DepthStream::<2, 3>::new();
+        let mut out = Vec::new();
+        for chunk in buf.chunks(size) {
+            stream
+                .feed(chunk, |e| out.push(format!("{:?}", e)))
+                .unwrap();
+        }
+        assert!(stream.is_idle());
+        out
+    }
+
+    #[test]
+    fn test_stream_chunks() {
+        let depth = Depth::<2, 3>::from_serde_json(SNAPSHOT).unwrap();
+        let update = DepthUpdate::<2, 3>::from_serde_json(UPDATE).unwrap();
+
+        let mut expected = Vec::new();
+        let level = |side, (p, q)| format!("{:?}", DepthEvent::<2, 3>::Level(side, p, q));
+        expected.extend(depth.bids.iter().map(|&l| level(Side::Bid, l)));
+        expected.extend(depth.asks.iter().map(|&l| level(Side::Ask, l)));
+        expected.push(format!(
+            "{:?}",
+            DepthEvent::<2, 3>::End(DepthHeader {
+                update_id: depth.last_update_id,
+                event_time: depth.event_time,
+                tx_time: depth.tx_time,
+                ..Default::default()
+            })
+        ));
+        expected.extend(update.bids.iter().map(|&l| level(Side::Bid, l)));
+        expected.push(format!(
+            "{:?}",
+            DepthEvent::<2, 3>::End(DepthHeader {
+                update_id: update.final_update_id,
+                first_update_id: Some(update.first_update_id),
+                prev_update_id: update.prev_final_update_id,
+                event_time: Some(update.event_time),
+                tx_time: update.tx_time,
+                symbol: Some(update.symbol),
+            })
+        ));
+
+        // Two messages back to back, cut at every possible size
+        let buf = [SNAPSHOT, b"\n", UPDATE, b"\r\n"].concat();
+        for size in 1..=buf.len() {
+            assert_eq!(events(&buf, size), expected, "chunk size {}", size);
+        }
+    }
+
+    #[test]
+    fn test_stream_partial() {
+        let mut stream = DepthStream::<2, 3>::new();
+        let mut levels = 0;
+        let mut ends = 0;
+        let (head, tail) = SNAPSHOT.split_at(100);
+
+        // Both bids are out before the message ends, the second cut mid-price
+        stream
+            .feed(head, |e| match e {
+                DepthEvent::Level(..) => levels += 1,
+                DepthEvent::End(_) => ends += 1,
+            })
+            .unwrap();
+        assert_eq!((levels, ends), (1, 0));
+        assert!(!stream.is_idle());
+
+        stream
+            .feed(tail, |e| match e {
+                DepthEvent::Level(..) => levels += 1,
+                DepthEvent::End(_) => ends += 1,
+            })
+            .unwrap();
+        assert_eq!((levels, ends), (3, 1));
+        assert!(stream.is_idle());
+    }
+
+    #[test]
+    fn test_stream_skip_and_errors() {
+        // Unknown fields of any shape, including a string longer than the
+        // scratch buffer cut across chunks
+        let long = "x".repeat(200);
+        let buf = format!(
+            r#"{{"x":{{"y":[1,{{"z":"]"}}],"w":null}},"v":"{long}","u":5,"n":-1.5e3,"b":[],"a":[["1.00","1.000"]]}}"#
+        );
+        for size in [1, 7, 64, buf.len()] {
+            let events = events(buf.as_bytes(), size);
+            assert_eq!(events.len(), 2);
+            assert!(events[1].contains("update_id: 5"));
+        }
+
+        let error = |buf: &[u8]| {
+            let mut stream = DepthStream::<2, 3>::new();
+            stream.feed(buf, |_| {}).unwrap_err()
+        };
+        assert_eq!(error(br#"{"u":1,"b":[["1.0","1.000"]]}"#).offset, 13);
+        assert!(matches!(
+            error(br#"{"u":1,"b":[["1.00","x"]]}"#).kind,
+            StreamErrorKind::Qty(_)
+        ));
+        assert_eq!(error(br#"{"u":1.5}"#).kind, StreamErrorKind::Integer);
+        assert_eq!(error(br#"{"E":1}"#).kind, StreamErrorKind::MissingUpdateId);
+        assert_eq!(
+            error(br#"{"u":1,"b":[["1.00"]]}"#).kind,
+            StreamErrorKind::Syntax
+        );
+        assert_eq!(error(br#"[]"#).kind, StreamErrorKind::Syntax);
+
+        let price = format!(r#"{{"u":1,"b":[["{}","1.000"]]}}"#, "1".repeat(100));
+        let mut stream = DepthStream::<2, 3>::new();
+        let (head, tail) = price.as_bytes().split_at(20);
+        stream.feed(head, |_| {}).unwrap();
+        let err = stream.feed(tail, |_| {}).unwrap_err();
+        assert_eq!((err.offset, err.kind), (13, StreamErrorKind::TooLong));
+
+        // A reset stream picks up at the next message
+        stream.reset();
+        let mut ends = 0;
+        stream
+            .feed(UPDATE, |e| ends += matches!(e, DepthEvent::End(_)) as usize)
+            .unwrap();
+        assert_eq!(ends, 1);
+        assert!(stream.is_idle());
+
+        // Offsets still count every byte fed, the failed chunk included
+        let err = stream.feed(br#"{"u":1,"b":[["1.0","1.000"]]}"#, |_| {});
+        let fed = (price.len() + UPDATE.len()) as u64;
+        assert_eq!(err.unwrap_err().offset, fed + 13);
+    }
+}
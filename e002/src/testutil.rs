@@ -0,0 +1,32 @@
+use crate::fp::Fp;
+use crate::strategy::{BookUpdate, MarketEvent, TradeTick};
+use e001::orderbook::Side;
+
+// Market event builders shared by the tests, at two decimals for prices and
+// three for quantities. Update ids follow the event time.
+
+pub(crate) fn book(
+    time: u64,
+    snapshot: bool,
+    bids: &[(Fp<2>, Fp<3>)],
+    asks: &[(Fp<2>, Fp<3>)],
+) -> MarketEvent<2, 3> {
+    MarketEvent::Book(BookUpdate {
+        time,
+        snapshot,
+        first_update_id: time,
+        update_id: time,
+        prev_update_id: None,
+        bids: bids.to_vec(),
+        asks: asks.to_vec(),
+    })
+}
+
+pub(crate) fn trade(time: u64, price: Fp<2>, qty: Fp<3>, aggressor: Side) -> MarketEvent<2, 3> {
+    MarketEvent::Trade(TradeTick {
+        time,
+        price,
+        qty,
+        aggressor,
+    })
+}
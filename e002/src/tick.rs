@@ -0,0 +1,149 @@
+use crate::fp::{Fp, Rounding, round_up};
+use e001::orderbook::Side;
+
+/// Tick and lot arithmetic. Everything stays in the raw integer domain, so a
+/// snapped value is always an exact multiple of the step.
+///
+/// Steps must be positive, all methods panic otherwise.
+impl<const DECIMALS: usize> Fp<DECIMALS> {
+    /// Snaps to a multiple of `step` using `rounding`.
+    #[inline]
+    pub fn round_to(self, step: Self, rounding: Rounding) -> Self {
+        assert!(step.is_positive(), "step must be positive");
+
+        let divisor = step.raw().unsigned_abs();
+        let magnitude = self.raw().unsigned_abs();
+        let (quot, rem) = (magnitude / divisor, magnitude % divisor);
+        let quot = quot + round_up(rounding, self.is_negative(), quot, rem, Some(divisor)) as u128;
+
+        let value = (quot * divisor) as i128;
+        Fp::from_raw(if self.is_negative() { -value } else { value })
+    }
+
+    /// Largest multiple of `step` not above `self`.
+    #[inline]
+    pub fn floor_to(self, step: Self) -> Self {
+        self.round_to(step, Rounding::ToNegativeInfinity)
+    }
+
+    /// Smallest multiple of `step` not below `self`.
+    #[inline]
+    pub fn ceil_to(self, step: Self) -> Self {
+        self.round_to(step, Rounding::ToPositiveInfinity)
+    }
+
+    #[inline]
+    pub fn is_multiple_of(self, step: Self) -> bool {
+        assert!(step.is_positive(), "step must be positive");
+        self.raw() % step.raw() == 0
+    }
+
+    /// Number of whole ticks from `self` to `other`, negative when `other`
+    /// is below. Partial ticks are truncated towards zero.
+    #[inline]
+    pub fn ticks_to(self, other: Self, tick: Self) -> i128 {
+        assert!(tick.is_positive(), "tick must be positive");
+        (other.raw() - self.raw()) / tick.raw()
+    }
+
+    /// Moves the value by `ticks` whole ticks, up when positive.
+    #[inline]
+    pub fn offset_ticks(self, ticks: i128, tick: Self) -> Self {
+        assert!(tick.is_positive(), "tick must be positive");
+        Fp::from_raw(self.raw() + ticks * tick.raw())
+    }
+
+    /// Snaps a quote price onto the tick grid without making it more
+    /// aggressive: bids round down, asks round up.
+    #[inline]
+    pub fn snap_price(self, side: Side, tick: Self) -> Self {
+        match side {
+            Side::Bid => self.floor_to(tick),
+            Side::Ask => self.ceil_to(tick),
+        }
+    }
+
+    /// Snaps a quantity down to the lot step, never sizing up an order.
+    #[inline]
+    pub fn snap_qty(self, lot: Self) -> Self {
+        self.round_to(lot, Rounding::ToZero)
+    }
+
+    /// Moves a quote price `ticks` ticks away from the touch: bids go down,
+    /// asks go up. Negative `ticks` make the price more aggressive.
+    #[inline]
+    pub fn passive_ticks(self, side: Side, ticks: i128, tick: Self) -> Self {
+        match side {
+            Side::Bid => self.offset_ticks(-ticks, tick),
+            Side::Ask => self.offset_ticks(ticks, tick),
+        }
+    }
+
+    /// Ticks between `self` and `other` counted away from the touch, so a
+    /// positive result means `other` is the more passive price for `side`.
+    #[inline]
+    pub fn ticks_behind(self, side: Side, other: Self, tick: Self) -> i128 {
+        match side {
+            Side::Bid => other.ticks_to(self, tick),
+            Side::Ask => self.ticks_to(other, tick),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fp;
+
+    const TICK: Fp<2> = fp!(0.10);
+
+    #[test]
+    fn test_tick_rounding() {
+        let price: Fp<2> = fp!(104276.97);
+
+        assert_eq!(price.floor_to(TICK), fp!(104276.90));
+        assert_eq!(price.ceil_to(TICK), fp!(104277.00));
+        assert_eq!(
+            price.round_to(TICK, Rounding::MidpointNearestEven),
+            fp!(104277.00)
+        );
+        assert_eq!(fp!(-1.25; 2).floor_to(TICK), fp!(-1.30));
+        assert_eq!(fp!(-1.25; 2).ceil_to(TICK), fp!(-1.20));
+
+        // Already on the grid
+        assert_eq!(fp!(104276.90; 2).floor_to(TICK), fp!(104276.90));
+        assert_eq!(fp!(104276.90; 2).ceil_to(TICK), fp!(104276.90));
+        assert!(fp!(104276.90; 2).is_multiple_of(TICK));
+        assert!(!price.is_multiple_of(TICK));
+
+        assert_eq!(fp!(0.0379; 4).snap_qty(fp!(0.001)), fp!(0.0370));
+    }
+
+    #[test]
+    fn test_tick_counting() {
+        let bid: Fp<2> = fp!(104276.90);
+        let ask: Fp<2> = fp!(104277.30);
+
+        assert_eq!(bid.ticks_to(ask, TICK), 4);
+        assert_eq!(ask.ticks_to(bid, TICK), -4);
+        assert_eq!(bid.offset_ticks(4, TICK), ask);
+        assert_eq!(ask.offset_ticks(-4, TICK), bid);
+    }
+
+    #[test]
+    fn test_tick_sides() {
+        let price: Fp<2> = fp!(100.05);
+
+        assert_eq!(price.snap_price(Side::Bid, TICK), fp!(100.00));
+        assert_eq!(price.snap_price(Side::Ask, TICK), fp!(100.10));
+
+        assert_eq!(fp!(100.00; 2).passive_ticks(Side::Bid, 2, TICK), fp!(99.80));
+        assert_eq!(
+            fp!(100.00; 2).passive_ticks(Side::Ask, 2, TICK),
+            fp!(100.20)
+        );
+
+        assert_eq!(fp!(100.00; 2).ticks_behind(Side::Bid, fp!(99.80), TICK), 2);
+        assert_eq!(fp!(100.00; 2).ticks_behind(Side::Ask, fp!(99.80), TICK), -2);
+    }
+}
@@ -0,0 +1,550 @@
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+use std::str::FromStr;
+
+use crate::fp::{Fp, ParseFpError, Rounding, parse_decimal, parse_exp, rescale, write_decimal};
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer};
+
+// Dimensioned wrappers around `Fp`. Each unit only adds and subtracts with
+// itself and scales by a plain `Fp`; crossing units goes through the few
+// products that mean something:
+//
+//   Price * Qty = Notional,  Notional / Price = Qty,  Notional / Qty = Price
+//
+// Prices and quantities usually differ in precision, so a `Notional` keeps
+// both: `Price<P> * Qty<Q>` is a `Notional<P, Q>` at `P + Q` decimals.
+//
+// Parsing, `Deserialize` and formatting of all three are the ones of `Fp`,
+// at `P + Q` decimals for `Notional`, and the wrappers order and hash the
+// same, so they work as book keys and sizes.
+
+macro_rules! unit {
+    ($(#[$doc:meta])* $name:ident) => {
+        $(#[$doc])*
+        #[derive(Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name<const N: usize>(Fp<N>);
+
+        impl<const N: usize> $name<N> {
+            pub const ZERO: Self = $name(Fp::ZERO);
+            pub const ONE: Self = $name(Fp::ONE);
+            pub const MIN: Self = $name(Fp::MIN);
+            pub const MAX: Self = $name(Fp::MAX);
+
+            #[inline]
+            pub const fn new(value: Fp<N>) -> Self {
+                $name(value)
+            }
+
+            #[inline]
+            pub const fn fp(self) -> Fp<N> {
+                self.0
+            }
+
+            #[inline]
+            pub const fn from_raw(raw: i128) -> Self {
+                $name(Fp::from_raw(raw))
+            }
+
+            #[inline]
+            pub const fn raw(self) -> i128 {
+                self.0.raw()
+            }
+
+            #[inline]
+            pub const fn from_int(int: i128) -> Self {
+                $name(Fp::from_int(int))
+            }
+
+            #[inline]
+            pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseFpError> {
+                Fp::from_bytes::<N>(buf).map($name)
+            }
+
+            #[inline]
+            pub fn abs(self) -> Self {
+                $name(self.0.abs())
+            }
+
+            #[inline]
+            pub fn is_zero(self) -> bool {
+                self.0.is_zero()
+            }
+
+            #[inline]
+            pub fn is_positive(self) -> bool {
+                self.0.is_positive()
+            }
+
+            #[inline]
+            pub fn is_negative(self) -> bool {
+                self.0.is_negative()
+            }
+
+            #[inline]
+            pub fn checked_add(self, rhs: Self) -> Option<Self> {
+                self.0.checked_add(rhs.0).map($name)
+            }
+
+            #[inline]
+            pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+                self.0.checked_sub(rhs.0).map($name)
+            }
+        }
+
+        impl<const N: usize> From<Fp<N>> for $name<N> {
+            fn from(value: Fp<N>) -> Self {
+                $name(value)
+            }
+        }
+
+        impl<const N: usize> From<$name<N>> for Fp<N> {
+            fn from(value: $name<N>) -> Self {
+                value.0
+            }
+        }
+
+        impl<const N: usize> Add for $name<N> {
+            type Output = Self;
+
+            fn add(self, rhs: Self) -> Self {
+                $name(self.0 + rhs.0)
+            }
+        }
+
+        impl<const N: usize> Sub for $name<N> {
+            type Output = Self;
+
+            fn sub(self, rhs: Self) -> Self {
+                $name(self.0 - rhs.0)
+            }
+        }
+
+        impl<const N: usize> Neg for $name<N> {
+            type Output = Self;
+
+            fn neg(self) -> Self {
+                $name(-self.0)
+            }
+        }
+
+        impl<const N: usize> AddAssign for $name<N> {
+            fn add_assign(&mut self, rhs: Self) {
+                self.0 = self.0 + rhs.0;
+            }
+        }
+
+        impl<const N: usize> SubAssign for $name<N> {
+            fn sub_assign(&mut self, rhs: Self) {
+                self.0 = self.0 - rhs.0;
+            }
+        }
+
+        // Scaling by a dimensionless factor
+        impl<const N: usize> Mul<Fp<N>> for $name<N> {
+            type Output = Self;
+
+            fn mul(self, rhs: Fp<N>) -> Self {
+                $name(self.0 * rhs)
+            }
+        }
+
+        impl<const N: usize> Div<Fp<N>> for $name<N> {
+            type Output = Self;
+
+            fn div(self, rhs: Fp<N>) -> Self {
+                $name(self.0 / rhs)
+            }
+        }
+
+        // The ratio of two values of one unit has none
+        impl<const N: usize> Div for $name<N> {
+            type Output = Fp<N>;
+
+            fn div(self, rhs: Self) -> Fp<N> {
+                self.0 / rhs.0
+            }
+        }
+
+        impl<const N: usize> Sum for $name<N> {
+            fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+                $name(iter.map(|v| v.0).sum())
+            }
+        }
+
+        impl<'a, const N: usize> Sum<&'a $name<N>> for $name<N> {
+            fn sum<I: Iterator<Item = &'a Self>>(iter: I) -> Self {
+                $name(iter.map(|v| v.0).sum())
+            }
+        }
+
+        impl<const N: usize> FromStr for $name<N> {
+            type Err = ParseFpError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Fp::from_str(s).map($name)
+            }
+        }
+
+        impl<'de, const N: usize> Deserialize<'de> for $name<N> {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                Fp::deserialize(deserializer).map($name)
+            }
+        }
+
+        impl<const N: usize> fmt::Display for $name<N> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Display::fmt(&self.0, f)
+            }
+        }
+
+        impl<const N: usize> fmt::Debug for $name<N> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Display::fmt(&self.0, f)
+            }
+        }
+    };
+}
+
+unit!(
+    /// Price of one unit of the base asset, in the quote asset.
+    ///
+    /// ```
+    /// use e002::units::{Notional, Price, Qty};
+    ///
+    /// let notional: Notional<2, 3> = Price::from_int(50) * Qty::from_int(2);
+    /// assert_eq!(notional / Price::from_int(50), Qty::from_int(2));
+    /// ```
+    ///
+    /// Mixing units outside those products does not compile:
+    ///
+    /// ```compile_fail
+    /// use e002::units::{Price, Qty};
+    ///
+    /// let _ = Price::<2>::ONE + Qty::<2>::ONE;
+    /// ```
+    Price
+);
+unit!(
+    /// Amount of the base asset
+    Qty
+);
+
+/// Value in the quote asset, price times quantity, for prices at `P` and
+/// quantities at `Q` decimals. Held exactly at `P + Q` decimals, so a
+/// product never loses digits; `to_fp` brings it to another precision.
+#[derive(Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Notional<const P: usize, const Q: usize>(i128);
+
+impl<const P: usize, const Q: usize> Notional<P, Q> {
+    pub const DECIMALS: usize = P + Q;
+    pub const ZERO: Self = Notional(0);
+
+    #[inline]
+    pub const fn from_raw(raw: i128) -> Self {
+        Notional(raw)
+    }
+
+    #[inline]
+    pub const fn raw(self) -> i128 {
+        self.0
+    }
+
+    #[inline]
+    pub const fn from_int(int: i128) -> Self {
+        Notional(int * 10i128.pow(Self::DECIMALS as u32))
+    }
+
+    /// From a value at any precision, rounded if it has more digits
+    #[inline]
+    pub fn from_fp<const M: usize>(value: Fp<M>, rounding: Rounding) -> Self {
+        Notional(rescale(value.raw(), M, Self::DECIMALS, rounding))
+    }
+
+    /// See `Fp::from_exp_bytes`
+    pub fn from_exp_bytes(buf: &[u8]) -> Result<Self, ParseFpError> {
+        parse_exp(buf, Self::DECIMALS, None).map(Notional)
+    }
+
+    /// See `Fp::from_exp_bytes_rounded`
+    pub fn from_exp_bytes_rounded(buf: &[u8], rounding: Rounding) -> Result<Self, ParseFpError> {
+        parse_exp(buf, Self::DECIMALS, Some(rounding)).map(Notional)
+    }
+
+    /// At `M` decimals, rounded if that drops digits
+    #[inline]
+    pub fn to_fp<const M: usize>(self, rounding: Rounding) -> Fp<M> {
+        Fp::from_raw(rescale(self.0, Self::DECIMALS, M, rounding))
+    }
+
+    #[inline]
+    pub fn abs(self) -> Self {
+        Notional(self.0.abs())
+    }
+
+    #[inline]
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    #[inline]
+    pub fn is_positive(self) -> bool {
+        self.0 > 0
+    }
+
+    #[inline]
+    pub fn is_negative(self) -> bool {
+        self.0 < 0
+    }
+
+    #[inline]
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        self.0.checked_add(rhs.0).map(Notional)
+    }
+
+    #[inline]
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.0.checked_sub(rhs.0).map(Notional)
+    }
+}
+
+impl<const P: usize, const Q: usize> Add for Notional<P, Q> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Notional(self.0 + rhs.0)
+    }
+}
+
+impl<const P: usize, const Q: usize> Sub for Notional<P, Q> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Notional(self.0 - rhs.0)
+    }
+}
+
+impl<const P: usize, const Q: usize> Neg for Notional<P, Q> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Notional(-self.0)
+    }
+}
+
+impl<const P: usize, const Q: usize> AddAssign for Notional<P, Q> {
+    fn add_assign(&mut self, rhs: Self) {
+        self.0 += rhs.0;
+    }
+}
+
+impl<const P: usize, const Q: usize> SubAssign for Notional<P, Q> {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.0 -= rhs.0;
+    }
+}
+
+impl<const P: usize, const Q: usize> Sum for Notional<P, Q> {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        Notional(iter.map(|v| v.0).sum())
+    }
+}
+
+impl<'a, const P: usize, const Q: usize> Sum<&'a Notional<P, Q>> for Notional<P, Q> {
+    fn sum<I: Iterator<Item = &'a Self>>(iter: I) -> Self {
+        Notional(iter.map(|v| v.0).sum())
+    }
+}
+
+/// Parses as `Fp` does, at `P + Q` decimals
+impl<const P: usize, const Q: usize> FromStr for Notional<P, Q> {
+    type Err = ParseFpError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let scale = 10i128.pow(Self::DECIMALS as u32);
+        parse_decimal(s.as_bytes(), Self::DECIMALS, scale).map(Notional)
+    }
+}
+
+impl<'de, const P: usize, const Q: usize> Deserialize<'de> for Notional<P, Q> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct NotionalVisitor<const P: usize, const Q: usize>;
+
+        impl<'de, const P: usize, const Q: usize> Visitor<'de> for NotionalVisitor<P, Q> {
+            type Value = Notional<P, Q>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(
+                    f,
+                    "a decimal string with exactly {} fractional digits",
+                    P + Q
+                )
+            }
+
+            fn visit_borrowed_str<E>(self, s: &'de str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                s.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(NotionalVisitor::<P, Q>)
+    }
+}
+
+impl<const P: usize, const Q: usize> fmt::Display for Notional<P, Q> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut buf = [0u8; Fp::<0>::MAX_STR_LEN];
+        let len = write_decimal(false, self.0.unsigned_abs(), Self::DECIMALS, &mut buf);
+        // Only ASCII digits and '.' were written
+        let s = std::str::from_utf8(&buf[..len]).unwrap();
+        f.pad_integral(self.0 >= 0, "", s)
+    }
+}
+
+impl<const P: usize, const Q: usize> fmt::Debug for Notional<P, Q> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl<const P: usize, const Q: usize> Mul<Qty<Q>> for Price<P> {
+    type Output = Notional<P, Q>;
+
+    fn mul(self, rhs: Qty<Q>) -> Notional<P, Q> {
+        Notional(self.raw() * rhs.raw())
+    }
+}
+
+impl<const P: usize, const Q: usize> Mul<Price<P>> for Qty<Q> {
+    type Output = Notional<P, Q>;
+
+    fn mul(self, rhs: Price<P>) -> Notional<P, Q> {
+        Notional(self.raw() * rhs.raw())
+    }
+}
+
+/// Truncates like `Fp` division
+impl<const P: usize, const Q: usize> Div<Price<P>> for Notional<P, Q> {
+    type Output = Qty<Q>;
+
+    fn div(self, rhs: Price<P>) -> Qty<Q> {
+        Qty::from_raw(self.0 / rhs.raw())
+    }
+}
+
+/// Truncates like `Fp` division
+impl<const P: usize, const Q: usize> Div<Qty<Q>> for Notional<P, Q> {
+    type Output = Price<P>;
+
+    fn div(self, rhs: Qty<Q>) -> Price<P> {
+        Price::from_raw(self.0 / rhs.raw())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use e001::btree::BTreeBook;
+    use e001::hybrid::HybridBook;
+    use e001::orderbook::{OrderBook, Side};
+
+    #[test]
+    fn test_units() {
+        let price: Price<2> = "104276.90".parse().unwrap();
+        let qty: Qty<2> = "0.25".parse().unwrap();
+
+        let notional = price * qty;
+        assert_eq!(notional, qty * price);
+        assert_eq!(notional.to_string(), "26069.2250");
+        assert_eq!(
+            Notional::<2, 2>::from_int(100) / Price::from_int(50),
+            Qty::from_int(2)
+        );
+        assert_eq!(
+            Notional::<2, 2>::from_int(100) / Qty::from_int(4),
+            Price::from_int(25)
+        );
+
+        assert_eq!(price - Price::from_int(104276), "0.90".parse().unwrap());
+        assert_eq!(
+            price / Price::from_int(2),
+            "52138.45".parse::<Fp<2>>().unwrap()
+        );
+        assert_eq!(qty * Fp::from_int(4), Qty::ONE);
+        assert_eq!(
+            [qty, qty, qty].iter().sum::<Qty<2>>(),
+            "0.75".parse().unwrap()
+        );
+
+        // Formatting is the one of `Fp`
+        assert_eq!(format!("{:>10.1}", price), "  104276.9");
+        assert_eq!(format!("{:?}", Qty::<3>::from_raw(-1500)), "-1.500");
+
+        // Mixed precisions keep every digit
+        let qty: Qty<3> = "0.013".parse().unwrap();
+        let notional = price * qty;
+        assert_eq!(notional.to_string(), "1355.59970");
+        assert_eq!(notional, "1355.59970".parse().unwrap());
+        assert_eq!(notional / price, qty);
+        assert_eq!(notional / qty, price);
+        assert_eq!(
+            notional.to_fp::<2>(Rounding::ToPositiveInfinity),
+            "1355.60".parse().unwrap()
+        );
+        assert_eq!(
+            notional.to_fp::<2>(Rounding::ToZero),
+            "1355.59".parse().unwrap()
+        );
+        assert_eq!(
+            Notional::<2, 3>::from_fp::<2>("10.00".parse().unwrap(), Rounding::ToZero),
+            Notional::from_int(10)
+        );
+        assert_eq!(format!("{:>12}", -notional), " -1355.59970");
+        assert_eq!(
+            [notional, notional].iter().sum::<Notional<2, 3>>(),
+            "2711.19940".parse().unwrap()
+        );
+
+        // Parsed and deserialized as `Fp` is, exponents included
+        assert_eq!("1.3555997e3".parse::<Notional<2, 3>>(), Ok(notional));
+        assert!("1e-6".parse::<Notional<2, 3>>().is_err());
+        assert_eq!(
+            Notional::<2, 3>::from_exp_bytes_rounded(b"1e-6", Rounding::AwayFromZero),
+            Ok(Notional::from_raw(1))
+        );
+        let parsed: Vec<Notional<2, 3>> =
+            serde_json::from_slice(br#"["1355.59970","1.3555997E3","-0.00001"]"#).unwrap();
+        assert_eq!(parsed, [notional, notional, Notional::from_raw(-1)]);
+    }
+
+    #[test]
+    fn test_units_in_books() {
+        let json = br#"[["104276.90","10.023"],["104276.80","0.032"]]"#;
+        let levels: Vec<(Price<2>, Qty<3>)> = serde_json::from_slice(json).unwrap();
+
+        let mut book = HybridBook::<Price<2>, Qty<3>>::new();
+        let mut tree = BTreeBook::<Price<2>, Qty<3>>::new();
+        for &(price, qty) in &levels {
+            book.insert(Side::Bid, price, qty);
+            tree.insert(Side::Bid, price, qty);
+        }
+        book.insert(Side::Ask, Price::from_int(104277), Qty::ONE);
+
+        let (bid, ask) = book.top();
+        assert_eq!(bid, Some((&levels[0].0, &levels[0].1)));
+        assert_eq!(*ask.unwrap().0 - *bid.unwrap().0, "0.10".parse().unwrap());
+        assert_eq!(
+            tree.bids().map(|(_, q)| *q).sum::<Qty<3>>(),
+            "10.055".parse().unwrap()
+        );
+    }
+}